@@ -0,0 +1,322 @@
+//! Random BOOM ASTs for property testing and fuzzing
+//!
+//! Generated functions declare a fixed pool of locals up front, then copy
+//! values into them and pass them to builtins, so passes are given well-formed
+//! input mixing static, runtime and unknown sizes. Runtime sizes are always the
+//! integer parameter `n`, as a local sized by itself (or by a bitvector) is not
+//! well-formed. Blocks may branch back to themselves to exercise fixpoint
+//! iteration.
+//!
+//! Identifiers are interned while generating, so the interner must be
+//! initialized first.
+
+use {
+    crate::boom::{
+        control_flow::{ControlFlowBlock, Terminator},
+        Ast, Bit, Expression, FunctionDefinition, FunctionSignature, Literal, Operation, Parameter,
+        Size, Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared},
+    proptest::{collection::vec, prelude::*, sample::select},
+};
+
+/// Locals declared by every generated function
+const LOCALS: &[&str] = &["a", "b", "c", "d", "e", "f"];
+
+/// Integer parameter of every generated function, used for runtime sizes
+const LENGTH: &str = "n";
+
+/// Functions called by generated statements, builtins with handlers and one
+/// without
+const FUNCTIONS: &[&str] = &[
+    "sail_assert",
+    "Zeros",
+    "Ones",
+    "ZeroExtend",
+    "SignExtend",
+    "truncate",
+    "sail_unsigned",
+    "sail_signed",
+    "not_vec",
+    "and_vec",
+    "or_vec",
+    "xor_vec",
+    "add_bits",
+    "sub_bits",
+    "shiftl",
+    "shiftr",
+    "bitvector_concat",
+    "vector_subrange",
+    "bitvector_access",
+    "replicate_bits",
+    "eq_vec",
+    "neq_vec",
+    "lt_vec",
+    "sgteq_vec",
+    "undefined_bitvector",
+    "g",
+];
+
+type Unary = fn(Shared<Value>) -> Operation;
+type Binary = fn(Shared<Value>, Shared<Value>) -> Operation;
+
+const UNARY: &[Unary] = &[Operation::Not, Operation::Complement];
+
+const BINARY: &[Binary] = &[
+    Operation::Equal,
+    Operation::NotEqual,
+    Operation::LessThan,
+    Operation::LessThanOrEqual,
+    Operation::GreaterThan,
+    Operation::GreaterThanOrEqual,
+    Operation::Subtract,
+    Operation::Add,
+    Operation::Or,
+    Operation::Multiply,
+    Operation::And,
+    Operation::Xor,
+    Operation::Divide,
+    Operation::LeftShift,
+    Operation::RightShift,
+    Operation::ArithmeticRightShift,
+    Operation::RotateRight,
+    Operation::RotateLeft,
+];
+
+fn local() -> impl Strategy<Value = InternedString> {
+    select(LOCALS).prop_map(InternedString::from)
+}
+
+fn literal() -> impl Strategy<Value = Literal> {
+    prop_oneof![
+        3 => (-8i64..=128).prop_map(|int| Literal::Int(int.into())),
+        2 => vec(select(&[Bit::Zero, Bit::One][..]), 1..=16).prop_map(Literal::Bits),
+        1 => any::<bool>().prop_map(Literal::Bool),
+    ]
+}
+
+fn operation(operand: BoxedStrategy<Value>) -> impl Strategy<Value = Operation> {
+    let operand = operand.prop_map(Shared::new);
+
+    prop_oneof![
+        1 => (select(UNARY), operand.clone()).prop_map(|(op, value)| op(value)),
+        4 => (select(BINARY), operand.clone(), operand.clone())
+            .prop_map(|(op, lhs, rhs)| op(lhs, rhs)),
+        1 => (operand, any::<Type>())
+            .prop_map(|(value, typ)| Operation::Cast(value, Shared::new(typ))),
+    ]
+}
+
+impl Arbitrary for Size {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        prop_oneof![
+            2 => (0usize..=128).prop_map(Size::Static),
+            2 => Just(Size::Unknown),
+            1 => Just(()).prop_map(|()| Size::Runtime(Shared::new(Value::Identifier(LENGTH.into())))),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for Type {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        prop_oneof![
+            4 => any::<Size>().prop_map(|size| Type::Bits { size }),
+            2 => prop_oneof![Just(Size::Static(64)), Just(Size::Unknown)]
+                .prop_map(|size| Type::Integer { size, range: None }),
+            1 => Just(Type::Bool),
+            1 => Just(Type::Bit),
+            1 => Just(Type::Unit),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for Value {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        let leaf = prop_oneof![
+            local().prop_map(Value::Identifier),
+            literal().prop_map(|literal| Value::Literal(Shared::new(literal))),
+        ];
+
+        leaf.prop_recursive(3, 16, 3, |operand| {
+            operation(operand.boxed()).prop_map(Value::Operation)
+        })
+        .boxed()
+    }
+}
+
+impl Arbitrary for Operation {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        operation(any::<Value>()).boxed()
+    }
+}
+
+impl Arbitrary for Statement {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        let value = || any::<Value>().prop_map(Shared::new);
+
+        prop_oneof![
+            // redeclarations of the pool of locals
+            1 => (local(), any::<Type>()).prop_map(|(name, typ)| Statement::TypeDeclaration {
+                name,
+                typ: Shared::new(typ),
+            }),
+            4 => (local(), value()).prop_map(|(ident, value)| Statement::Copy {
+                expression: Expression::Identifier(ident),
+                value,
+            }),
+            4 => (
+                proptest::option::weighted(0.9, local()),
+                select(FUNCTIONS),
+                vec(value(), 0..=3),
+            )
+                .prop_map(|(destination, name, arguments)| Statement::FunctionCall {
+                    expression: destination.map(Expression::Identifier),
+                    name: name.into(),
+                    arguments,
+                }),
+        ]
+        .boxed()
+    }
+}
+
+impl Arbitrary for FunctionDefinition {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        let declarations = vec(any::<Type>(), LOCALS.len()).prop_map(|types| {
+            LOCALS
+                .iter()
+                .zip(types)
+                .map(|(name, typ)| {
+                    Shared::new(Statement::TypeDeclaration {
+                        name: (*name).into(),
+                        typ: Shared::new(typ),
+                    })
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // the statements of each block, and whether it may branch back to itself
+        let blocks = vec(
+            (
+                vec(any::<Statement>().prop_map(Shared::new), 0..8),
+                proptest::option::weighted(0.2, local()),
+            ),
+            1..=4,
+        );
+
+        ("[a-z]{1,8}", declarations, blocks)
+            .prop_map(|(name, declarations, blocks)| {
+                let cfg = blocks
+                    .iter()
+                    .map(|_| ControlFlowBlock::new())
+                    .collect::<Vec<_>>();
+
+                for (index, (block, (statements, condition))) in cfg.iter().zip(blocks).enumerate()
+                {
+                    block.set_statements(statements);
+
+                    let terminator = match (cfg.get(index + 1), condition) {
+                        (Some(next), Some(condition)) => Terminator::Conditional {
+                            condition: Value::Identifier(condition),
+                            target: block.clone(),
+                            fallthrough: next.clone(),
+                        },
+                        (Some(next), None) => Terminator::Unconditional {
+                            target: next.clone(),
+                        },
+                        (None, _) => Terminator::Return(None),
+                    };
+                    block.set_terminator(terminator);
+                }
+
+                let entry_block = cfg[0].clone();
+                let mut statements = declarations;
+                statements.extend(entry_block.statements());
+                entry_block.set_statements(statements);
+
+                FunctionDefinition {
+                    signature: FunctionSignature {
+                        name: name.as_str().into(),
+                        parameters: Shared::new(vec![Parameter {
+                            name: LENGTH.into(),
+                            typ: Shared::new(Type::Integer {
+                                size: Size::Static(64),
+                                range: None,
+                            }),
+                            is_ref: false,
+                        }]),
+                        return_type: Shared::new(Type::Unit),
+                    },
+                    entry_block,
+                }
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Ast {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): ()) -> Self::Strategy {
+        vec(any::<FunctionDefinition>(), 1..=4)
+            .prop_map(|functions| Ast {
+                functions: functions
+                    .into_iter()
+                    .map(|def| (def.signature.name, def))
+                    .collect(),
+                ..Default::default()
+            })
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{resolve_bitvectors::ResolveBitvectors, Pass},
+            test_utils::init,
+            Ast,
+        },
+        common::shared::Shared,
+        proptest::{
+            prelude::*,
+            test_runner::{Config, TestRunner},
+        },
+    };
+
+    #[test]
+    fn resolve_bitvectors_terminates() {
+        init();
+
+        // the interner must be initialized before generating, so the runner is
+        // driven directly rather than through `proptest!`
+        let mut runner = TestRunner::new(Config::with_cases(1000));
+        runner
+            .run(&any::<Ast>(), |ast| {
+                ResolveBitvectors::default().run(Shared::new(ast));
+                Ok(())
+            })
+            .unwrap();
+    }
+}
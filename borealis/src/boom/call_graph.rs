@@ -0,0 +1,286 @@
+//! Callers and callees of the functions in an AST
+//!
+//! Edges are found from every `Statement::FunctionCall`, including those in
+//! nested bodies. Calls to functions without a definition, such as builtins,
+//! are included as callees, but those functions are not part of any strongly
+//! connected component.
+
+use {
+    crate::boom::{
+        visitor::{Visitor, Walkable},
+        Ast, Statement,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap, HashSet},
+};
+
+/// Call graph of the functions defined in an AST
+#[derive(Debug)]
+pub struct CallGraph {
+    callees: HashMap<InternedString, HashSet<InternedString>>,
+    callers: HashMap<InternedString, HashSet<InternedString>>,
+    /// Strongly connected components of the defined functions, callees before
+    /// callers
+    components: Vec<Vec<InternedString>>,
+    /// Functions that can call themselves, directly or through other functions
+    recursive: HashSet<InternedString>,
+}
+
+impl CallGraph {
+    /// Builds the call graph of an AST
+    pub fn build(ast: &Ast) -> Self {
+        let mut callees = HashMap::<InternedString, HashSet<InternedString>>::default();
+        let mut callers = HashMap::<InternedString, HashSet<InternedString>>::default();
+
+        for (name, def) in &ast.functions {
+            let mut calls = Calls::default();
+            calls.visit_function_definition(def);
+
+            for callee in &calls.0 {
+                callers.entry(*callee).or_default().insert(*name);
+            }
+            callees.insert(*name, calls.0);
+        }
+
+        let mut graph = Self {
+            callees,
+            callers,
+            components: vec![],
+            recursive: HashSet::default(),
+        };
+        graph.compute_components();
+
+        graph
+    }
+
+    /// Gets the functions called by `function`
+    pub fn callees(&self, function: InternedString) -> HashSet<InternedString> {
+        self.callees.get(&function).cloned().unwrap_or_default()
+    }
+
+    /// Gets the functions calling `function`
+    pub fn callers(&self, function: InternedString) -> HashSet<InternedString> {
+        self.callers.get(&function).cloned().unwrap_or_default()
+    }
+
+    /// Determines whether `function` can call itself, directly or through
+    /// other functions
+    pub fn is_recursive(&self, function: InternedString) -> bool {
+        self.recursive.contains(&function)
+    }
+
+    /// Gets the strongly connected components of the defined functions, each
+    /// component before any component calling into it
+    ///
+    /// Functions in a component of more than one function are mutually
+    /// recursive.
+    pub fn strongly_connected_components(&self) -> &[Vec<InternedString>] {
+        &self.components
+    }
+
+    /// Finds the strongly connected components and recursive functions
+    fn compute_components(&mut self) {
+        let mut tarjan = Tarjan {
+            callees: &self.callees,
+            indices: HashMap::default(),
+            lowlinks: HashMap::default(),
+            stack: vec![],
+            on_stack: HashSet::default(),
+            work: vec![],
+            components: vec![],
+        };
+
+        let mut functions = self.callees.keys().copied().collect::<Vec<_>>();
+        functions.sort_by_key(ToString::to_string);
+        for function in functions {
+            tarjan.run(function);
+        }
+
+        self.components = tarjan.components;
+        self.recursive = self
+            .components
+            .iter()
+            .filter(|component| {
+                component.len() > 1 || self.callees[&component[0]].contains(&component[0])
+            })
+            .flatten()
+            .copied()
+            .collect();
+    }
+}
+
+/// State of Tarjan's algorithm, run iteratively as call chains can be long
+struct Tarjan<'a> {
+    callees: &'a HashMap<InternedString, HashSet<InternedString>>,
+    indices: HashMap<InternedString, usize>,
+    lowlinks: HashMap<InternedString, usize>,
+    stack: Vec<InternedString>,
+    on_stack: HashSet<InternedString>,
+    /// Functions being visited, with the callees left to visit
+    work: Vec<(InternedString, Vec<InternedString>)>,
+    components: Vec<Vec<InternedString>>,
+}
+
+impl Tarjan<'_> {
+    /// Finds the components reachable from `root` not already found
+    fn run(&mut self, root: InternedString) {
+        if self.indices.contains_key(&root) {
+            return;
+        }
+
+        self.visit(root);
+
+        while let Some((function, remaining)) = self.work.last_mut() {
+            let function = *function;
+
+            if let Some(callee) = remaining.pop() {
+                if !self.indices.contains_key(&callee) {
+                    self.visit(callee);
+                } else if self.on_stack.contains(&callee) {
+                    self.lower(function, self.indices[&callee]);
+                }
+                continue;
+            }
+
+            self.work.pop();
+
+            if let Some((caller, _)) = self.work.last() {
+                let caller = *caller;
+                self.lower(caller, self.lowlinks[&function]);
+            }
+
+            if self.lowlinks[&function] == self.indices[&function] {
+                let mut component = vec![];
+                loop {
+                    let member = self.stack.pop().unwrap();
+                    self.on_stack.remove(&member);
+                    component.push(member);
+                    if member == function {
+                        break;
+                    }
+                }
+                component.reverse();
+                self.components.push(component);
+            }
+        }
+    }
+
+    fn visit(&mut self, function: InternedString) {
+        let index = self.indices.len();
+        self.indices.insert(function, index);
+        self.lowlinks.insert(function, index);
+        self.stack.push(function);
+        self.on_stack.insert(function);
+
+        // defined callees only, popped in name order
+        let mut callees = self.callees[&function]
+            .iter()
+            .copied()
+            .filter(|callee| self.callees.contains_key(callee))
+            .collect::<Vec<_>>();
+        callees.sort_by_key(|callee| std::cmp::Reverse(callee.to_string()));
+        self.work.push((function, callees));
+    }
+
+    fn lower(&mut self, function: InternedString, lowlink: usize) {
+        let current = self.lowlinks[&function];
+        self.lowlinks.insert(function, current.min(lowlink));
+    }
+}
+
+/// Collects the names of called functions
+#[derive(Default)]
+struct Calls(HashSet<InternedString>);
+
+impl Visitor for Calls {
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        if let Statement::FunctionCall { name, .. } = &*node.get() {
+            self.0.insert(*name);
+        }
+
+        node.get().walk(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{call_graph::CallGraph, test_utils::*},
+        common::{intern::InternedString, HashSet},
+        pretty_assertions::assert_eq,
+    };
+
+    fn names(names: &[&str]) -> HashSet<InternedString> {
+        names
+            .iter()
+            .map(|name| InternedString::from(*name))
+            .collect()
+    }
+
+    fn components(graph: &CallGraph) -> Vec<Vec<String>> {
+        graph
+            .strongly_connected_components()
+            .iter()
+            .map(|component| component.iter().map(ToString::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn linear_chain() {
+        init();
+
+        let ast = ast([
+            function("f", vec![call("a", "g", vec![])]),
+            function("g", vec![call("b", "h", vec![])]),
+            function("h", vec![call("c", "Zeros", vec![int(4)])]),
+        ]);
+        let graph = CallGraph::build(&ast.get());
+
+        assert_eq!(graph.callees("f".into()), names(&["g"]));
+        assert_eq!(graph.callees("h".into()), names(&["Zeros"]));
+        assert_eq!(graph.callers("h".into()), names(&["g"]));
+        assert_eq!(graph.callers("f".into()), names(&[]));
+        assert_eq!(graph.callers("Zeros".into()), names(&["h"]));
+
+        for name in ["f", "g", "h"] {
+            assert!(!graph.is_recursive(name.into()));
+        }
+        assert_eq!(components(&graph), [["h"], ["g"], ["f"]]);
+    }
+
+    #[test]
+    fn self_recursive() {
+        init();
+
+        let ast = ast([
+            function("f", vec![call("a", "f", vec![]), call("b", "g", vec![])]),
+            function("g", vec![]),
+        ]);
+        let graph = CallGraph::build(&ast.get());
+
+        assert!(graph.is_recursive("f".into()));
+        assert!(!graph.is_recursive("g".into()));
+        assert_eq!(graph.callers("f".into()), names(&["f"]));
+        assert_eq!(components(&graph), [["g"], ["f"]]);
+    }
+
+    #[test]
+    fn two_function_cycle() {
+        init();
+
+        let ast = ast([
+            function("e", vec![call("a", "f", vec![])]),
+            function("f", vec![call("a", "g", vec![])]),
+            function("g", vec![call("b", "f", vec![])]),
+        ]);
+        let graph = CallGraph::build(&ast.get());
+
+        assert!(!graph.is_recursive("e".into()));
+        assert!(graph.is_recursive("f".into()));
+        assert!(graph.is_recursive("g".into()));
+        assert_eq!(graph.callers("f".into()), names(&["e", "g"]));
+        assert_eq!(
+            components(&graph),
+            [vec!["f".to_owned(), "g".to_owned()], vec!["e".to_owned()]]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! Successors, predecessors and dominators of the blocks in a function
+//!
+//! Block parents are maintained as terminators are set, but may include blocks
+//! no longer reachable from the entry block. Edges here are derived only from
+//! the terminators of reachable blocks.
+
+use {
+    crate::boom::{control_flow::ControlFlowBlock, FunctionDefinition},
+    common::{identifiable::Id, HashMap, HashSet},
+};
+
+/// Control flow graph of the blocks reachable from a function's entry block
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+    entry: ControlFlowBlock,
+    blocks: HashMap<Id, ControlFlowBlock>,
+    successors: HashMap<Id, Vec<Id>>,
+    predecessors: HashMap<Id, Vec<Id>>,
+    /// Immediate dominator of each block, the entry block dominates itself
+    immediate_dominators: HashMap<Id, Id>,
+}
+
+impl ControlFlowGraph {
+    /// Builds the graph of a function
+    pub fn build(def: &FunctionDefinition) -> Self {
+        let entry = def.entry_block.clone();
+
+        let blocks = entry
+            .iter()
+            .map(|block| (block.id(), block))
+            .collect::<HashMap<_, _>>();
+
+        let mut successors = HashMap::<Id, Vec<Id>>::default();
+        let mut predecessors = HashMap::<Id, Vec<Id>>::default();
+
+        for (id, block) in &blocks {
+            predecessors.entry(*id).or_default();
+
+            let targets = block
+                .terminator()
+                .targets()
+                .iter()
+                .map(ControlFlowBlock::id)
+                .collect::<Vec<_>>();
+
+            for target in &targets {
+                let target_predecessors = predecessors.entry(*target).or_default();
+                if !target_predecessors.contains(id) {
+                    target_predecessors.push(*id);
+                }
+            }
+
+            successors.insert(*id, targets);
+        }
+
+        let mut graph = Self {
+            entry,
+            blocks,
+            successors,
+            predecessors,
+            immediate_dominators: HashMap::default(),
+        };
+        graph.compute_dominators();
+
+        graph
+    }
+
+    /// Gets the entry block
+    pub fn entry(&self) -> ControlFlowBlock {
+        self.entry.clone()
+    }
+
+    /// Gets all blocks reachable from the entry block
+    pub fn blocks(&self) -> Vec<ControlFlowBlock> {
+        self.entry.iter().collect()
+    }
+
+    /// Gets the blocks `block` may jump to
+    pub fn successors(&self, block: &ControlFlowBlock) -> Vec<ControlFlowBlock> {
+        self.lookup(self.successors.get(&block.id()))
+    }
+
+    /// Gets the reachable blocks that may jump to `block`
+    pub fn predecessors(&self, block: &ControlFlowBlock) -> Vec<ControlFlowBlock> {
+        self.lookup(self.predecessors.get(&block.id()))
+    }
+
+    /// Gets the closest block that every path from the entry block to `block`
+    /// passes through, `None` for the entry block or unreachable blocks
+    pub fn immediate_dominator(&self, block: &ControlFlowBlock) -> Option<ControlFlowBlock> {
+        if block.id() == self.entry.id() {
+            return None;
+        }
+
+        self.immediate_dominators
+            .get(&block.id())
+            .map(|id| self.blocks[id].clone())
+    }
+
+    /// Gets the blocks dominating `block`, starting with `block` itself and
+    /// ending with the entry block
+    pub fn dominators(&self, block: &ControlFlowBlock) -> Vec<ControlFlowBlock> {
+        if !self.blocks.contains_key(&block.id()) {
+            return vec![];
+        }
+
+        let mut dominators = vec![block.clone()];
+        while let Some(dominator) = self.immediate_dominator(dominators.last().unwrap()) {
+            dominators.push(dominator);
+        }
+
+        dominators
+    }
+
+    /// Determines whether every path from the entry block to `block` passes
+    /// through `dominator`
+    pub fn dominates(&self, dominator: &ControlFlowBlock, block: &ControlFlowBlock) -> bool {
+        self.dominators(block)
+            .iter()
+            .any(|b| b.id() == dominator.id())
+    }
+
+    /// Gets all blocks reachable from the entry block, each before its
+    /// successors other than along back edges
+    pub fn blocks_in_reverse_postorder(&self) -> Vec<ControlFlowBlock> {
+        self.reverse_postorder()
+            .iter()
+            .map(|id| self.blocks[id].clone())
+            .collect()
+    }
+
+    fn lookup(&self, ids: Option<&Vec<Id>>) -> Vec<ControlFlowBlock> {
+        ids.into_iter()
+            .flatten()
+            .map(|id| self.blocks[id].clone())
+            .collect()
+    }
+
+    /// Gets the reachable blocks in reverse postorder
+    fn reverse_postorder(&self) -> Vec<Id> {
+        let mut visited = HashSet::default();
+        let mut postorder = vec![];
+
+        // (block, index of the next successor to visit)
+        let mut stack = vec![(self.entry.id(), 0)];
+        visited.insert(self.entry.id());
+
+        while let Some((id, index)) = stack.pop() {
+            match self.successors[&id].get(index) {
+                Some(successor) => {
+                    stack.push((id, index + 1));
+                    if visited.insert(*successor) {
+                        stack.push((*successor, 0));
+                    }
+                }
+                None => postorder.push(id),
+            }
+        }
+
+        postorder.reverse();
+        postorder
+    }
+
+    /// Computes immediate dominators using the iterative algorithm from "A
+    /// Simple, Fast Dominance Algorithm" (Cooper, Harvey and Kennedy)
+    fn compute_dominators(&mut self) {
+        let order = self.reverse_postorder();
+        let index = order
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect::<HashMap<_, _>>();
+
+        let entry = self.entry.id();
+        let mut idoms = HashMap::<Id, Id>::default();
+        idoms.insert(entry, entry);
+
+        let intersect = |idoms: &HashMap<Id, Id>, mut a: Id, mut b: Id| {
+            while a != b {
+                while index[&a] > index[&b] {
+                    a = idoms[&a];
+                }
+                while index[&b] > index[&a] {
+                    b = idoms[&b];
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for id in order.iter().skip(1) {
+                let mut processed = self.predecessors[id]
+                    .iter()
+                    .filter(|predecessor| idoms.contains_key(predecessor));
+
+                let Some(first) = processed.next() else {
+                    continue;
+                };
+
+                let idom = processed.fold(*first, |idom, predecessor| {
+                    intersect(&idoms, idom, *predecessor)
+                });
+
+                if idoms.get(id) != Some(&idom) {
+                    idoms.insert(*id, idom);
+                    changed = true;
+                }
+            }
+        }
+
+        self.immediate_dominators = idoms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            control_flow::{graph::ControlFlowGraph, ControlFlowBlock, Terminator},
+            test_utils::*,
+            FunctionDefinition, Value,
+        },
+        common::{identifiable::Id, HashSet},
+        pretty_assertions::assert_eq,
+    };
+
+    fn ids(blocks: Vec<ControlFlowBlock>) -> HashSet<Id> {
+        blocks.iter().map(ControlFlowBlock::id).collect()
+    }
+
+    fn set<const N: usize>(blocks: [&ControlFlowBlock; N]) -> HashSet<Id> {
+        blocks.into_iter().map(ControlFlowBlock::id).collect()
+    }
+
+    fn branch(from: &ControlFlowBlock, target: &ControlFlowBlock, fallthrough: &ControlFlowBlock) {
+        from.set_terminator(Terminator::Conditional {
+            condition: Value::Identifier("c".into()),
+            target: target.clone(),
+            fallthrough: fallthrough.clone(),
+        });
+    }
+
+    fn jump(from: &ControlFlowBlock, target: &ControlFlowBlock) {
+        from.set_terminator(Terminator::Unconditional {
+            target: target.clone(),
+        });
+    }
+
+    fn entry(def: &FunctionDefinition) -> ControlFlowBlock {
+        def.entry_block.clone()
+    }
+
+    #[test]
+    fn straight_line() {
+        init();
+
+        let def = function("f", vec![]);
+        let (a, b) = (ControlFlowBlock::new(), ControlFlowBlock::new());
+        jump(&entry(&def), &a);
+        jump(&a, &b);
+
+        let cfg = ControlFlowGraph::build(&def);
+
+        assert_eq!(cfg.blocks().len(), 3);
+        assert_eq!(ids(cfg.successors(&a)), set([&b]));
+        assert!(cfg.predecessors(&entry(&def)).is_empty());
+        assert_eq!(ids(cfg.dominators(&b)), set([&entry(&def), &a, &b]));
+    }
+
+    #[test]
+    fn diamond() {
+        init();
+
+        let def = function("f", vec![]);
+        let (left, right, merge) = (
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+        );
+        branch(&entry(&def), &left, &right);
+        jump(&left, &merge);
+        jump(&right, &merge);
+
+        let cfg = ControlFlowGraph::build(&def);
+
+        assert_eq!(ids(cfg.predecessors(&merge)), set([&left, &right]));
+        assert_eq!(
+            cfg.immediate_dominator(&merge).map(|block| block.id()),
+            Some(entry(&def).id())
+        );
+        assert!(cfg.dominates(&entry(&def), &left));
+        assert!(!cfg.dominates(&left, &merge));
+        assert!(cfg.immediate_dominator(&entry(&def)).is_none());
+    }
+
+    #[test]
+    fn loop_() {
+        init();
+
+        let def = function("f", vec![]);
+        let (header, body, exit) = (
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+        );
+        jump(&entry(&def), &header);
+        branch(&header, &body, &exit);
+        jump(&body, &header);
+
+        let cfg = ControlFlowGraph::build(&def);
+
+        assert_eq!(ids(cfg.predecessors(&header)), set([&entry(&def), &body]));
+        assert_eq!(
+            cfg.immediate_dominator(&exit).map(|block| block.id()),
+            Some(header.id())
+        );
+        assert_eq!(
+            cfg.immediate_dominator(&body).map(|block| block.id()),
+            Some(header.id())
+        );
+    }
+}
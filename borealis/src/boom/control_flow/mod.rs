@@ -17,8 +17,9 @@ use {
         identifiable::Id,
         intern::InternedString,
         shared::{Shared, Weak},
-        HashSet,
+        HashMap, HashSet,
     },
+    serde::{Deserialize, Deserializer, Serialize, Serializer},
     std::{
         fmt::{self, Display, Formatter},
         hash::{DefaultHasher, Hash, Hasher},
@@ -28,6 +29,7 @@ use {
 
 pub mod builder;
 pub mod dot;
+pub mod graph;
 pub mod util;
 
 /// Node in a control flow graph, contains a basic block of statements and a
@@ -173,6 +175,124 @@ impl ControlFlowBlock {
     }
 }
 
+/// Serialized form of a control flow graph, blocks refer to each other by index
+/// with the entry block first
+#[derive(Serialize, Deserialize)]
+struct SerializedGraph {
+    blocks: Vec<SerializedBlock>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedBlock {
+    label: Option<InternedString>,
+    statements: Vec<Shared<Statement>>,
+    terminator: SerializedTerminator,
+}
+
+#[derive(Serialize, Deserialize)]
+enum SerializedTerminator {
+    Return(Option<Value>),
+    Panic(Vec<Shared<Value>>),
+    Conditional {
+        condition: Value,
+        target: usize,
+        fallthrough: usize,
+    },
+    Unconditional {
+        target: usize,
+    },
+}
+
+/// Serializes the whole graph reachable from a block, which is restored as the
+/// entry block when deserializing
+impl Serialize for ControlFlowBlock {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let blocks = self.iter().collect::<Vec<_>>();
+        let indices = blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| (block.id(), index))
+            .collect::<HashMap<_, _>>();
+        let index = |block: &ControlFlowBlock| indices[&block.id()];
+
+        SerializedGraph {
+            blocks: blocks
+                .iter()
+                .map(|block| SerializedBlock {
+                    label: block.label(),
+                    statements: block.statements(),
+                    terminator: match block.terminator() {
+                        Terminator::Return(value) => SerializedTerminator::Return(value),
+                        Terminator::Panic(values) => SerializedTerminator::Panic(values),
+                        Terminator::Conditional {
+                            condition,
+                            target,
+                            fallthrough,
+                        } => SerializedTerminator::Conditional {
+                            condition,
+                            target: index(&target),
+                            fallthrough: index(&fallthrough),
+                        },
+                        Terminator::Unconditional { target } => {
+                            SerializedTerminator::Unconditional {
+                                target: index(&target),
+                            }
+                        }
+                    },
+                })
+                .collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ControlFlowBlock {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let SerializedGraph { blocks: serialized } = SerializedGraph::deserialize(deserializer)?;
+
+        let blocks = serialized
+            .iter()
+            .map(|_| ControlFlowBlock::new())
+            .collect::<Vec<_>>();
+        let block = |index: usize| {
+            blocks.get(index).cloned().ok_or_else(|| {
+                serde::de::Error::custom(format!("block index {index} out of range"))
+            })
+        };
+
+        for (
+            SerializedBlock {
+                label,
+                statements,
+                terminator,
+            },
+            current,
+        ) in serialized.into_iter().zip(&blocks)
+        {
+            current.set_label(label);
+            current.set_statements(statements);
+            current.set_terminator(match terminator {
+                SerializedTerminator::Return(value) => Terminator::Return(value),
+                SerializedTerminator::Panic(values) => Terminator::Panic(values),
+                SerializedTerminator::Conditional {
+                    condition,
+                    target,
+                    fallthrough,
+                } => Terminator::Conditional {
+                    condition,
+                    target: block(target)?,
+                    fallthrough: block(fallthrough)?,
+                },
+                SerializedTerminator::Unconditional { target } => Terminator::Unconditional {
+                    target: block(target)?,
+                },
+            });
+        }
+
+        block(0)
+    }
+}
+
 /// Non-owning reference to a `ControlFlowBlock`
 #[derive(Debug)]
 pub struct ControlFlowBlockWeak(Weak<ControlFlowBlockInner>);
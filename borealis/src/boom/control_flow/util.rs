@@ -2,10 +2,10 @@
 
 use {
     crate::boom::{
-        control_flow::{dot, ControlFlowBlock},
-        Expression, Statement, Value,
+        control_flow::{dot, ControlFlowBlock, Terminator},
+        Expression, NamedType, NamedValue, Operation, Size, Statement, Type, Value,
     },
-    common::{intern::InternedString, shared::Shared, HashSet},
+    common::{intern::InternedString, shared::Shared, HashMap, HashSet},
     itertools::Itertools,
     log::trace,
     std::{
@@ -118,6 +118,234 @@ impl ControlFlowBlock {
             // this is technically possible, should probably be handled properly if it does occur
             .expect("found multiple statements matching target")
     }
+
+    /// Duplicates a control flow graph, the copy has its own blocks,
+    /// statements, values and types so can be mutated without affecting the
+    /// original
+    ///
+    /// Passes such as `CopyPropagation` rewrite values in place, so sharing a
+    /// value between a function and its copy would change both.
+    pub fn deep_clone(&self) -> ControlFlowBlock {
+        let blocks = self.iter().collect::<Vec<_>>();
+
+        let clones = blocks
+            .iter()
+            .map(|block| {
+                let clone = ControlFlowBlock::new();
+                clone.set_label(block.label());
+                clone.set_statements(block.statements().iter().map(clone_statement).collect());
+                (block.id(), clone)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let map = |block: &ControlFlowBlock| clones[&block.id()].clone();
+
+        for block in &blocks {
+            let terminator = match block.terminator() {
+                Terminator::Return(value) => Terminator::Return(value.map(clone_owned_value)),
+                Terminator::Panic(values) => {
+                    Terminator::Panic(values.iter().map(clone_value).collect())
+                }
+                Terminator::Conditional {
+                    condition,
+                    target,
+                    fallthrough,
+                } => Terminator::Conditional {
+                    condition: clone_owned_value(condition),
+                    target: map(&target),
+                    fallthrough: map(&fallthrough),
+                },
+                Terminator::Unconditional { target } => Terminator::Unconditional {
+                    target: map(&target),
+                },
+            };
+
+            map(block).set_terminator(terminator);
+        }
+
+        map(self)
+    }
+}
+
+/// Copies a statement, including its values, any type it declares and nested
+/// statements
+fn clone_statement(statement: &Shared<Statement>) -> Shared<Statement> {
+    let statement = statement.get();
+
+    match &*statement {
+        Statement::TypeDeclaration { name, typ } => Statement::TypeDeclaration {
+            name: *name,
+            typ: clone_type(typ),
+        },
+        Statement::Copy { expression, value } => Statement::Copy {
+            expression: expression.clone(),
+            value: clone_value(value),
+        },
+        Statement::FunctionCall {
+            expression,
+            name,
+            arguments,
+        } => Statement::FunctionCall {
+            expression: expression.clone(),
+            name: *name,
+            arguments: arguments.iter().map(clone_value).collect(),
+        },
+        Statement::Jump { condition, target } => Statement::Jump {
+            condition: clone_value(condition),
+            target: *target,
+        },
+        Statement::If {
+            condition,
+            if_body,
+            else_body,
+        } => Statement::If {
+            condition: clone_value(condition),
+            if_body: if_body.iter().map(clone_statement).collect(),
+            else_body: else_body.iter().map(clone_statement).collect(),
+        },
+        Statement::Panic(values) => Statement::Panic(values.iter().map(clone_value).collect()),
+        statement @ (Statement::Label(_)
+        | Statement::Goto(_)
+        | Statement::End(_)
+        | Statement::Undefined
+        | Statement::Exit(_)
+        | Statement::Comment(_)) => statement.clone(),
+    }
+    .into()
+}
+
+/// Copies a type, including the values of runtime lengths and nested types
+fn clone_type(typ: &Shared<Type>) -> Shared<Type> {
+    let size = |size: &Size| match size {
+        Size::Runtime(length) => Size::Runtime(clone_value(length)),
+        size => size.clone(),
+    };
+    let fields = |fields: &[NamedType]| {
+        fields
+            .iter()
+            .map(|field| NamedType {
+                name: field.name,
+                typ: clone_type(&field.typ),
+            })
+            .collect()
+    };
+
+    Shared::new(match &*typ.get() {
+        Type::Integer { size: s, range } => Type::Integer {
+            size: size(s),
+            range: range.clone(),
+        },
+        Type::Bits { size: s } => Type::Bits { size: size(s) },
+        Type::Union { name, fields: f } => Type::Union {
+            name: *name,
+            fields: fields(f),
+        },
+        Type::Struct { name, fields: f } => Type::Struct {
+            name: *name,
+            fields: fields(f),
+        },
+        Type::List { element_type } => Type::List {
+            element_type: clone_type(element_type),
+        },
+        Type::Vector { element_type } => Type::Vector {
+            element_type: clone_type(element_type),
+        },
+        Type::FixedVector {
+            length,
+            element_type,
+        } => Type::FixedVector {
+            length: *length,
+            element_type: clone_type(element_type),
+        },
+        Type::Reference(typ) => Type::Reference(clone_type(typ)),
+        Type::Tuple(types) => Type::Tuple(types.iter().map(clone_type).collect()),
+        typ @ (Type::Unit
+        | Type::String
+        | Type::Bool
+        | Type::Bit
+        | Type::Real
+        | Type::Float
+        | Type::Constant(_)
+        | Type::Enum { .. }) => typ.clone(),
+    })
+}
+
+/// Copies a value, not sharing any of its operands with the original
+fn clone_value(value: &Shared<Value>) -> Shared<Value> {
+    let v = clone_value;
+    let types = |types: &[Shared<Type>]| types.iter().map(clone_type).collect();
+
+    Shared::new(match &*value.get() {
+        Value::Identifier(ident) => Value::Identifier(*ident),
+        Value::Literal(literal) => Value::Literal(Shared::new(literal.get().clone())),
+        Value::Operation(operation) => Value::Operation(match operation {
+            Operation::Not(a) => Operation::Not(v(a)),
+            Operation::Complement(a) => Operation::Complement(v(a)),
+            Operation::Cast(a, typ) => Operation::Cast(v(a), clone_type(typ)),
+            Operation::Equal(a, b) => Operation::Equal(v(a), v(b)),
+            Operation::NotEqual(a, b) => Operation::NotEqual(v(a), v(b)),
+            Operation::LessThan(a, b) => Operation::LessThan(v(a), v(b)),
+            Operation::LessThanOrEqual(a, b) => Operation::LessThanOrEqual(v(a), v(b)),
+            Operation::GreaterThan(a, b) => Operation::GreaterThan(v(a), v(b)),
+            Operation::GreaterThanOrEqual(a, b) => Operation::GreaterThanOrEqual(v(a), v(b)),
+            Operation::Subtract(a, b) => Operation::Subtract(v(a), v(b)),
+            Operation::Add(a, b) => Operation::Add(v(a), v(b)),
+            Operation::Or(a, b) => Operation::Or(v(a), v(b)),
+            Operation::Multiply(a, b) => Operation::Multiply(v(a), v(b)),
+            Operation::And(a, b) => Operation::And(v(a), v(b)),
+            Operation::Xor(a, b) => Operation::Xor(v(a), v(b)),
+            Operation::Divide(a, b) => Operation::Divide(v(a), v(b)),
+            Operation::LogicalAnd(a, b) => Operation::LogicalAnd(v(a), v(b)),
+            Operation::LogicalOr(a, b) => Operation::LogicalOr(v(a), v(b)),
+            Operation::LeftShift(a, b) => Operation::LeftShift(v(a), v(b)),
+            Operation::RightShift(a, b) => Operation::RightShift(v(a), v(b)),
+            Operation::ArithmeticRightShift(a, b) => Operation::ArithmeticRightShift(v(a), v(b)),
+            Operation::RotateRight(a, b) => Operation::RotateRight(v(a), v(b)),
+            Operation::RotateLeft(a, b) => Operation::RotateLeft(v(a), v(b)),
+        }),
+        Value::Struct { name, fields } => Value::Struct {
+            name: *name,
+            fields: fields
+                .iter()
+                .map(|field| NamedValue {
+                    name: field.name,
+                    value: v(&field.value),
+                })
+                .collect(),
+        },
+        Value::Field { value, field_name } => Value::Field {
+            value: v(value),
+            field_name: *field_name,
+        },
+        Value::CtorKind {
+            value,
+            identifier,
+            types: t,
+        } => Value::CtorKind {
+            value: v(value),
+            identifier: *identifier,
+            types: types(t),
+        },
+        Value::CtorUnwrap {
+            value,
+            identifier,
+            types: t,
+        } => Value::CtorUnwrap {
+            value: v(value),
+            identifier: *identifier,
+            types: types(t),
+        },
+        Value::Tuple(values) => Value::Tuple(values.iter().map(v).collect()),
+        Value::TupleMember { value, index } => Value::TupleMember {
+            value: v(value),
+            index: *index,
+        },
+    })
+}
+
+/// Copies a value held directly, such as by a terminator
+fn clone_owned_value(value: Value) -> Value {
+    clone_value(&Shared::new(value)).get().clone()
 }
 
 /// Finds and returns the first common child block of blocks `left` and `right`,
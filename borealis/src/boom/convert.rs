@@ -4,7 +4,7 @@ use rayon::iter::ParallelIterator;
 use {
     crate::boom::{
         self, control_flow::builder::ControlFlowGraphBuilder, Bit, FunctionSignature, NamedType,
-        Parameter, Size,
+        Parameter, Position, Size, Span, Spans,
     },
     common::{intern::InternedString, shared::Shared, HashMap},
     rayon::iter::IntoParallelIterator,
@@ -47,15 +47,14 @@ impl BoomEmitter {
     fn process_definition(&mut self, definition: &jib_ast::Definition) {
         match definition {
             jib_ast::Definition::Register(ident, typ, body) => {
+                let body = convert_body(body.as_ref(), &mut self.ast.spans);
+
                 self.ast.registers.insert(
                     ident.as_interned(),
                     (
                         convert_type(typ),
                         //  allow unknown terminators for registers
-                        ControlFlowGraphBuilder::from_statements(
-                            &convert_body(body.as_ref()),
-                            true,
-                        ),
+                        ControlFlowGraphBuilder::from_statements(&body, true),
                     ),
                 );
             }
@@ -77,6 +76,8 @@ impl BoomEmitter {
                 self.ast.definitions.push(def);
             }
             jib_ast::Definition::Let(_, bindings, body) => {
+                let body = convert_body(body.as_ref(), &mut self.ast.spans);
+
                 self.ast.definitions.push(boom::Definition::Let {
                     bindings: bindings
                         .iter()
@@ -86,10 +87,7 @@ impl BoomEmitter {
                         })
                         .collect(),
                     //  allow unknown terminators for letbinds
-                    body: ControlFlowGraphBuilder::from_statements(
-                        &convert_body(body.as_ref()),
-                        true,
-                    ),
+                    body: ControlFlowGraphBuilder::from_statements(&body, true),
                 });
             }
             jib_ast::Definition::Val(id, _, parameters, out) => {
@@ -120,7 +118,7 @@ impl BoomEmitter {
 
                 let name = name.as_interned();
 
-                let body = convert_body(body.as_ref());
+                let body = convert_body(body.as_ref(), &mut self.ast.spans);
 
                 //debug!("building new control flow graph for {name}");
                 // do not allow unknown terminators for regular functions
@@ -159,9 +157,11 @@ fn convert_type<T: Borrow<jib_ast::Type>>(typ: T) -> Shared<boom::Type> {
 
         jib_ast::Type::Lint => boom::Type::Integer {
             size: Size::Unknown,
+            range: None,
         },
         jib_ast::Type::Fint(i) => boom::Type::Integer {
             size: Size::Static(usize::try_from(*i).unwrap()),
+            range: None,
         },
 
         jib_ast::Type::Unit => boom::Type::Unit,
@@ -193,25 +193,44 @@ fn convert_type<T: Borrow<jib_ast::Type>>(typ: T) -> Shared<boom::Type> {
             element_type: (convert_type(&**typ)),
         },
         jib_ast::Type::Ref(typ) => boom::Type::Reference(convert_type(&**typ)),
+        jib_ast::Type::Tup(types) => boom::Type::Tuple(types.iter().map(convert_type).collect()),
         jib_ast::Type::Constant(_)
         | jib_ast::Type::Sbits(_)
         | jib_ast::Type::Float(_)
         | jib_ast::Type::RoundingMode
-        | jib_ast::Type::Tup(_)
         | jib_ast::Type::Poly(_) => todo!(),
     })
 }
 
-fn convert_body(instructions: &[jib_ast::Instruction]) -> Vec<Shared<boom::Statement>> {
+/// Converts a sequence of instructions, recording the span of each resulting
+/// statement
+fn convert_body(
+    instructions: &[jib_ast::Instruction],
+    spans: &mut Spans,
+) -> Vec<Shared<boom::Statement>> {
     instructions
         .iter()
-        .flat_map(|instr| convert_statement(&instr.inner))
+        .flat_map(|instr| {
+            let statements = convert_statement(&instr.inner, spans);
+
+            // statements of nested blocks already have their own, narrower, span
+            if let Some(span) = convert_location(&instr.annot.1) {
+                statements
+                    .iter()
+                    .for_each(|statement| spans.insert(statement, span.clone()));
+            }
+
+            statements
+        })
         .collect()
 }
 
-fn convert_statement(statement: &jib_ast::InstructionAux) -> Vec<Shared<boom::Statement>> {
+fn convert_statement(
+    statement: &jib_ast::InstructionAux,
+    spans: &mut Spans,
+) -> Vec<Shared<boom::Statement>> {
     if let jib_ast::InstructionAux::Block(instructions) = statement {
-        return convert_body(instructions.as_ref());
+        return convert_body(instructions.as_ref(), spans);
     }
 
     let statements = match statement {
@@ -255,8 +274,8 @@ fn convert_statement(statement: &jib_ast::InstructionAux) -> Vec<Shared<boom::St
         jib_ast::InstructionAux::If(condition, if_body, else_body, _) => {
             vec![boom::Statement::If {
                 condition: convert_value(condition),
-                if_body: convert_body(if_body.as_ref()),
-                else_body: convert_body(else_body.as_ref()),
+                if_body: convert_body(if_body.as_ref(), spans),
+                else_body: convert_body(else_body.as_ref(), spans),
             }]
         }
 
@@ -275,6 +294,28 @@ fn convert_statement(statement: &jib_ast::InstructionAux) -> Vec<Shared<boom::St
     statements.into_iter().map(Shared::new).collect()
 }
 
+/// Gets the source range of a location, `None` if it is unknown
+fn convert_location(location: &sail_ast::Location) -> Option<Span> {
+    match location {
+        sail_ast::Location::Unknown => None,
+        sail_ast::Location::Unique(_, location)
+        | sail_ast::Location::Generated(location)
+        | sail_ast::Location::Hint(_, location, _) => convert_location(location),
+        sail_ast::Location::Range(start, end) => Some(Span {
+            file: start.pos_fname,
+            start: convert_position(start),
+            end: convert_position(end),
+        }),
+    }
+}
+
+fn convert_position(position: &sailrs::types::Position) -> Position {
+    Position {
+        line: usize::try_from(position.pos_lnum).unwrap_or_default(),
+        column: usize::try_from(position.pos_cnum - position.pos_bol + 1).unwrap_or_default(),
+    }
+}
+
 fn convert_name(name: &jib_ast::Name) -> InternedString {
     match name {
         jib_ast::Name::Name(ident, _) | jib_ast::Name::Global(ident, _) => ident.as_interned(),
@@ -297,7 +338,10 @@ fn convert_expression(expression: &jib_ast::Expression) -> Option<boom::Expressi
         jib_ast::Expression::Addr(expr) => Some(boom::Expression::Address(Box::new(
             convert_expression(expr).unwrap(),
         ))),
-        jib_ast::Expression::Tuple(_, _) => todo!(),
+        jib_ast::Expression::Tuple(expression, index) => Some(boom::Expression::TupleMember {
+            expression: Box::new(convert_expression(expression).unwrap()),
+            index: usize::try_from(*index).unwrap(),
+        }),
         jib_ast::Expression::Void => None,
     }
 }
@@ -306,7 +350,9 @@ fn convert_value(value: &jib_ast::Value) -> Shared<boom::Value> {
     Shared::new(match value {
         jib_ast::Value::Id(name, _) => boom::Value::Identifier(convert_name(name)),
         jib_ast::Value::Lit(vl, _) => boom::Value::Literal(convert_literal(vl)),
-        jib_ast::Value::Tuple(_, _) => todo!(),
+        jib_ast::Value::Tuple(values, _) => {
+            boom::Value::Tuple(values.iter().map(convert_value).collect())
+        }
         jib_ast::Value::Struct(fields, jib_ast::Type::Struct(ident, _)) => boom::Value::Struct {
             name: ident.as_interned(),
             fields: fields
@@ -328,7 +374,11 @@ fn convert_value(value: &jib_ast::Value) -> Shared<boom::Value> {
             identifier: ctor.as_interned(),
             types: unifiers.iter().map(convert_type).collect(),
         },
-        jib_ast::Value::TupleMember(_, _, _) => todo!(),
+        // the first integer is the number of elements in the tuple
+        jib_ast::Value::TupleMember(value, _, index) => boom::Value::TupleMember {
+            value: convert_value(value),
+            index: usize::try_from(*index).unwrap(),
+        },
         jib_ast::Value::Call(op, values) => {
             let values = values.iter().map(convert_value).collect::<Vec<_>>();
 
@@ -5,26 +5,42 @@
 
 use {
     crate::boom::{
-        control_flow::ControlFlowBlock,
+        control_flow::{graph::ControlFlowGraph, ControlFlowBlock},
         convert::BoomEmitter,
         visitor::{Visitor, Walkable},
     },
-    common::{intern::InternedString, shared::Shared, HashMap},
+    common::{
+        intern::InternedString,
+        shared::{Shared, SharedKey},
+        HashMap, HashSet,
+    },
+    itertools::Itertools,
     kinded::Kinded,
     num_bigint::BigInt,
     rayon::iter::IntoParallelIterator,
     sailrs::jib_ast,
-    std::{fmt::Debug, ops::Add},
+    serde::{Deserialize, Serialize},
+    std::{
+        fmt::{self, Debug, Display, Formatter},
+        ops::Add,
+    },
 };
 
+#[cfg(any(test, feature = "fuzz"))]
+pub mod arbitrary;
+pub mod call_graph;
 pub mod control_flow;
 pub mod convert;
 pub mod passes;
 pub mod pretty_print;
+pub mod size_report;
+pub mod structural;
+#[cfg(test)]
+pub(crate) mod test_utils;
 pub mod visitor;
 
 /// BOOM AST
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Ast {
     /// Sequence of definitions
     pub definitions: Vec<Definition>,
@@ -32,6 +48,12 @@ pub struct Ast {
     pub registers: HashMap<InternedString, (Shared<Type>, ControlFlowBlock)>,
     /// Function definitions by identifier
     pub functions: HashMap<InternedString, FunctionDefinition>,
+    /// Source locations of statements converted from JIB
+    #[serde(skip)]
+    pub spans: Spans,
+    /// Identifiers of statements, assigned by `NumberStatements`
+    #[serde(skip)]
+    pub statement_ids: StatementIds,
 }
 
 impl Ast {
@@ -46,8 +68,116 @@ impl Ast {
     }
 }
 
+/// Range of a Sail source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub file: InternedString,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.start.line, self.start.column)
+    }
+}
+
+/// Line and column in a source file, both starting at 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Source spans of statements, by statement identity
+///
+/// Statements rewritten in place keep their span. Passes replacing a statement
+/// with new ones pass its span on with `inherit`, other statements created by
+/// passes have none. Being keyed by identity, spans are not serialized with the
+/// AST.
+#[derive(Debug, Clone, Default)]
+pub struct Spans(HashMap<SharedKey<Statement>, Span>);
+
+impl Spans {
+    /// Records the span of a statement, unless it already has one
+    pub fn insert(&mut self, statement: &Shared<Statement>, span: Span) {
+        self.0.entry(statement.clone().into()).or_insert(span);
+    }
+
+    /// Gets the span of a statement
+    pub fn get(&self, statement: &Shared<Statement>) -> Option<Span> {
+        self.0.get(&statement.clone().into()).cloned()
+    }
+
+    /// Gives a statement replacing `original` the same span
+    pub fn inherit(&mut self, original: &Shared<Statement>, replacement: &Shared<Statement>) {
+        if let Some(span) = self.get(original) {
+            self.insert(replacement, span);
+        }
+    }
+
+    /// Gives the statements of a copy of a control flow graph the spans of the
+    /// statements they were copied from
+    pub fn inherit_copy(&mut self, original: &ControlFlowBlock, copy: &ControlFlowBlock) {
+        for (original, copy) in original.iter().zip(copy.iter()) {
+            self.inherit_statements(&original.statements(), &copy.statements());
+        }
+    }
+
+    fn inherit_statements(
+        &mut self,
+        originals: &[Shared<Statement>],
+        copies: &[Shared<Statement>],
+    ) {
+        for (original, copy) in originals.iter().zip(copies) {
+            self.inherit(original, copy);
+
+            if let (
+                Statement::If {
+                    if_body, else_body, ..
+                },
+                Statement::If {
+                    if_body: copied_if_body,
+                    else_body: copied_else_body,
+                    ..
+                },
+            ) = (&*original.get(), &*copy.get())
+            {
+                self.inherit_statements(if_body, copied_if_body);
+                self.inherit_statements(else_body, copied_else_body);
+            }
+        }
+    }
+}
+
+/// Stable identifiers of statements, by statement identity
+///
+/// Identifiers are never reused, so a statement moved or rewritten in place by
+/// a pass can be recognised in diagnostics and dumps before and after it.
+#[derive(Debug, Clone, Default)]
+pub struct StatementIds {
+    ids: HashMap<SharedKey<Statement>, usize>,
+    next: usize,
+}
+
+impl StatementIds {
+    /// Gets the identifier of a statement, assigning the next one if it has
+    /// none
+    pub fn assign(&mut self, statement: &Shared<Statement>) -> usize {
+        *self.ids.entry(statement.clone().into()).or_insert_with(|| {
+            self.next += 1;
+            self.next
+        })
+    }
+
+    /// Gets the identifier of a statement
+    pub fn get(&self, statement: &Shared<Statement>) -> Option<usize> {
+        self.ids.get(&statement.clone().into()).copied()
+    }
+}
+
 /// Top-level definition of a BOOM item
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Definition {
     /// Enum definition
     Enum {
@@ -101,7 +231,7 @@ impl Walkable for Definition {
 }
 
 /// Function signature and body
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionDefinition {
     /// Function type signature
     pub signature: FunctionSignature,
@@ -144,9 +274,116 @@ impl FunctionDefinition {
             .find(|(name, ..)| *name == ident)
             .map(|(.., typ)| typ.get().clone())
     }
+
+    /// Finds the only assignment to `ident` that may reach the statement
+    /// `at`, returning the assigning statement and the assigned value
+    ///
+    /// Searches backwards from `at` within its block, then backwards through
+    /// the predecessors of every block without an assignment, so an assignment
+    /// later in a loop containing `at` is also found. Returns `None` if more
+    /// than one assignment may reach `at`, if `at` may be reached without any,
+    /// or if the assignment is not a copy, such as the result of a call.
+    pub fn get_assignment_dominating(
+        &self,
+        ident: InternedString,
+        at: &Shared<Statement>,
+    ) -> Option<(Shared<Statement>, Shared<Value>)> {
+        let (block, index) = self.entry_block.find_statement(at.clone())?;
+
+        let last_assignment = |statements: &[Shared<Statement>]| {
+            statements
+                .iter()
+                .rev()
+                .find(|statement| assigns(statement, ident))
+                .cloned()
+        };
+
+        if let Some(assignment) = last_assignment(&block.statements()[..index]) {
+            return assigned_value(&assignment, ident);
+        }
+
+        let graph = ControlFlowGraph::build(self);
+        let entry = graph.entry();
+        if block.id() == entry.id() {
+            return None;
+        }
+
+        let mut found = None::<Shared<Statement>>;
+        let mut visited = HashSet::default();
+        let mut pending = graph.predecessors(&block);
+
+        while let Some(current) = pending.pop() {
+            if !visited.insert(current.id()) {
+                continue;
+            }
+
+            match last_assignment(&current.statements()) {
+                Some(assignment) => match &found {
+                    Some(found) if !Shared::ptr_eq(found, &assignment) => return None,
+                    _ => found = Some(assignment),
+                },
+                // reached from the start of the function without an assignment
+                None if current.id() == entry.id() => return None,
+                None => pending.extend(graph.predecessors(&current)),
+            }
+        }
+
+        assigned_value(&found?, ident)
+    }
+
+    /// Finds the only assignment to `ident` that may reach the statement `at`,
+    /// otherwise the only assignment in any block
+    pub fn get_assignment_any_block(
+        &self,
+        ident: InternedString,
+        at: &Shared<Statement>,
+    ) -> Option<(Shared<Statement>, Shared<Value>)> {
+        self.get_assignment_dominating(ident, at).or_else(|| {
+            let assignment = self
+                .entry_block
+                .iter()
+                .flat_map(|block| block.statements())
+                .filter(|statement| assigns(statement, ident))
+                .exactly_one()
+                .ok()?;
+
+            assigned_value(&assignment, ident)
+        })
+    }
+}
+
+/// Determines whether a statement assigns to `ident`, by a copy or as the
+/// result of a call
+fn assigns(statement: &Shared<Statement>, ident: InternedString) -> bool {
+    match &*statement.get() {
+        Statement::Copy {
+            expression: Expression::Identifier(assign),
+            ..
+        }
+        | Statement::FunctionCall {
+            expression: Some(Expression::Identifier(assign)),
+            ..
+        } => *assign == ident,
+        _ => false,
+    }
+}
+
+/// Gets the value assigned to `ident` by a statement, if it is a copy to that
+/// local
+fn assigned_value(
+    statement: &Shared<Statement>,
+    ident: InternedString,
+) -> Option<(Shared<Statement>, Shared<Value>)> {
+    match &*statement.get() {
+        Statement::Copy {
+            expression: Expression::Identifier(assign),
+            value,
+        } if *assign == ident => Some((statement.clone(), value.clone())),
+        _ => None,
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Parameter {
     pub name: InternedString,
     pub typ: Shared<Type>,
@@ -160,7 +397,7 @@ impl Walkable for Parameter {
 }
 
 /// Function parameter and return types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FunctionSignature {
     pub name: InternedString,
     pub parameters: Shared<Vec<Parameter>>,
@@ -178,7 +415,7 @@ impl Walkable for FunctionSignature {
 }
 
 /// Name and type of a union field, struct field, or function parameter
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NamedType {
     pub name: InternedString,
     pub typ: Shared<Type>,
@@ -191,7 +428,7 @@ impl Walkable for NamedType {
 }
 
 /// Name and type of a union field, struct field, or function parameter
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NamedValue {
     pub name: InternedString,
     pub value: Shared<Value>,
@@ -204,7 +441,7 @@ impl Walkable for NamedValue {
 }
 
 /// Type
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Type {
     // removed before emitting
     Unit,
@@ -219,6 +456,8 @@ pub enum Type {
 
     Integer {
         size: Size,
+        /// Values the integer may hold, if known
+        range: Option<Range>,
     },
     Bits {
         size: Size,
@@ -255,13 +494,16 @@ pub enum Type {
     },
 
     Reference(Shared<Self>),
+
+    /// Removed by `LowerTuples`
+    Tuple(Vec<Shared<Self>>),
 }
 
 impl Type {
     // Gets the size of a type if it is an integer
     pub fn get_size(&self) -> Option<Size> {
         match self {
-            Type::Integer { size } | Type::Bits { size } => Some(size.clone()),
+            Type::Integer { size, .. } | Type::Bits { size } => Some(size.clone()),
             _ => None,
         }
     }
@@ -269,14 +511,21 @@ impl Type {
     // Gets a reference to the size of a type if it is an integer
     pub fn get_size_mut(&mut self) -> Option<&mut Size> {
         match self {
-            Type::Integer { size } | Type::Bits { size } => Some(size),
+            Type::Integer { size, .. } | Type::Bits { size } => Some(size),
             _ => None,
         }
     }
 }
 
+/// Inclusive bounds on the value of an integer, as proven by `RangeAnalysis`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Range {
+    pub min: BigInt,
+    pub max: BigInt,
+}
+
 /// Size of a boom integer
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Size {
     /// Size is known statically at borealis compile time
     Static(usize),
@@ -310,6 +559,10 @@ impl Walkable for Shared<Type> {
             | Vector { element_type }
             | FixedVector { element_type, .. }
             | Reference(element_type) => visitor.visit_type(element_type.clone()),
+
+            Tuple(elements) => elements
+                .iter()
+                .for_each(|element| visitor.visit_type(element.clone())),
         }
     }
 }
@@ -352,7 +605,7 @@ impl Add for Size {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Statement {
     TypeDeclaration {
         name: InternedString,
@@ -440,7 +693,7 @@ impl Walkable for Statement {
 }
 
 /// Expression
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expression {
     Identifier(InternedString),
     Field {
@@ -448,20 +701,25 @@ pub enum Expression {
         field: InternedString,
     },
     Address(Box<Self>),
+    /// Element of a tuple, removed by `LowerTuples`
+    TupleMember {
+        expression: Box<Self>,
+        index: usize,
+    },
 }
 
 impl Walkable for Expression {
     fn walk<V: Visitor>(&self, visitor: &mut V) {
         match self {
             Self::Identifier(_) => (),
-            Self::Field { expression, .. } | Self::Address(expression) => {
-                visitor.visit_expression(expression)
-            }
+            Self::Field { expression, .. }
+            | Self::TupleMember { expression, .. }
+            | Self::Address(expression) => visitor.visit_expression(expression),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Value {
     Identifier(InternedString),
     Literal(Shared<Literal>),
@@ -484,6 +742,13 @@ pub enum Value {
         identifier: InternedString,
         types: Vec<Shared<Type>>,
     },
+    /// Removed by `LowerTuples`
+    Tuple(Vec<Shared<Self>>),
+    /// Removed by `LowerTuples`
+    TupleMember {
+        value: Shared<Self>,
+        index: usize,
+    },
 }
 
 impl Value {
@@ -522,6 +787,9 @@ impl Value {
             }
             Self::Literal(literal) => match &*literal.get() {
                 Literal::Bool(value) => Some(*value),
+                // folded comparisons
+                Literal::Int(value) if *value == 0.into() => Some(false),
+                Literal::Int(value) if *value == 1.into() => Some(true),
                 _ => None,
             },
 
@@ -549,7 +817,12 @@ impl Walkable for Value {
             Value::Struct { fields, .. } => fields
                 .iter()
                 .for_each(|field| visitor.visit_named_value(field)),
-            Value::Field { value, .. } => visitor.visit_value(value.clone()),
+            Value::Field { value, .. } | Value::TupleMember { value, .. } => {
+                visitor.visit_value(value.clone())
+            }
+            Value::Tuple(values) => values
+                .iter()
+                .for_each(|value| visitor.visit_value(value.clone())),
             Value::CtorKind { value, types, .. } | Value::CtorUnwrap { value, types, .. } => {
                 visitor.visit_value(value.clone());
                 types.iter().for_each(|typ| visitor.visit_type(typ.clone()));
@@ -570,7 +843,7 @@ impl From<Operation> for Shared<Value> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Literal {
     Int(BigInt),
     // Little-endian order
@@ -589,7 +862,7 @@ impl Walkable for Literal {
     }
 }
 
-#[derive(Debug, Clone, Kinded)]
+#[derive(Debug, Clone, Kinded, Serialize, Deserialize)]
 pub enum Operation {
     Not(Shared<Value>),
     Complement(Shared<Value>),
@@ -610,10 +883,16 @@ pub enum Operation {
     Xor(Shared<Value>, Shared<Value>),
     Divide(Shared<Value>, Shared<Value>),
 
+    /// Conjunction of two booleans, unlike `And` of their backing values
+    LogicalAnd(Shared<Value>, Shared<Value>),
+    /// Disjunction of two booleans, unlike `Or` of their backing values
+    LogicalOr(Shared<Value>, Shared<Value>),
+
     Cast(Shared<Value>, Shared<Type>),
 
     LeftShift(Shared<Value>, Shared<Value>),
     RightShift(Shared<Value>, Shared<Value>),
+    ArithmeticRightShift(Shared<Value>, Shared<Value>),
     RotateRight(Shared<Value>, Shared<Value>),
     RotateLeft(Shared<Value>, Shared<Value>),
 }
@@ -637,8 +916,11 @@ impl Walkable for Operation {
             | Operation::Xor(lhs, rhs)
             | Operation::And(lhs, rhs)
             | Operation::Divide(lhs, rhs)
+            | Operation::LogicalAnd(lhs, rhs)
+            | Operation::LogicalOr(lhs, rhs)
             | Operation::LeftShift(lhs, rhs)
             | Operation::RightShift(lhs, rhs)
+            | Operation::ArithmeticRightShift(lhs, rhs)
             | Operation::RotateLeft(lhs, rhs)
             | Operation::RotateRight(lhs, rhs) => {
                 visitor.visit_value(lhs.clone());
@@ -653,7 +935,7 @@ impl Walkable for Operation {
 }
 
 /// Bit
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum Bit {
     /// Fixed zero
     Zero,
@@ -713,3 +995,79 @@ pub fn bits_to_int<B: AsRef<[Bit]>>(bits: B) -> u64 {
 
     bits.iter().rev().fold(0, |acc, bit| acc << 1 | bit.value())
 }
+
+/// Converts a sequence of bits of any length to an integer
+pub fn bits_to_bigint<B: AsRef<[Bit]>>(bits: B) -> BigInt {
+    let bits = bits.as_ref();
+
+    assert!(bits.iter().all(Bit::is_fixed));
+
+    bits.iter().rev().fold(BigInt::from(0), |acc, bit| {
+        acc << 1 | BigInt::from(bit.value())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            control_flow::ControlFlowBlock,
+            passes::{resolve_bitvectors::ResolveBitvectors, Pass},
+            pretty_print::print_functions_stable,
+            test_utils::*,
+            Ast, Size,
+        },
+        common::shared::Shared,
+        pretty_assertions::assert_eq,
+    };
+
+    fn render_functions(ast: Shared<Ast>) -> String {
+        let mut buf = vec![];
+        print_functions_stable(&mut buf, ast);
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        init();
+
+        let original = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                declare("z", bits(Size::Unknown)),
+                call("x", "Zeros", vec![int(8)]),
+                copy("y", ident("x")),
+                call("z", "bitvector_concat", vec![ident("x"), ident("y")]),
+            ],
+        )]);
+
+        let json = serde_json::to_string(&*original.get()).unwrap();
+        let deserialized = Shared::new(serde_json::from_str::<Ast>(&json).unwrap());
+
+        assert_eq!(
+            render_functions(original.clone()),
+            render_functions(deserialized.clone())
+        );
+
+        ResolveBitvectors::default().run(original.clone());
+        ResolveBitvectors::default().run(deserialized.clone());
+
+        let resolved = render_functions(original);
+        assert!(resolved.contains("bv16 z;"));
+        assert_eq!(resolved, render_functions(deserialized));
+    }
+
+    #[test]
+    fn evaluate_folded_comparison() {
+        init();
+
+        // `FoldConstants` replaces constant comparisons with 0 or 1
+        let block = ControlFlowBlock::new();
+
+        assert_eq!(int(0).get().evaluate_bool(&block), Some(false));
+        assert_eq!(int(1).get().evaluate_bool(&block), Some(true));
+        assert_eq!(int(2).get().evaluate_bool(&block), None);
+    }
+}
@@ -0,0 +1,351 @@
+//! Gives every local in a function a unique name
+//!
+//! Passes key locals by name, so two declarations of the same name in one
+//! function (such as in both arms of a branch, or after inlining) would have
+//! their types and sizes conflated. Every declaration after the first of a
+//! name is renamed, and each read or write is renamed to match the closest
+//! dominating declaration.
+
+use {
+    crate::boom::{
+        control_flow::{graph::ControlFlowGraph, ControlFlowBlock, Terminator},
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, Expression, FunctionDefinition, Statement, Value,
+    },
+    common::{identifiable::Id, intern::InternedString, shared::Shared, HashMap, HashSet},
+};
+
+/// Renames locals declared more than once in a function
+#[derive(Debug, Default)]
+pub struct AlphaRename;
+
+impl AlphaRename {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for AlphaRename {
+    fn name(&self) -> &'static str {
+        "AlphaRename"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get().functions.values().map(rename_function).any()
+    }
+}
+
+/// Current name of each renamed local
+type Scope = HashMap<InternedString, InternedString>;
+
+fn rename_function(def: &FunctionDefinition) -> bool {
+    let cfg = ControlFlowGraph::build(def);
+    let blocks = cfg.blocks();
+
+    let mut names = def
+        .signature
+        .parameters
+        .get()
+        .iter()
+        .map(|parameter| parameter.name)
+        .collect::<HashSet<_>>();
+
+    let declarations = blocks
+        .iter()
+        .flat_map(|block| block.statements())
+        .flat_map(|statement| declarations(&statement))
+        .collect::<Vec<_>>();
+
+    let mut seen = names.clone();
+    let duplicates = declarations
+        .iter()
+        .filter(|(_, name)| !seen.insert(*name))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if duplicates.is_empty() {
+        return false;
+    }
+
+    names.extend(declarations.iter().map(|(_, name)| *name));
+
+    let mut renamer = Renamer {
+        names,
+        renamed: duplicates
+            .iter()
+            .map(|(statement, _)| statement.as_ptr())
+            .collect(),
+        scopes: HashMap::default(),
+    };
+
+    // dominators first, so each block starts from the scope of its immediate
+    // dominator
+    let mut blocks = blocks;
+    blocks.sort_by_key(|block| cfg.dominators(block).len());
+
+    for block in &blocks {
+        let scope = cfg
+            .immediate_dominator(block)
+            .map(|dominator| renamer.scopes[&dominator.id()].clone())
+            .unwrap_or_default();
+
+        renamer.rename_block(block, scope);
+    }
+
+    true
+}
+
+struct Renamer {
+    /// Every name used by a parameter or local
+    names: HashSet<InternedString>,
+    /// Declarations to be renamed, by address
+    renamed: HashSet<*const Statement>,
+    /// Scope at the end of each processed block
+    scopes: HashMap<Id, Scope>,
+}
+
+impl Renamer {
+    fn rename_block(&mut self, block: &ControlFlowBlock, mut scope: Scope) {
+        for statement in block.statements() {
+            self.rename_statement(&statement, &mut scope);
+        }
+
+        let terminator = match block.terminator() {
+            Terminator::Return(Some(value)) => {
+                Terminator::Return(Some(renamed_value(value, &scope)))
+            }
+            Terminator::Conditional {
+                condition,
+                target,
+                fallthrough,
+            } => Terminator::Conditional {
+                condition: renamed_value(condition, &scope),
+                target,
+                fallthrough,
+            },
+            Terminator::Panic(values) => {
+                values.iter().for_each(|value| rename_value(value, &scope));
+                Terminator::Panic(values)
+            }
+            terminator => terminator,
+        };
+        block.set_terminator(terminator);
+
+        self.scopes.insert(block.id(), scope);
+    }
+
+    fn rename_statement(&mut self, statement: &Shared<Statement>, scope: &mut Scope) {
+        let renamed = self.renamed.contains(&statement.as_ptr());
+
+        match &mut *statement.get_mut() {
+            Statement::TypeDeclaration { name, .. } => {
+                if renamed {
+                    let unique = self.unique(*name);
+                    scope.insert(*name, unique);
+                    *name = unique;
+                } else {
+                    // declared again under its own name, shadowing any renaming
+                    scope.remove(name);
+                }
+            }
+            Statement::Copy { expression, value } => {
+                rename_expression(expression, scope);
+                rename_value(value, scope);
+            }
+            Statement::FunctionCall {
+                expression,
+                arguments,
+                ..
+            } => {
+                if let Some(expression) = expression {
+                    rename_expression(expression, scope);
+                }
+                arguments
+                    .iter()
+                    .for_each(|argument| rename_value(argument, scope));
+            }
+            Statement::Jump { condition, .. } => rename_value(condition, scope),
+            Statement::If {
+                condition,
+                if_body,
+                else_body,
+            } => {
+                rename_value(condition, scope);
+                for statement in if_body.iter().chain(else_body.iter()) {
+                    self.rename_statement(statement, scope);
+                }
+            }
+            Statement::Panic(values) => values.iter().for_each(|value| rename_value(value, scope)),
+            Statement::Label(_)
+            | Statement::Goto(_)
+            | Statement::End(_)
+            | Statement::Undefined
+            | Statement::Exit(_)
+            | Statement::Comment(_) => (),
+        }
+    }
+
+    /// Generates a name not used by any other local
+    fn unique(&mut self, name: InternedString) -> InternedString {
+        (1..)
+            .map(|n| InternedString::from(format!("{name}_{n}")))
+            .find(|candidate| self.names.insert(*candidate))
+            .unwrap()
+    }
+}
+
+/// Gets the declarations in a statement, including in nested bodies
+fn declarations(statement: &Shared<Statement>) -> Vec<(Shared<Statement>, InternedString)> {
+    match &*statement.get() {
+        Statement::TypeDeclaration { name, .. } => vec![(statement.clone(), *name)],
+        Statement::If {
+            if_body, else_body, ..
+        } => if_body
+            .iter()
+            .chain(else_body)
+            .flat_map(declarations)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn rename_expression(expression: &mut Expression, scope: &Scope) {
+    match expression {
+        Expression::Identifier(ident) => {
+            if let Some(renamed) = scope.get(ident) {
+                *ident = *renamed;
+            }
+        }
+        Expression::Field { expression, .. }
+        | Expression::TupleMember { expression, .. }
+        | Expression::Address(expression) => rename_expression(expression, scope),
+    }
+}
+
+fn rename_value(value: &Shared<Value>, scope: &Scope) {
+    struct Rename<'a>(&'a Scope);
+
+    impl Visitor for Rename<'_> {
+        fn visit_value(&mut self, node: Shared<Value>) {
+            let renamed = match &*node.get() {
+                Value::Identifier(ident) => self.0.get(ident).copied(),
+                _ => None,
+            };
+
+            match renamed {
+                Some(renamed) => *node.get_mut() = Value::Identifier(renamed),
+                None => node.get().walk(self),
+            }
+        }
+    }
+
+    Rename(scope).visit_value(value.clone());
+}
+
+/// Renames a value held directly by a terminator
+fn renamed_value(value: Value, scope: &Scope) -> Value {
+    let value = Shared::new(value);
+    rename_value(&value, scope);
+    let renamed = value.get().clone();
+    renamed
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            control_flow::{ControlFlowBlock, Terminator},
+            passes::{alpha_rename::AlphaRename, Pass},
+            pretty_print::print_statement,
+            test_utils::*,
+            Size, Value,
+        },
+        pretty_assertions::{assert_eq, assert_ne},
+    };
+
+    fn statements(block: &ControlFlowBlock) -> Vec<String> {
+        block
+            .statements()
+            .into_iter()
+            .map(|statement| {
+                let mut buf = vec![];
+                print_statement(&mut buf, statement);
+                String::from_utf8(buf).unwrap().trim().to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shadowed_in_branches() {
+        init();
+
+        let def = function("f", vec![]);
+
+        let (left, right) = (ControlFlowBlock::new(), ControlFlowBlock::new());
+        left.set_statements(vec![
+            declare("x", bits(Size::Static(8))),
+            copy("x", int(1)),
+            copy("y", ident("x")),
+        ]);
+        right.set_statements(vec![
+            declare("x", bits(Size::Static(16))),
+            copy("x", int(2)),
+            copy("y", ident("x")),
+        ]);
+        right.set_terminator(Terminator::Return(Some(Value::Identifier("x".into()))));
+        def.entry_block.set_terminator(Terminator::Conditional {
+            condition: Value::Identifier("c".into()),
+            target: left.clone(),
+            fallthrough: right.clone(),
+        });
+
+        let ast = ast([def]);
+
+        assert!(AlphaRename.run(ast.clone()));
+        assert!(!AlphaRename.run(ast.clone()));
+
+        let terminator = right.terminator();
+
+        // whichever block is visited second has its `x` renamed
+        let (left, right) = (statements(&left), statements(&right));
+        let name = |declaration: &str| {
+            declaration
+                .split(' ')
+                .nth(1)
+                .unwrap()
+                .trim_end_matches(';')
+                .to_owned()
+        };
+        let (l, r) = (name(&left[0]), name(&right[0]));
+        assert_ne!(l, r);
+
+        assert_eq!(
+            left,
+            [
+                format!("bv8 {l};"),
+                format!("{l} = 1;"),
+                format!("y = {l};")
+            ]
+        );
+        assert_eq!(
+            right,
+            [
+                format!("bv16 {r};"),
+                format!("{r} = 2;"),
+                format!("y = {r};")
+            ]
+        );
+        assert!(matches!(
+            terminator,
+            Terminator::Return(Some(Value::Identifier(x))) if x.as_ref() == r
+        ));
+
+        // distinct names keep distinct types
+        assert!(matches!(size_of(&ast, "f", &l), Some(Size::Static(8))));
+        assert!(matches!(size_of(&ast, "f", &r), Some(Size::Static(16))));
+    }
+}
@@ -0,0 +1,168 @@
+//! Orders the operands of commutative operations
+//!
+//! `a | b` and `b | a` compute the same value but are not structurally equal,
+//! so common subexpression elimination cannot merge them. Operands of
+//! commutative operations are sorted by a stable key: identifiers first by
+//! name, then nested operations, then literals, ties broken by their printed
+//! form. Operations such as shifts and subtraction are never reordered.
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, FunctionDefinition, Operation, Value,
+    },
+    common::shared::Shared,
+};
+
+/// Sorts the operands of commutative operations
+#[derive(Debug, Default)]
+pub struct Canonicalize;
+
+impl Canonicalize {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for Canonicalize {
+    fn name(&self) -> &'static str {
+        "Canonicalize"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get()
+            .functions
+            .values()
+            .map(canonicalize_function)
+            .any()
+    }
+}
+
+fn canonicalize_function(def: &FunctionDefinition) -> bool {
+    let mut sorter = Sorter { did_change: false };
+    sorter.visit_function_definition(def);
+    sorter.did_change
+}
+
+struct Sorter {
+    did_change: bool,
+}
+
+impl Visitor for Sorter {
+    fn visit_value(&mut self, node: Shared<Value>) {
+        // operands first, so their keys are those of their canonical form
+        node.get().walk(self);
+
+        let swap = match &*node.get() {
+            Value::Operation(
+                Operation::Add(lhs, rhs)
+                | Operation::Multiply(lhs, rhs)
+                | Operation::And(lhs, rhs)
+                | Operation::Or(lhs, rhs)
+                | Operation::Xor(lhs, rhs)
+                | Operation::Equal(lhs, rhs)
+                | Operation::NotEqual(lhs, rhs),
+            ) => key(rhs) < key(lhs),
+            _ => false,
+        };
+
+        if !swap {
+            return;
+        }
+
+        if let Value::Operation(
+            Operation::Add(lhs, rhs)
+            | Operation::Multiply(lhs, rhs)
+            | Operation::And(lhs, rhs)
+            | Operation::Or(lhs, rhs)
+            | Operation::Xor(lhs, rhs)
+            | Operation::Equal(lhs, rhs)
+            | Operation::NotEqual(lhs, rhs),
+        ) = &mut *node.get_mut()
+        {
+            std::mem::swap(lhs, rhs);
+        }
+
+        self.did_change = true;
+    }
+}
+
+/// Gets the sort key of an operand
+fn key(value: &Shared<Value>) -> (u8, String) {
+    let value = value.get();
+
+    let rank = match &*value {
+        Value::Identifier(_) => 0,
+        Value::Literal(_) => 2,
+        _ => 1,
+    };
+
+    (rank, value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{
+                canonicalize::Canonicalize,
+                common_subexpression_elimination::CommonSubexpressionElimination, Pass,
+            },
+            test_utils::*,
+            Operation, Size,
+        },
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn commutative_operands_merged() {
+        init();
+
+        let (first, second) = (
+            copy("x", Operation::Or(ident("b"), ident("a")).into()),
+            copy("y", Operation::Or(ident("a"), ident("b")).into()),
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(8))),
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Static(8))),
+                first.clone(),
+                second.clone(),
+            ],
+        )]);
+
+        assert!(Canonicalize.run(ast.clone()));
+        assert!(!Canonicalize.run(ast.clone()));
+
+        assert_eq!(copied_value(&first), "(a | b)");
+        assert_eq!(copied_value(&second), "(a | b)");
+
+        assert!(CommonSubexpressionElimination.run(ast));
+        assert_eq!(copied_value(&first), "cse_0");
+        assert_eq!(copied_value(&second), "cse_0");
+    }
+
+    #[test]
+    fn literals_last_non_commutative_kept() {
+        init();
+
+        let statement = copy(
+            "x",
+            Operation::Add(int(1), Operation::Subtract(int(2), ident("a")).into()).into(),
+        );
+        let shift = copy("y", Operation::LeftShift(int(1), ident("a")).into());
+        let ast = ast([function("f", vec![statement.clone(), shift.clone()])]);
+
+        assert!(Canonicalize.run(ast));
+
+        assert_eq!(copied_value(&statement), "((2 - a) + 1)");
+        assert_eq!(copied_value(&shift), "(1 << a)");
+    }
+}
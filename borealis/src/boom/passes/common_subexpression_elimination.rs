@@ -0,0 +1,414 @@
+//! Repeated operations within a block can be computed once
+//!
+//! Lowering bitvector builtins produces many identical operations, such as
+//! taking the same subrange of a value several times. Within a block, when an
+//! operation is structurally equal to an earlier one, the earlier one is
+//! assigned to a temporary and both are replaced by a read of it.
+//!
+//! Only whole operations are considered, not their operands. An earlier
+//! operation stops being available once any local it reads is assigned, and
+//! function calls make nothing available across them as they may modify
+//! registers.
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        structural::{self, StructuralEq},
+        visitor::{Visitor, Walkable},
+        Ast, Expression, FunctionDefinition, Operation, Spans, Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap, HashSet},
+};
+
+/// Replaces repeated operations within a block with a temporary
+#[derive(Debug, Default)]
+pub struct CommonSubexpressionElimination;
+
+impl CommonSubexpressionElimination {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for CommonSubexpressionElimination {
+    fn name(&self) -> &'static str {
+        "CommonSubexpressionElimination"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        let mut spans = std::mem::take(&mut ast.get_mut().spans);
+
+        let did_change = ast
+            .get()
+            .functions
+            .values()
+            .map(|def| eliminate_common_subexpressions(def, &mut spans))
+            .any();
+
+        ast.get_mut().spans = spans;
+        did_change
+    }
+}
+
+/// Earlier occurrence of an operation
+struct Available {
+    /// Operation, as it was when first found
+    operation: Value,
+    /// Node of the first occurrence, replaced by a read of the temporary once
+    /// a second occurrence is found
+    first: Shared<Value>,
+    /// Index of the statement containing the first occurrence
+    statement: usize,
+    /// Type of the result of the operation
+    typ: Shared<Type>,
+    /// Locals read by the operation
+    reads: HashSet<InternedString>,
+    temporary: Option<InternedString>,
+}
+
+/// Eliminates common subexpressions in a function, temporaries take the span
+/// of the statement they are inserted before
+fn eliminate_common_subexpressions(def: &FunctionDefinition, spans: &mut Spans) -> bool {
+    let types = local_types(def);
+    let mut temporaries = Temporaries::new(def);
+    let mut did_change = false;
+
+    for block in def.entry_block.iter() {
+        let statements = block.statements();
+
+        // temporaries to insert before each statement
+        let mut prefixes = vec![vec![]; statements.len()];
+        // by structural hash of the operation
+        let mut available = HashMap::<u64, Vec<Available>>::default();
+
+        for (index, statement) in statements.iter().enumerate() {
+            if matches!(&*statement.get(), Statement::If { .. }) {
+                // conservatively forget everything, nested bodies may assign to anything
+                available.clear();
+                continue;
+            }
+
+            for node in operations(statement) {
+                let operation = node.get().clone();
+                let hash = structural::hash(&operation);
+
+                let earlier = available.get_mut(&hash).and_then(|candidates| {
+                    candidates
+                        .iter_mut()
+                        .find(|candidate| candidate.operation.structural_eq(&operation))
+                });
+
+                match earlier {
+                    Some(earlier) => {
+                        let temporary = match earlier.temporary {
+                            Some(temporary) => temporary,
+                            None => {
+                                let temporary = temporaries.next();
+                                let value = Shared::new(std::mem::replace(
+                                    &mut *earlier.first.get_mut(),
+                                    Value::Identifier(temporary),
+                                ));
+
+                                prefixes[earlier.statement].extend([
+                                    Statement::TypeDeclaration {
+                                        name: temporary,
+                                        typ: Shared::new(earlier.typ.get().clone()),
+                                    }
+                                    .into(),
+                                    Statement::Copy {
+                                        expression: Expression::Identifier(temporary),
+                                        value,
+                                    }
+                                    .into(),
+                                ]);
+
+                                earlier.temporary = Some(temporary);
+                                temporary
+                            }
+                        };
+
+                        *node.get_mut() = Value::Identifier(temporary);
+                        did_change = true;
+                    }
+                    None => {
+                        let Some(typ) = result_type(statement, &node, &types) else {
+                            continue;
+                        };
+
+                        available.entry(hash).or_default().push(Available {
+                            operation,
+                            first: node.clone(),
+                            statement: index,
+                            typ,
+                            reads: reads(&node),
+                            temporary: None,
+                        });
+                    }
+                }
+            }
+
+            match &*statement.get() {
+                Statement::FunctionCall { .. } => available.clear(),
+                statement => {
+                    if let Some(written) = written_ident(statement) {
+                        available.values_mut().for_each(|candidates| {
+                            candidates.retain(|earlier| !earlier.reads.contains(&written))
+                        });
+                    }
+                }
+            }
+        }
+
+        if prefixes.iter().any(|prefix| !prefix.is_empty()) {
+            block.set_statements(
+                prefixes
+                    .into_iter()
+                    .zip(statements)
+                    .flat_map(|(prefix, statement)| {
+                        prefix
+                            .iter()
+                            .for_each(|temporary| spans.inherit(&statement, temporary));
+                        prefix.into_iter().chain([statement])
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    did_change
+}
+
+/// Generates names for temporaries that do not collide with existing locals
+struct Temporaries {
+    existing: HashSet<InternedString>,
+    count: usize,
+}
+
+impl Temporaries {
+    fn new(def: &FunctionDefinition) -> Self {
+        Self {
+            existing: local_types(def).into_keys().collect(),
+            count: 0,
+        }
+    }
+
+    fn next(&mut self) -> InternedString {
+        loop {
+            let name = InternedString::from(format!("cse_{}", self.count));
+            self.count += 1;
+
+            if self.existing.insert(name) {
+                return name;
+            }
+        }
+    }
+}
+
+/// Gets the declared types of the parameters and locals of a function
+fn local_types(def: &FunctionDefinition) -> HashMap<InternedString, Shared<Type>> {
+    def.entry_block
+        .iter()
+        .flat_map(|block| block.statements())
+        .filter_map(|statement| match &*statement.get() {
+            Statement::TypeDeclaration { name, typ } => Some((*name, typ.clone())),
+            _ => None,
+        })
+        .chain(
+            def.signature
+                .parameters
+                .get()
+                .iter()
+                .map(|parameter| (parameter.name, parameter.typ.clone())),
+        )
+        .collect()
+}
+
+/// Gets the type of the result of an operation, if it can be determined
+///
+/// Comparisons are booleans, otherwise the operation must be the whole value
+/// copied into a local.
+fn result_type(
+    statement: &Shared<Statement>,
+    node: &Shared<Value>,
+    types: &HashMap<InternedString, Shared<Type>>,
+) -> Option<Shared<Type>> {
+    if let Value::Operation(
+        Operation::Not(_)
+        | Operation::Equal(..)
+        | Operation::NotEqual(..)
+        | Operation::LessThan(..)
+        | Operation::LessThanOrEqual(..)
+        | Operation::GreaterThan(..)
+        | Operation::GreaterThanOrEqual(..),
+    ) = &*node.get()
+    {
+        return Some(Shared::new(Type::Bool));
+    }
+
+    match &*statement.get() {
+        Statement::Copy {
+            expression: Expression::Identifier(destination),
+            value,
+        } if Shared::ptr_eq(value, node) => types.get(destination).cloned(),
+        _ => None,
+    }
+}
+
+/// Gets the outermost operations read by a statement, in order
+fn operations(statement: &Shared<Statement>) -> Vec<Shared<Value>> {
+    #[derive(Default)]
+    struct Operations(Vec<Shared<Value>>);
+
+    impl Visitor for Operations {
+        fn visit_value(&mut self, node: Shared<Value>) {
+            if let Value::Operation(_) = &*node.get() {
+                self.0.push(node.clone());
+                return;
+            }
+
+            node.get().walk(self);
+        }
+    }
+
+    let mut operations = Operations::default();
+    operations.visit_statement(statement.clone());
+    operations.0
+}
+
+/// Gets all locals read by a value
+fn reads(value: &Shared<Value>) -> HashSet<InternedString> {
+    #[derive(Default)]
+    struct Reads(HashSet<InternedString>);
+
+    impl Visitor for Reads {
+        fn visit_value(&mut self, node: Shared<Value>) {
+            if let Value::Identifier(ident) = &*node.get() {
+                self.0.insert(*ident);
+            }
+
+            node.get().walk(self);
+        }
+    }
+
+    let mut reads = Reads::default();
+    reads.visit_value(value.clone());
+    reads.0
+}
+
+/// Gets the local assigned to (wholly or in part) by a statement
+fn written_ident(statement: &Statement) -> Option<InternedString> {
+    fn root(expression: &Expression) -> InternedString {
+        match expression {
+            Expression::Identifier(ident) => *ident,
+            Expression::Field { expression, .. }
+            | Expression::TupleMember { expression, .. }
+            | Expression::Address(expression) => root(expression),
+        }
+    }
+
+    match statement {
+        Statement::TypeDeclaration { name, .. } => Some(*name),
+        Statement::Copy { expression, .. } => Some(root(expression)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{common_subexpression_elimination::CommonSubexpressionElimination, Pass},
+            pretty_print::print_statement,
+            test_utils::*,
+            Ast, Operation, Size, Value,
+        },
+        common::{intern::InternedString, shared::Shared},
+        pretty_assertions::assert_eq,
+    };
+
+    /// Renders the statements of function `f`
+    fn statements(ast: &Shared<Ast>) -> Vec<String> {
+        ast.get().functions[&InternedString::from("f")]
+            .entry_block
+            .statements()
+            .into_iter()
+            .map(|statement| {
+                let mut buf = vec![];
+                print_statement(&mut buf, statement);
+                String::from_utf8(buf).unwrap().trim().to_owned()
+            })
+            .collect()
+    }
+
+    /// `(x >> 4) & 15`
+    fn slice() -> Shared<Value> {
+        Operation::And(Operation::RightShift(ident("x"), int(4)).into(), int(15)).into()
+    }
+
+    #[test]
+    fn repeated_slice() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("a", bits(Size::Static(4))),
+                declare("b", bits(Size::Static(4))),
+                copy("a", slice()),
+                copy("b", slice()),
+            ],
+        )]);
+
+        assert!(CommonSubexpressionElimination.run(ast.clone()));
+        assert!(!CommonSubexpressionElimination.run(ast.clone()));
+
+        let statements = statements(&ast);
+        assert_eq!(
+            statements[4..],
+            ["cse_0 = ((x >> 4) & 15);", "a = cse_0;", "b = cse_0;"]
+        );
+        assert!(statements[3].contains("cse_0"));
+    }
+
+    #[test]
+    fn not_across_call() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("a", bits(Size::Static(4))),
+                declare("b", bits(Size::Static(4))),
+                copy("a", slice()),
+                call("r", "g", vec![]),
+                copy("b", slice()),
+            ],
+        )]);
+
+        assert!(!CommonSubexpressionElimination.run(ast));
+    }
+
+    #[test]
+    fn operand_reassigned() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("a", bits(Size::Static(4))),
+                declare("b", bits(Size::Static(4))),
+                copy("a", slice()),
+                copy("x", int(0)),
+                copy("b", slice()),
+            ],
+        )]);
+
+        assert!(!CommonSubexpressionElimination.run(ast));
+    }
+}
@@ -0,0 +1,357 @@
+//! Copies between locals can be removed by reading the source directly
+//!
+//! Within a block, after `dest = src` later reads of `dest` are replaced with
+//! `src` until either is reassigned. Once `dest` is no longer read anywhere in
+//! the function the copy is removed.
+//!
+//! Copies between locals of different types or lengths are kept, as are
+//! copies involving registers or references, which may be modified by function
+//! calls.
+
+use {
+    crate::boom::{
+        control_flow::{ControlFlowBlock, Terminator},
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, Expression, FunctionDefinition, Size, Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap, HashSet},
+    std::mem::discriminant,
+};
+
+/// Replaces reads of copied locals with the source of the copy
+#[derive(Debug, Default)]
+pub struct CopyPropagation;
+
+impl CopyPropagation {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for CopyPropagation {
+    fn name(&self) -> &'static str {
+        "CopyPropagation"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get().functions.values().map(propagate_copies).any()
+    }
+}
+
+fn propagate_copies(def: &FunctionDefinition) -> bool {
+    let locals = locals(def);
+    let mut did_change = false;
+    let mut propagated = vec![];
+
+    for block in def.entry_block.iter() {
+        // destination to source of active copies
+        let mut copies = HashMap::<InternedString, InternedString>::default();
+
+        for statement in block.statements() {
+            did_change |= substitute(&statement, &copies);
+
+            match &*statement.get() {
+                // conservatively forget everything, nested bodies may assign to anything
+                Statement::If { .. } => copies.clear(),
+                statement => {
+                    if let Some(written) = written_ident(statement) {
+                        copies.retain(|dest, src| *dest != written && *src != written);
+                    }
+                }
+            }
+
+            if let Some((dest, src)) = local_copy(&statement) {
+                let compatible = locals
+                    .get(&dest)
+                    .zip(locals.get(&src))
+                    .is_some_and(|(dest, src)| same_type(&dest.get(), &src.get()));
+
+                if dest != src && compatible {
+                    copies.insert(dest, src);
+                    propagated.push((block.clone(), statement.clone(), dest));
+                }
+            }
+        }
+    }
+
+    // remove copies whose destination is no longer read
+    let reads = reads(def);
+    for (block, statement, dest) in propagated {
+        if !reads.contains(&dest) {
+            block.set_statements(
+                block
+                    .statements()
+                    .into_iter()
+                    .filter(|s| !Shared::ptr_eq(s, &statement))
+                    .collect(),
+            );
+            did_change = true;
+        }
+    }
+
+    did_change
+}
+
+/// Gets the types of locals that can be propagated, excluding references
+fn locals(def: &FunctionDefinition) -> HashMap<InternedString, Shared<Type>> {
+    def.entry_block
+        .iter()
+        .flat_map(|block| block.statements())
+        .filter_map(|statement| match &*statement.get() {
+            Statement::TypeDeclaration { name, typ } => Some((*name, typ.clone())),
+            _ => None,
+        })
+        .chain(
+            def.signature
+                .parameters
+                .get()
+                .iter()
+                .filter(|parameter| !parameter.is_ref)
+                .map(|parameter| (parameter.name, parameter.typ.clone())),
+        )
+        .filter(|(_, typ)| !matches!(&*typ.get(), Type::Reference(_)))
+        .collect()
+}
+
+/// Determines whether a copy between two types preserves the value, bitvectors
+/// must have the same static length
+fn same_type(a: &Type, b: &Type) -> bool {
+    if discriminant(a) != discriminant(b) {
+        return false;
+    }
+
+    match (a.get_size(), b.get_size()) {
+        (None, None) => true,
+        (Some(Size::Static(a)), Some(Size::Static(b))) => a == b,
+        _ => false,
+    }
+}
+
+/// Gets the destination and source of a copy from one identifier to another
+fn local_copy(statement: &Shared<Statement>) -> Option<(InternedString, InternedString)> {
+    match &*statement.get() {
+        Statement::Copy {
+            expression: Expression::Identifier(dest),
+            value,
+        } => match &*value.get() {
+            Value::Identifier(src) => Some((*dest, *src)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Gets the local assigned to (wholly or in part) by a statement
+fn written_ident(statement: &Statement) -> Option<InternedString> {
+    fn root(expression: &Expression) -> InternedString {
+        match expression {
+            Expression::Identifier(ident) => *ident,
+            Expression::Field { expression, .. }
+            | Expression::TupleMember { expression, .. }
+            | Expression::Address(expression) => root(expression),
+        }
+    }
+
+    match statement {
+        Statement::TypeDeclaration { name, .. } => Some(*name),
+        Statement::Copy { expression, .. }
+        | Statement::FunctionCall {
+            expression: Some(expression),
+            ..
+        } => Some(root(expression)),
+        _ => None,
+    }
+}
+
+/// Replaces reads of copy destinations with their sources in a statement,
+/// returning whether any were replaced
+fn substitute(
+    statement: &Shared<Statement>,
+    copies: &HashMap<InternedString, InternedString>,
+) -> bool {
+    struct Substitute<'a> {
+        copies: &'a HashMap<InternedString, InternedString>,
+        did_change: bool,
+    }
+
+    impl Visitor for Substitute<'_> {
+        fn visit_value(&mut self, node: Shared<Value>) {
+            let replacement = match &*node.get() {
+                Value::Identifier(ident) => self.copies.get(ident).copied(),
+                _ => None,
+            };
+
+            match replacement {
+                Some(src) => {
+                    *node.get_mut() = Value::Identifier(src);
+                    self.did_change = true;
+                }
+                None => node.get().walk(self),
+            }
+        }
+    }
+
+    if copies.is_empty() {
+        return false;
+    }
+
+    let mut visitor = Substitute {
+        copies,
+        did_change: false,
+    };
+    visitor.visit_statement(statement.clone());
+    visitor.did_change
+}
+
+/// Gets all identifiers read in a function, including block terminators
+fn reads(def: &FunctionDefinition) -> HashSet<InternedString> {
+    #[derive(Default)]
+    struct Reads(HashSet<InternedString>);
+
+    impl Visitor for Reads {
+        fn visit_value(&mut self, node: Shared<Value>) {
+            if let Value::Identifier(ident) = &*node.get() {
+                self.0.insert(*ident);
+            }
+
+            node.get().walk(self);
+        }
+    }
+
+    let mut reads = Reads::default();
+
+    def.entry_block.iter().for_each(|block: ControlFlowBlock| {
+        block.walk(&mut reads);
+
+        match block.terminator() {
+            Terminator::Return(Some(value))
+            | Terminator::Conditional {
+                condition: value, ..
+            } => reads.visit_value(Shared::new(value)),
+            Terminator::Panic(values) => values
+                .into_iter()
+                .for_each(|value| reads.visit_value(value)),
+            Terminator::Return(None) | Terminator::Unconditional { .. } => (),
+        }
+    });
+
+    reads.0
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{copy_propagation::CopyPropagation, Pass},
+            pretty_print::print_statement,
+            test_utils::*,
+            Ast, Size,
+        },
+        common::{intern::InternedString, shared::Shared},
+        pretty_assertions::assert_eq,
+    };
+
+    /// Renders the statements of function `f`
+    fn statements(ast: &Shared<Ast>) -> Vec<String> {
+        ast.get().functions[&InternedString::from("f")]
+            .entry_block
+            .statements()
+            .into_iter()
+            .map(|statement| {
+                let mut buf = vec![];
+                print_statement(&mut buf, statement);
+                String::from_utf8(buf).unwrap().trim().to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chain() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(8))),
+                declare("c", bits(Size::Static(8))),
+                declare("d", bits(Size::Static(8))),
+                copy("b", ident("a")),
+                copy("c", ident("b")),
+                call("d", "not_vec", vec![ident("c")]),
+            ],
+        )]);
+
+        assert!(CopyPropagation.run(ast.clone()));
+        assert!(!CopyPropagation.run(ast.clone()));
+
+        assert_eq!(statements(&ast)[4..], ["d = not_vec(a);"]);
+    }
+
+    #[test]
+    fn source_reassigned() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(8))),
+                declare("d", bits(Size::Static(8))),
+                copy("b", ident("a")),
+                copy("a", int(1)),
+                call("d", "not_vec", vec![ident("b")]),
+            ],
+        )]);
+
+        assert!(!CopyPropagation.run(ast.clone()));
+
+        assert_eq!(
+            statements(&ast)[3..],
+            ["b = a;", "a = 1;", "d = not_vec(b);"]
+        );
+    }
+
+    #[test]
+    fn widening_copy() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(16))),
+                declare("d", bits(Size::Static(16))),
+                copy("b", ident("a")),
+                call("d", "not_vec", vec![ident("b")]),
+            ],
+        )]);
+
+        assert!(!CopyPropagation.run(ast.clone()));
+    }
+
+    #[test]
+    fn call_reads_destination() {
+        init();
+
+        // the copy is kept as long as the destination is still read
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(8))),
+                copy("b", ident("a")),
+                call("b", "g", vec![ident("b")]),
+                call("d", "g", vec![ident("b")]),
+            ],
+        )]);
+
+        assert!(CopyPropagation.run(ast.clone()));
+
+        assert_eq!(statements(&ast)[2..], ["b = a;", "b = g(a);", "d = g(b);"]);
+    }
+}
@@ -0,0 +1,295 @@
+//! Copies to locals that are never read can be removed
+//!
+//! Liveness starts from statements with side effects (function calls, writes
+//! to registers or fields), block terminators and return values. Sweeping each
+//! function in reverse, a copy to a live local makes the locals it reads live,
+//! until no more become live. Remaining copies to dead locals are removed, as
+//! are declarations of locals no longer assigned or read.
+
+use {
+    crate::boom::{
+        control_flow::Terminator,
+        passes::{any::AnyExt, Diagnostic, Pass, Severity},
+        visitor::{Visitor, Walkable},
+        Ast, Expression, FunctionDefinition, Statement, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashSet},
+};
+
+/// Removes copies and declarations of locals that are never read
+#[derive(Debug, Default)]
+pub struct DeadCodeElimination {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DeadCodeElimination {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for DeadCodeElimination {
+    fn name(&self) -> &'static str {
+        "DeadCodeElimination"
+    }
+
+    fn reset(&mut self) {}
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get()
+            .functions
+            .values()
+            .map(|def| {
+                let removed = eliminate_dead_code(def);
+
+                if removed > 0 {
+                    self.diagnostics.push(Diagnostic {
+                        severity: Severity::Info,
+                        pass: self.name(),
+                        function: Some(def.signature.name),
+                        statement: None,
+                        span: None,
+                        statement_id: None,
+                        ident: None,
+                        message: format!("removed {removed} dead statements"),
+                    });
+                }
+
+                removed > 0
+            })
+            .any()
+    }
+}
+
+/// Removes dead statements from a function until none remain, returning the
+/// number removed
+fn eliminate_dead_code(def: &FunctionDefinition) -> usize {
+    let mut removed = 0;
+
+    loop {
+        let live = live_locals(def);
+        let assigned = assigned_locals(def);
+
+        let mut removed_this_sweep = 0;
+
+        for block in def.entry_block.iter() {
+            let statements = block.statements();
+            let len = statements.len();
+
+            block.set_statements(
+                statements
+                    .into_iter()
+                    .filter(|statement| match &*statement.get() {
+                        Statement::Copy {
+                            expression: Expression::Identifier(dest),
+                            ..
+                        } => live.contains(dest) || !is_local(def, *dest),
+                        Statement::TypeDeclaration { name, .. } => {
+                            live.contains(name) || assigned.contains(name)
+                        }
+                        _ => true,
+                    })
+                    .collect(),
+            );
+
+            removed_this_sweep += len - block.statements().len();
+        }
+
+        if removed_this_sweep == 0 {
+            break removed;
+        }
+
+        removed += removed_this_sweep;
+    }
+}
+
+/// Determines whether `ident` is declared locally in the function, parameters
+/// and registers are never dead
+fn is_local(def: &FunctionDefinition, ident: InternedString) -> bool {
+    def.entry_block
+        .iter()
+        .flat_map(|block| block.statements())
+        .any(|statement| {
+            matches!(&*statement.get(), Statement::TypeDeclaration { name, .. } if *name == ident)
+        })
+}
+
+/// Gets the locals that are needed by any side effect or terminator of a
+/// function
+fn live_locals(def: &FunctionDefinition) -> HashSet<InternedString> {
+    let mut live = ReadIdents::default();
+
+    // terminators are always live
+    for block in def.entry_block.iter() {
+        match block.terminator() {
+            Terminator::Return(Some(value))
+            | Terminator::Conditional {
+                condition: value, ..
+            } => live.visit_value(Shared::new(value)),
+            Terminator::Panic(values) => {
+                values.into_iter().for_each(|value| live.visit_value(value))
+            }
+            Terminator::Return(None) | Terminator::Unconditional { .. } => (),
+        }
+    }
+
+    // sweep in reverse until no more locals become live
+    loop {
+        let count = live.0.len();
+
+        let statements = def
+            .entry_block
+            .iter()
+            .flat_map(|block| block.statements())
+            .collect::<Vec<_>>();
+
+        for statement in statements.iter().rev() {
+            let statement = statement.get();
+
+            match &*statement {
+                Statement::TypeDeclaration { .. } => (),
+                Statement::Copy {
+                    expression: Expression::Identifier(dest),
+                    value,
+                } => {
+                    if live.0.contains(dest) || !is_local(def, *dest) {
+                        live.visit_value(value.clone());
+                    }
+                }
+                // everything else has side effects or assigns only part of a local
+                statement => statement.walk(&mut live),
+            }
+        }
+
+        if live.0.len() == count {
+            break live.0;
+        }
+    }
+}
+
+/// Gets the locals assigned (wholly or in part) by any statement
+fn assigned_locals(def: &FunctionDefinition) -> HashSet<InternedString> {
+    fn root(expression: &Expression) -> InternedString {
+        match expression {
+            Expression::Identifier(ident) => *ident,
+            Expression::Field { expression, .. }
+            | Expression::TupleMember { expression, .. }
+            | Expression::Address(expression) => root(expression),
+        }
+    }
+
+    def.entry_block
+        .iter()
+        .flat_map(|block| block.statements())
+        .filter_map(|statement| match &*statement.get() {
+            Statement::Copy { expression, .. }
+            | Statement::FunctionCall {
+                expression: Some(expression),
+                ..
+            } => Some(root(expression)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Collects every identifier read by the visited values
+#[derive(Default)]
+struct ReadIdents(HashSet<InternedString>);
+
+impl Visitor for ReadIdents {
+    fn visit_value(&mut self, node: Shared<Value>) {
+        if let Value::Identifier(ident) = &*node.get() {
+            self.0.insert(*ident);
+        }
+
+        node.get().walk(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            control_flow::Terminator,
+            passes::{dead_code_elimination::DeadCodeElimination, Pass},
+            test_utils::*,
+            Size, Value,
+        },
+        common::intern::InternedString,
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn dead_temporary() {
+        init();
+
+        let def = function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("t", bits(Size::Static(8))),
+                copy("t", ident("a")),
+            ],
+        );
+        def.entry_block
+            .set_terminator(Terminator::Return(Some(Value::Identifier("a".into()))));
+        let ast = ast([def]);
+
+        let mut pass = DeadCodeElimination::default();
+        assert!(pass.run(ast.clone()));
+        assert!(!pass.run(ast.clone()));
+
+        let def = ast.get().functions[&InternedString::from("f")].clone();
+        assert_eq!(def.entry_block.statements().len(), 1);
+        assert!(def.get_ident_type("t".into()).is_none());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "removed 2 dead statements");
+    }
+
+    #[test]
+    fn live_through_call() {
+        init();
+
+        // `t` is only read by a call, which must be kept along with everything it
+        // reads
+        let statements = vec![
+            declare("a", bits(Size::Static(8))),
+            declare("t", bits(Size::Static(8))),
+            declare("r", bits(Size::Static(8))),
+            copy("t", ident("a")),
+            call("r", "g", vec![ident("t")]),
+        ];
+        let ast = ast([function("f", statements)]);
+
+        let mut pass = DeadCodeElimination::default();
+        assert!(!pass.run(ast.clone()));
+        assert!(pass.take_diagnostics().is_empty());
+    }
+
+    #[test]
+    fn dead_cycle() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(8))),
+                copy("a", ident("b")),
+                copy("b", ident("a")),
+            ],
+        )]);
+
+        assert!(DeadCodeElimination::default().run(ast.clone()));
+
+        let def = ast.get().functions[&InternedString::from("f")].clone();
+        assert!(def.entry_block.statements().is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+//! Merges functions with identical bodies
+//!
+//! Monomorphization specializes a function once per set of argument lengths,
+//! so the same helper can be instantiated many times with bodies differing
+//! only in name. Functions are compared by their stable pretty-printed form
+//! with the name removed, which covers parameters, return type, locals and
+//! every block. Of each group of equal functions the first by name is kept,
+//! the others are removed and calls to them are redirected to it.
+//!
+//! Redirecting calls can make their callers identical in turn, which is merged
+//! the next time the pass is run.
+
+use {
+    crate::boom::{
+        passes::Pass,
+        pretty_print::print_function_stable,
+        visitor::{Visitor, Walkable},
+        Ast, FunctionDefinition, FunctionSignature, Statement,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap},
+};
+
+/// Replaces functions by an identical representative
+#[derive(Debug, Default)]
+pub struct DeduplicateFunctions;
+
+impl DeduplicateFunctions {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for DeduplicateFunctions {
+    fn name(&self) -> &'static str {
+        "DeduplicateFunctions"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        let redirects = find_duplicates(&ast.get());
+        if redirects.is_empty() {
+            return false;
+        }
+
+        let mut ast = ast.get_mut();
+        ast.functions
+            .retain(|name, _| !redirects.contains_key(name));
+
+        let mut redirector = Redirector {
+            redirects: &redirects,
+        };
+        ast.functions
+            .values()
+            .for_each(|def| redirector.visit_function_definition(def));
+
+        true
+    }
+}
+
+/// Maps each duplicate function to the function replacing it
+fn find_duplicates(ast: &Ast) -> HashMap<InternedString, InternedString> {
+    let mut names = ast.functions.keys().copied().collect::<Vec<_>>();
+    names.sort_by_key(ToString::to_string);
+
+    let mut representatives = HashMap::<String, InternedString>::default();
+    let mut redirects = HashMap::default();
+
+    for name in names {
+        let representative = *representatives
+            .entry(anonymous_body(&ast.functions[&name]))
+            .or_insert(name);
+
+        if representative != name {
+            redirects.insert(name, representative);
+        }
+    }
+
+    redirects
+}
+
+/// Prints a function without its name
+fn anonymous_body(def: &FunctionDefinition) -> String {
+    let anonymous = FunctionDefinition {
+        signature: FunctionSignature {
+            name: "".into(),
+            ..def.signature.clone()
+        },
+        entry_block: def.entry_block.clone(),
+    };
+
+    let mut buf = vec![];
+    print_function_stable(&mut buf, &anonymous);
+    String::from_utf8(buf).unwrap()
+}
+
+/// Points calls to duplicate functions at their representatives
+struct Redirector<'a> {
+    redirects: &'a HashMap<InternedString, InternedString>,
+}
+
+impl Visitor for Redirector<'_> {
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        if let Statement::FunctionCall { name, .. } = &mut *node.get_mut() {
+            if let Some(representative) = self.redirects.get(name) {
+                *name = *representative;
+            }
+        }
+
+        node.get().walk(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{deduplicate_functions::DeduplicateFunctions, Pass},
+            test_utils::*,
+            Operation, Size, Statement,
+        },
+        common::intern::InternedString,
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn identical_functions_merged() {
+        init();
+
+        let helper = |name| {
+            function(
+                name,
+                vec![
+                    declare("t", bits(Size::Static(8))),
+                    copy("t", Operation::And(ident("t"), int(1)).into()),
+                ],
+            )
+        };
+        let (first, second) = (call("a", "helper_a", vec![]), call("b", "helper_b", vec![]));
+
+        let ast = ast([
+            helper("helper_a"),
+            helper("helper_b"),
+            function(
+                "other",
+                vec![
+                    declare("t", bits(Size::Static(16))),
+                    copy("t", Operation::And(ident("t"), int(1)).into()),
+                ],
+            ),
+            function("main", vec![first.clone(), second.clone()]),
+        ]);
+
+        assert!(DeduplicateFunctions.run(ast.clone()));
+        assert!(!DeduplicateFunctions.run(ast.clone()));
+
+        let mut names = ast
+            .get()
+            .functions
+            .keys()
+            .map(InternedString::to_string)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["helper_a", "main", "other"]);
+
+        for statement in [first, second] {
+            assert!(matches!(
+                &*statement.get(),
+                Statement::FunctionCall { name, .. } if name.as_ref() == "helper_a"
+            ));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Writes the functions of the AST to a file between other passes
+//!
+//! Output is stable, so dumps taken before and after a pass can be diffed to
+//! see exactly which statements it changed.
+
+use {
+    crate::boom::{passes::Pass, pretty_print::print_functions_stable, Ast},
+    common::shared::Shared,
+    log::warn,
+    std::{
+        fs::{create_dir_all, File},
+        io::BufWriter,
+        path::PathBuf,
+    },
+};
+
+/// Dumps the BOOM IR to a file, never changes the AST
+#[derive(Debug)]
+pub struct DumpIr {
+    path: PathBuf,
+}
+
+impl DumpIr {
+    /// Creates a pass that dumps to `path`, overwriting the file each run
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Create a new Pass object
+    pub fn new_boxed<P: Into<PathBuf>>(path: P) -> Box<dyn Pass> {
+        Box::new(Self::new(path))
+    }
+}
+
+impl Pass for DumpIr {
+    fn name(&self) -> &'static str {
+        "DumpIr"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        if let Some(parent) = self.path.parent() {
+            if let Err(e) = create_dir_all(parent) {
+                warn!("failed to create directory {parent:?}: {e}");
+                return false;
+            }
+        }
+
+        match File::create(&self.path) {
+            Ok(file) => print_functions_stable(&mut BufWriter::new(file), ast),
+            Err(e) => warn!("failed to create IR dump {:?}: {e}", self.path),
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{dump_ir::DumpIr, Pass},
+            test_utils::*,
+            Size,
+        },
+        std::fs,
+    };
+
+    #[test]
+    fn dump() {
+        init();
+
+        let ast = ast([
+            function("g", vec![]),
+            function(
+                "f",
+                vec![declare("x", bits(Size::Static(8))), copy("x", int(5))],
+            ),
+        ]);
+
+        let path = std::env::temp_dir()
+            .join(format!("dump_ir_{}", std::process::id()))
+            .join("ast.boom");
+
+        assert!(!DumpIr::new(&path).run(ast.clone()));
+        let first = fs::read_to_string(&path).unwrap();
+
+        assert!(first.contains("fn f("));
+        assert!(first.contains("x = 5;"));
+        // sorted by function name
+        assert!(first.find("fn f(").unwrap() < first.find("fn g(").unwrap());
+
+        DumpIr::new(&path).run(ast);
+        assert_eq!(fs::read_to_string(&path).unwrap(), first);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
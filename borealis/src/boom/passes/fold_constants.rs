@@ -0,0 +1,423 @@
+//! Operations on constant operands can be evaluated at compile time
+//!
+//! Bitvector handlers emit many shifts and masks over literals, these are
+//! folded into a single literal. Identities such as shifts by zero are also
+//! removed when the other operand is not constant.
+//!
+//! Folded integers wrap to the range of the unsigned 64-bit backing value, as
+//! the operations they replace would, so a complement of zero is folded to the
+//! same literal as a bitvector of 64 ones. Comparisons fold to the integers 0
+//! and 1, like the single bit bitvectors the comparison builtins return.
+
+use {
+    crate::boom::{
+        passes::{
+            any::AnyExt,
+            resolve_bitvectors::{mask, MAX_LENGTH},
+            Pass,
+        },
+        visitor::{Visitor, Walkable},
+        Ast, Literal, Operation, Value,
+    },
+    common::shared::Shared,
+    num_bigint::BigInt,
+};
+
+/// Folds operations on constant operands into literals
+#[derive(Debug, Default)]
+pub struct FoldConstants {
+    did_change: bool,
+}
+
+impl FoldConstants {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for FoldConstants {
+    fn name(&self) -> &'static str {
+        "FoldConstants"
+    }
+
+    fn reset(&mut self) {
+        self.did_change = false;
+    }
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get()
+            .functions
+            .values()
+            .map(|def| {
+                self.reset();
+                self.visit_function_definition(def);
+                self.did_change
+            })
+            .any()
+    }
+}
+
+impl Visitor for FoldConstants {
+    fn visit_value(&mut self, node: Shared<Value>) {
+        // fold operands first so nested operations collapse in a single run
+        node.get().walk(self);
+
+        let folded = match &*node.get() {
+            Value::Operation(operation) => fold(operation),
+            _ => None,
+        };
+
+        if let Some(folded) = folded {
+            *node.get_mut() = folded;
+            self.did_change = true;
+        }
+    }
+}
+
+/// Gets the value of an integer literal
+fn int(value: &Shared<Value>) -> Option<BigInt> {
+    match &*value.get() {
+        Value::Literal(literal) => match &*literal.get() {
+            Literal::Int(int) => Some(int.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Gets a shift amount, if it is a constant that fits in a `usize`
+fn shift_amount(value: &Shared<Value>) -> Option<usize> {
+    int(value).and_then(|amount| usize::try_from(amount).ok())
+}
+
+/// Reduces a value modulo 2^64, into the range of the backing value
+fn wrap(value: BigInt) -> BigInt {
+    value & mask(MAX_LENGTH)
+}
+
+/// Gets the integer literal a folded comparison is replaced with
+fn truth(value: bool) -> Value {
+    Value::Literal(Shared::new(Literal::Int(u8::from(value).into())))
+}
+
+/// Orders two constants, if they have the same order as signed and unsigned
+/// integers
+///
+/// Operations are not typed, so a backing value with the top bit set may be a
+/// negative signed integer or a large unsigned one. Comparisons of such values
+/// are left to the generated code.
+fn compare(
+    lhs: &Shared<Value>,
+    rhs: &Shared<Value>,
+    ordering: fn(&BigInt, &BigInt) -> bool,
+) -> Option<Value> {
+    let (lhs, rhs) = (wrap(int(lhs)?), wrap(int(rhs)?));
+
+    let sign = BigInt::from(1) << (MAX_LENGTH - 1);
+    if lhs >= sign || rhs >= sign {
+        return None;
+    }
+
+    Some(truth(ordering(&lhs, &rhs)))
+}
+
+/// Evaluates an operation, returning the value it should be replaced with
+fn fold(operation: &Operation) -> Option<Value> {
+    let literal = |literal| Some(Value::Literal(Shared::new(literal)));
+    let wrapped = |value| literal(Literal::Int(wrap(value)));
+
+    match operation {
+        Operation::Complement(value) => wrapped(!int(value)?),
+
+        Operation::Add(lhs, rhs) => wrapped(int(lhs)? + int(rhs)?),
+        Operation::Subtract(lhs, rhs) => wrapped(int(lhs)? - int(rhs)?),
+
+        Operation::Multiply(lhs, rhs) => match (int(lhs), int(rhs)) {
+            (Some(lhs), Some(rhs)) => wrapped(lhs * rhs),
+            (Some(one), None) if one == BigInt::from(1) => Some(rhs.get().clone()),
+            (None, Some(one)) if one == BigInt::from(1) => Some(lhs.get().clone()),
+            _ => None,
+        },
+
+        Operation::And(lhs, rhs) => match (int(lhs), int(rhs)) {
+            (Some(lhs), Some(rhs)) => wrapped(lhs & rhs),
+            // masking with zero
+            (Some(zero), None) | (None, Some(zero)) if zero == BigInt::from(0) => {
+                literal(Literal::Int(zero))
+            }
+            _ => None,
+        },
+
+        Operation::Or(lhs, rhs) => match (int(lhs), int(rhs)) {
+            (Some(lhs), Some(rhs)) => wrapped(lhs | rhs),
+            (Some(zero), None) if zero == BigInt::from(0) => Some(rhs.get().clone()),
+            (None, Some(zero)) if zero == BigInt::from(0) => Some(lhs.get().clone()),
+            _ => None,
+        },
+
+        Operation::Xor(lhs, rhs) => match (int(lhs), int(rhs)) {
+            (Some(lhs), Some(rhs)) => wrapped(lhs ^ rhs),
+            (Some(zero), None) if zero == BigInt::from(0) => Some(rhs.get().clone()),
+            (None, Some(zero)) if zero == BigInt::from(0) => Some(lhs.get().clone()),
+            _ => None,
+        },
+
+        Operation::LeftShift(value, amount) | Operation::RightShift(value, amount) => {
+            let amount = shift_amount(amount)?;
+
+            match int(value) {
+                Some(value) => match operation {
+                    Operation::LeftShift(..) => wrapped(value << amount),
+                    // shifts in zeros as the backing value is unsigned
+                    _ => wrapped(wrap(value) >> amount),
+                },
+                // shifting by zero
+                None if amount == 0 => Some(value.get().clone()),
+                None => None,
+            }
+        }
+
+        // equal backing values are equal whether signed or unsigned
+        Operation::Equal(lhs, rhs) => match (int(lhs), int(rhs)) {
+            (Some(lhs), Some(rhs)) => Some(truth(wrap(lhs) == wrap(rhs))),
+            _ => None,
+        },
+        Operation::NotEqual(lhs, rhs) => match (int(lhs), int(rhs)) {
+            (Some(lhs), Some(rhs)) => Some(truth(wrap(lhs) != wrap(rhs))),
+            _ => None,
+        },
+
+        Operation::LessThan(lhs, rhs) => compare(lhs, rhs, |lhs, rhs| lhs < rhs),
+        Operation::LessThanOrEqual(lhs, rhs) => compare(lhs, rhs, |lhs, rhs| lhs <= rhs),
+        Operation::GreaterThan(lhs, rhs) => compare(lhs, rhs, |lhs, rhs| lhs > rhs),
+        Operation::GreaterThanOrEqual(lhs, rhs) => compare(lhs, rhs, |lhs, rhs| lhs >= rhs),
+
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{fold_constants::FoldConstants, Pass},
+            test_utils::*,
+            Operation, Value,
+        },
+        common::shared::Shared,
+        pretty_assertions::assert_eq,
+    };
+
+    /// Folds `value` as the source of a copy, returning the rendered result
+    fn folded(value: Shared<Value>) -> String {
+        init();
+
+        let statement = copy("y", value);
+        let ast = ast([function("f", vec![statement.clone()])]);
+
+        FoldConstants::default().run(ast.clone());
+        assert!(!FoldConstants::default().run(ast));
+
+        copied_value(&statement)
+    }
+
+    #[test]
+    fn and() {
+        assert_eq!(folded(Operation::And(int(0xFF), int(0x3C)).into()), "60");
+    }
+
+    #[test]
+    fn and_zero_mask() {
+        assert_eq!(folded(Operation::And(ident("x"), int(0)).into()), "0");
+        assert_eq!(folded(Operation::And(int(0), ident("x")).into()), "0");
+    }
+
+    #[test]
+    fn masked_complement() {
+        // `not_vec` of a 4-bit `0b0101`
+        let value = Operation::And(Operation::Complement(int(0b0101)).into(), int(0xF));
+
+        assert_eq!(folded(value.into()), "10");
+    }
+
+    #[test]
+    fn wrapping_add() {
+        // `add_bits` of 8-bit `0xFF` and `1`
+        let value = Operation::And(Operation::Add(int(0xFF), int(1)).into(), int(0xFF));
+
+        assert_eq!(folded(value.into()), "0");
+    }
+
+    #[test]
+    fn wrapping_subtract() {
+        let value = Operation::And(Operation::Subtract(int(0), int(1)).into(), int(0xFF));
+
+        assert_eq!(folded(value.into()), "255");
+    }
+
+    #[test]
+    fn wrapping_add_64_bits() {
+        let value = Operation::Add(int(u64::MAX), int(1));
+
+        assert_eq!(folded(value.into()), "0");
+    }
+
+    #[test]
+    fn wrapping_subtract_64_bits() {
+        let value = Operation::Subtract(int(0), int(1));
+
+        assert_eq!(folded(value.into()), u64::MAX.to_string());
+    }
+
+    #[test]
+    fn wrapping_complement_and_shifts() {
+        assert_eq!(
+            folded(Operation::Complement(int(0)).into()),
+            u64::MAX.to_string()
+        );
+        assert_eq!(folded(Operation::LeftShift(int(1), int(64)).into()), "0");
+        assert_eq!(folded(Operation::RightShift(int(-1), int(60)).into()), "15");
+    }
+
+    #[test]
+    fn multiply() {
+        assert_eq!(folded(Operation::Multiply(int(6), int(7)).into()), "42");
+        assert_eq!(folded(Operation::Multiply(int(1), ident("x")).into()), "x");
+    }
+
+    #[test]
+    fn multiply_power_of_two() {
+        assert_eq!(folded(Operation::Multiply(int(5), int(8)).into()), "40");
+        assert_eq!(
+            folded(Operation::Multiply(ident("x"), int(8)).into()),
+            "(x * 8)"
+        );
+    }
+
+    #[test]
+    fn arithmetic_operands() {
+        // operands of each arithmetic operation are walked and folded first
+        let sum = Operation::Add(Operation::Multiply(int(2), int(3)).into(), int(1));
+        let difference = Operation::Subtract(int(10), Operation::Add(int(2), int(2)).into());
+        let product = Operation::Multiply(Operation::Subtract(int(5), int(1)).into(), int(2));
+
+        assert_eq!(folded(sum.into()), "7");
+        assert_eq!(folded(difference.into()), "6");
+        assert_eq!(folded(product.into()), "8");
+    }
+
+    #[test]
+    fn or() {
+        assert_eq!(folded(Operation::Or(int(0x50), int(0x05)).into()), "85");
+    }
+
+    #[test]
+    fn or_zero() {
+        assert_eq!(folded(Operation::Or(int(0), ident("x")).into()), "x");
+        assert_eq!(folded(Operation::Or(ident("x"), int(0)).into()), "x");
+    }
+
+    #[test]
+    fn xor() {
+        assert_eq!(folded(Operation::Xor(int(0b1100), int(0b1010)).into()), "6");
+        assert_eq!(folded(Operation::Xor(ident("x"), int(0)).into()), "x");
+    }
+
+    #[test]
+    fn xor_operands() {
+        // both operands are walked and folded before the XOR itself
+        let value = Operation::Xor(
+            Operation::Or(int(0b1000), int(0b0100)).into(),
+            Operation::LeftShift(int(0b0101), int(1)).into(),
+        );
+
+        assert_eq!(folded(value.into()), "6");
+    }
+
+    #[test]
+    fn left_shift() {
+        assert_eq!(folded(Operation::LeftShift(int(3), int(4)).into()), "48");
+    }
+
+    #[test]
+    fn right_shift() {
+        assert_eq!(folded(Operation::RightShift(int(48), int(4)).into()), "3");
+    }
+
+    #[test]
+    fn shift_by_zero() {
+        assert_eq!(folded(Operation::LeftShift(int(5), int(0)).into()), "5");
+        assert_eq!(folded(Operation::LeftShift(ident("x"), int(0)).into()), "x");
+        assert_eq!(
+            folded(Operation::RightShift(ident("x"), int(0)).into()),
+            "x"
+        );
+    }
+
+    #[test]
+    fn runtime_shift() {
+        assert_eq!(
+            folded(Operation::LeftShift(int(1), ident("n")).into()),
+            "(1 << n)"
+        );
+    }
+
+    #[test]
+    fn equal() {
+        assert_eq!(folded(Operation::Equal(int(2), int(2)).into()), "1");
+        assert_eq!(folded(Operation::Equal(int(2), int(3)).into()), "0");
+    }
+
+    #[test]
+    fn ordering() {
+        assert_eq!(folded(Operation::LessThan(int(1), int(2)).into()), "1");
+        assert_eq!(
+            folded(Operation::LessThanOrEqual(int(3), int(2)).into()),
+            "0"
+        );
+        assert_eq!(folded(Operation::GreaterThan(int(3), int(2)).into()), "1");
+        assert_eq!(
+            folded(Operation::GreaterThanOrEqual(int(2), int(2)).into()),
+            "1"
+        );
+    }
+
+    #[test]
+    fn equal_64_bits() {
+        // `Ones(64)` compared with a folded complement of zero
+        let value = Operation::Equal(int(u64::MAX), Operation::Complement(int(0)).into());
+
+        assert_eq!(folded(value.into()), "1");
+        assert_eq!(
+            folded(Operation::NotEqual(int(u64::MAX), int(-1)).into()),
+            "0"
+        );
+    }
+
+    #[test]
+    fn ordering_top_bit_set() {
+        // 2^63 is greater than 1 unsigned but less than 1 signed, so is not folded
+        let value = Operation::LessThan(Operation::Add(int(1u64 << 63), int(0)).into(), int(1));
+
+        assert_eq!(folded(value.into()), "(9223372036854775808 < 1)");
+        assert_eq!(
+            folded(Operation::LessThan(int(-1), int(0)).into()),
+            "(-1 < 0)"
+        );
+    }
+
+    #[test]
+    fn not_equal() {
+        assert_eq!(folded(Operation::NotEqual(int(2), int(2)).into()), "0");
+        assert_eq!(folded(Operation::NotEqual(int(2), int(3)).into()), "1");
+    }
+
+    #[test]
+    fn nested() {
+        // emitted by concatenating a zero with a literal
+        let value = Operation::Or(Operation::LeftShift(int(0), int(8)).into(), int(5));
+
+        assert_eq!(folded(value.into()), "5");
+    }
+}
@@ -146,3 +146,86 @@ fn fold_graph(entry_block: ControlFlowBlock) -> bool {
 
     did_change
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            control_flow::{ControlFlowBlock, Terminator},
+            passes::{fold_unconditionals::FoldUnconditionals, Pass},
+            test_utils::*,
+            Value,
+        },
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn straight_line() {
+        init();
+
+        let def = function("f", vec![copy("x", int(1))]);
+
+        // blocks are only held by the terminators jumping to them, once folded
+        // they are dropped and no longer parents
+        {
+            let (second, third) = (ControlFlowBlock::new(), ControlFlowBlock::new());
+            second.set_statements(vec![copy("y", int(2))]);
+            third.set_statements(vec![copy("z", int(3))]);
+            third.set_terminator(Terminator::Return(None));
+            second.set_terminator(Terminator::Unconditional { target: third });
+            def.entry_block
+                .set_terminator(Terminator::Unconditional { target: second });
+        }
+
+        let ast = ast([def.clone()]);
+        assert!(FoldUnconditionals.run(ast.clone()));
+        assert!(!FoldUnconditionals.run(ast));
+
+        assert_eq!(def.entry_block.iter().count(), 1);
+        assert_eq!(
+            def.entry_block
+                .statements()
+                .iter()
+                .map(|statement| statement.get().to_string())
+                .collect::<Vec<_>>(),
+            ["x = 1;", "y = 2;", "z = 3;"]
+        );
+        assert!(matches!(
+            def.entry_block.terminator(),
+            Terminator::Return(None)
+        ));
+    }
+
+    #[test]
+    fn join_point_kept() {
+        init();
+
+        let def = function("f", vec![]);
+
+        let (left, right, join) = (
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+        );
+        left.set_statements(vec![copy("x", int(1))]);
+        right.set_statements(vec![copy("x", int(2))]);
+        join.set_statements(vec![copy("y", ident("x"))]);
+        left.set_terminator(Terminator::Unconditional {
+            target: join.clone(),
+        });
+        right.set_terminator(Terminator::Unconditional {
+            target: join.clone(),
+        });
+        def.entry_block.set_terminator(Terminator::Conditional {
+            condition: Value::Identifier("c".into()),
+            target: left.clone(),
+            fallthrough: right.clone(),
+        });
+
+        let ast = ast([def.clone()]);
+        assert!(!FoldUnconditionals.run(ast));
+
+        assert_eq!(def.entry_block.iter().count(), 4);
+        assert_eq!(join.statements().len(), 1);
+    }
+}
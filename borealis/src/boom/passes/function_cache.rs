@@ -0,0 +1,262 @@
+//! On-disk cache of functions after running a pipeline
+//!
+//! Each function is keyed by a hash of its stable pretty-printed body, the keys
+//! of the functions it calls, and a salt covering the build of borealis, the
+//! passes and their configuration, the definitions and the registers. Editing
+//! a function changes its key and the keys of every function calling it,
+//! directly or indirectly, while the other functions are read back from the
+//! cache rather than run through the pipeline again. Mutually recursive
+//! functions are hashed together.
+//!
+//! Entries are JSON files named by their key. Keys are hashed with xxHash,
+//! which is stable across runs, and the build is identified by a hash of the
+//! running executable, so entries are only reused by the same build.
+
+use {
+    crate::boom::{
+        call_graph::CallGraph, pretty_print::print_function_stable, Ast, FunctionDefinition,
+    },
+    common::{intern::InternedString, HashMap},
+    log::warn,
+    once_cell::sync::Lazy,
+    std::{
+        collections::BTreeMap,
+        env, fs,
+        hash::{Hash, Hasher},
+        path::PathBuf,
+        time::SystemTime,
+    },
+    twox_hash::XxHash64,
+};
+
+/// Functions after running a pipeline, keyed by their contents before
+#[derive(Debug)]
+pub struct FunctionCache {
+    dir: PathBuf,
+    /// Functions run through the pipeline in the last run, as they were not
+    /// cached
+    processed: Vec<InternedString>,
+}
+
+impl FunctionCache {
+    /// Opens the cache stored in `dir`, which is created on the first store
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            processed: vec![],
+        }
+    }
+
+    /// Gets the functions run through the pipeline in the last run, sorted by
+    /// name
+    pub fn processed(&self) -> &[InternedString] {
+        &self.processed
+    }
+
+    pub(crate) fn set_processed<I: IntoIterator<Item = InternedString>>(&mut self, names: I) {
+        self.processed = names.into_iter().collect();
+        self.processed.sort_by_key(ToString::to_string);
+    }
+
+    /// Reads a cached function, a missing or unreadable entry is a miss
+    pub(crate) fn load(&self, key: u64) -> Option<FunctionDefinition> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    /// Writes a function to the cache, failing to do so only loses the entry
+    pub(crate) fn store(&self, key: u64, def: &FunctionDefinition) {
+        let result = fs::create_dir_all(&self.dir)
+            .map_err(|e| e.to_string())
+            .and_then(|()| serde_json::to_vec(def).map_err(|e| e.to_string()))
+            .and_then(|bytes| fs::write(self.path(key), bytes).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            warn!("failed to cache {}: {e}", def.signature.name);
+        }
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{key:016x}.json"))
+    }
+}
+
+/// Identifies the build of borealis by a hash of the running executable
+static BUILD_ID: Lazy<u64> = Lazy::new(|| {
+    let contents = env::current_exe().and_then(fs::read);
+
+    let mut state = XxHash64::default();
+    match contents {
+        Ok(contents) => contents.hash(&mut state),
+        // never matches an existing entry
+        Err(e) => {
+            warn!("failed to identify build, function cache disabled: {e}");
+            SystemTime::now().hash(&mut state);
+        }
+    }
+    state.finish()
+});
+
+/// Hashes everything other than function bodies that affects the result of a
+/// pipeline, given the name and configuration of each pass
+pub fn context_salt(ast: &Ast, passes: &[(&str, String)], max_rounds: usize) -> u64 {
+    let registers = ast
+        .registers
+        .iter()
+        .map(|(name, register)| (name.to_string(), register))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut state = XxHash64::default();
+    BUILD_ID.hash(&mut state);
+    passes.hash(&mut state);
+    max_rounds.hash(&mut state);
+    serde_json::to_string(&ast.definitions)
+        .unwrap()
+        .hash(&mut state);
+    serde_json::to_string(&registers).unwrap().hash(&mut state);
+    state.finish()
+}
+
+/// Computes the cache key of every function
+pub fn cache_keys(ast: &Ast, salt: u64) -> HashMap<InternedString, u64> {
+    let calls = CallGraph::build(ast);
+    let mut keys = HashMap::<InternedString, u64>::default();
+
+    // callees first, so their keys are known
+    for component in calls.strongly_connected_components() {
+        let mut members = component.clone();
+        members.sort_by_key(ToString::to_string);
+
+        let mut state = XxHash64::default();
+        salt.hash(&mut state);
+
+        for name in &members {
+            let mut buf = vec![];
+            print_function_stable(&mut buf, &ast.functions[name]);
+            buf.hash(&mut state);
+        }
+
+        let mut callees = members
+            .iter()
+            .flat_map(|name| calls.callees(*name))
+            .filter(|callee| !members.contains(callee))
+            .filter_map(|callee| Some((callee.to_string(), *keys.get(&callee)?)))
+            .collect::<Vec<_>>();
+        callees.sort();
+        callees.hash(&mut state);
+
+        let component_key = state.finish();
+        for name in members {
+            let mut state = XxHash64::default();
+            component_key.hash(&mut state);
+            name.as_ref().hash(&mut state);
+            keys.insert(name, state.finish());
+        }
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{
+                function_cache::FunctionCache,
+                manager::{OptLevel, PassManager},
+                resolve_bitvectors::ResolveBitvectors,
+            },
+            test_utils::*,
+            Ast, Size,
+        },
+        common::{intern::InternedString, shared::Shared},
+        pretty_assertions::assert_eq,
+        std::{env, fs},
+    };
+
+    /// Builds `f` calling `g`, which creates `length` zeros, and the unrelated
+    /// `h`
+    fn model(length: i32) -> Shared<Ast> {
+        ast([
+            function(
+                "f",
+                vec![declare("y", bits(Size::Static(8))), call("y", "g", vec![])],
+            ),
+            function(
+                "g",
+                vec![
+                    declare("x", bits(Size::Unknown)),
+                    call("x", "Zeros", vec![int(length)]),
+                ],
+            ),
+            function(
+                "h",
+                vec![
+                    declare("z", bits(Size::Unknown)),
+                    call("z", "Ones", vec![int(4)]),
+                ],
+            ),
+        ])
+    }
+
+    fn run(ast: Shared<Ast>, cache: &mut FunctionCache) -> Vec<String> {
+        let diagnostics =
+            PassManager::with_opt_level(OptLevel::None).run_to_fixpoint_cached(ast, cache);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        cache
+            .processed()
+            .iter()
+            .map(InternedString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn changed_function_and_callers_rerun() {
+        init();
+
+        let dir = env::temp_dir().join(format!("borealis-function-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut cache = FunctionCache::new(&dir);
+
+        assert_eq!(run(model(8), &mut cache), ["f", "g", "h"]);
+
+        let ast = model(8);
+        assert!(run(ast.clone(), &mut cache).is_empty());
+        assert!(matches!(size_of(&ast, "h", "z"), Some(Size::Static(4))));
+
+        let ast = model(16);
+        assert_eq!(run(ast.clone(), &mut cache), ["f", "g"]);
+        assert!(matches!(size_of(&ast, "g", "x"), Some(Size::Static(16))));
+        assert!(matches!(size_of(&ast, "h", "z"), Some(Size::Static(4))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn configuration_changes_keys() {
+        init();
+
+        let dir = env::temp_dir().join(format!(
+            "borealis-function-cache-configuration-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let mut cache = FunctionCache::new(&dir);
+
+        let mut run = |strict_builtins| {
+            let mut manager = PassManager::new();
+            manager.add(Box::new(
+                ResolveBitvectors::default().with_strict_builtins(strict_builtins),
+            ));
+            manager.run_to_fixpoint_cached(model(8), &mut cache);
+            cache.processed().len()
+        };
+
+        assert_eq!(run(false), 3);
+        assert_eq!(run(false), 0);
+        assert_eq!(run(true), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,635 @@
+//! Inlines small functions into their callers
+//!
+//! After monomorphization there are many small functions called only once or
+//! twice, the call overhead of which dominates their bodies. Calls to
+//! functions with fewer statements than the threshold are replaced by a copy
+//! of the callee's blocks, with its parameters and locals renamed so they do
+//! not collide with those of the caller.
+//!
+//! The block containing the call is split in two: the first half assigns the
+//! arguments and jumps to the copied entry block, and each copied return
+//! assigns the call destination and jumps to the second half.
+//!
+//! Recursive functions (directly or through other functions) and functions
+//! with reference parameters are never inlined.
+
+use {
+    crate::boom::{
+        call_graph::CallGraph,
+        control_flow::{ControlFlowBlock, Terminator},
+        passes::Pass,
+        Ast, Expression, FunctionDefinition, NamedValue, Operation, Size, Spans, Statement, Type,
+        Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap},
+};
+
+/// Default maximum number of statements in an inlined function
+const DEFAULT_THRESHOLD: usize = 8;
+
+/// Inlines calls to functions below a size threshold
+#[derive(Debug)]
+pub struct Inline {
+    /// Functions with at least this many statements are not inlined
+    threshold: usize,
+    /// Number of calls inlined, used to give each copy unique names
+    count: usize,
+}
+
+impl Default for Inline {
+    fn default() -> Self {
+        Self::new(DEFAULT_THRESHOLD)
+    }
+}
+
+impl Inline {
+    /// Creates a pass inlining functions with fewer than `threshold`
+    /// statements
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            count: 0,
+        }
+    }
+
+    /// Create a new Pass object
+    pub fn new_boxed(threshold: usize) -> Box<dyn Pass> {
+        Box::new(Self::new(threshold))
+    }
+
+    /// Determines whether a call in `caller` to `callee` can be inlined
+    fn can_inline(
+        &self,
+        caller: InternedString,
+        callee: &FunctionDefinition,
+        calls: &CallGraph,
+    ) -> bool {
+        callee.signature.name != caller
+            && !calls.is_recursive(callee.signature.name)
+            && !callee
+                .signature
+                .parameters
+                .get()
+                .iter()
+                .any(|parameter| parameter.is_ref)
+            && statement_count(callee) < self.threshold
+    }
+
+    /// Inlines eligible calls in a function until none remain, returning
+    /// whether any were inlined
+    fn inline_calls(
+        &mut self,
+        def: &FunctionDefinition,
+        functions: &HashMap<InternedString, FunctionDefinition>,
+        calls: &CallGraph,
+        spans: &mut Spans,
+    ) -> bool {
+        let mut did_change = false;
+
+        loop {
+            let call = def.entry_block.iter().find_map(|block| {
+                block
+                    .statements()
+                    .iter()
+                    .enumerate()
+                    .find_map(|(index, statement)| match &*statement.get() {
+                        Statement::FunctionCall { name, .. } => functions
+                            .get(name)
+                            .filter(|callee| self.can_inline(def.signature.name, callee, calls))
+                            .map(|callee| (block.clone(), index, callee.clone())),
+                        _ => None,
+                    })
+            });
+
+            let Some((block, index, callee)) = call else {
+                return did_change;
+            };
+
+            self.inline_call(&block, index, &callee, spans);
+            did_change = true;
+        }
+    }
+
+    /// Replaces the call at `index` in `block` with a copy of `callee`
+    ///
+    /// Copied statements keep the spans of the callee's, the statements
+    /// assigning the arguments and result take the span of the call.
+    fn inline_call(
+        &mut self,
+        block: &ControlFlowBlock,
+        index: usize,
+        callee: &FunctionDefinition,
+        spans: &mut Spans,
+    ) {
+        let Statement::FunctionCall {
+            expression,
+            arguments,
+            ..
+        } = block.statements()[index].get().clone()
+        else {
+            unreachable!("inlined statement must be a call");
+        };
+
+        let names = self.rename_locals(callee);
+        let body = callee.entry_block.deep_clone();
+
+        // second half of the split block, run after the callee returns
+        let statements = block.statements();
+        let call = statements[index].clone();
+        let continuation = ControlFlowBlock::new();
+        continuation.set_statements(statements[index + 1..].to_vec());
+        continuation.set_terminator(block.terminator());
+
+        for copied in body.iter() {
+            copied.set_statements(
+                copied
+                    .statements()
+                    .iter()
+                    .map(|statement| rename_statement(statement, &names))
+                    .collect(),
+            );
+
+            let terminator = match copied.terminator() {
+                Terminator::Return(value) => {
+                    if let (Some(expression), Some(value)) = (&expression, value) {
+                        let assignment: Shared<Statement> = Statement::Copy {
+                            expression: expression.clone(),
+                            value: rename_value(&Shared::new(value), &names),
+                        }
+                        .into();
+                        spans.inherit(&call, &assignment);
+
+                        let mut statements = copied.statements();
+                        statements.push(assignment);
+                        copied.set_statements(statements);
+                    }
+
+                    Terminator::Unconditional {
+                        target: continuation.clone(),
+                    }
+                }
+                Terminator::Panic(values) => {
+                    Terminator::Panic(values.iter().map(|v| rename_value(v, &names)).collect())
+                }
+                Terminator::Conditional {
+                    condition,
+                    target,
+                    fallthrough,
+                } => Terminator::Conditional {
+                    condition: rename_value(&Shared::new(condition), &names).get().clone(),
+                    target,
+                    fallthrough,
+                },
+                terminator @ Terminator::Unconditional { .. } => terminator,
+            };
+
+            copied.set_terminator(terminator);
+        }
+        spans.inherit_copy(&callee.entry_block, &body);
+
+        // first half, assigns the arguments to the renamed parameters
+        let parameters = callee
+            .signature
+            .parameters
+            .get()
+            .iter()
+            .zip(arguments)
+            .flat_map(|(parameter, argument)| {
+                let name = names[&parameter.name];
+                [
+                    Statement::TypeDeclaration {
+                        name,
+                        typ: Shared::new(parameter.typ.get().clone()),
+                    }
+                    .into(),
+                    Statement::Copy {
+                        expression: Expression::Identifier(name),
+                        value: argument,
+                    }
+                    .into(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        parameters
+            .iter()
+            .for_each(|parameter| spans.inherit(&call, parameter));
+
+        block.set_statements(
+            statements[..index]
+                .iter()
+                .cloned()
+                .chain(parameters)
+                .collect(),
+        );
+        block.set_terminator(Terminator::Unconditional { target: body });
+    }
+
+    /// Gets unique names for the parameters and locals of a callee
+    fn rename_locals(
+        &mut self,
+        callee: &FunctionDefinition,
+    ) -> HashMap<InternedString, InternedString> {
+        let prefix = format!("{}_{}", callee.signature.name, self.count);
+        self.count += 1;
+
+        fn declared(statement: &Shared<Statement>, names: &mut Vec<InternedString>) {
+            match &*statement.get() {
+                Statement::TypeDeclaration { name, .. } => names.push(*name),
+                Statement::If {
+                    if_body, else_body, ..
+                } => if_body
+                    .iter()
+                    .chain(else_body)
+                    .for_each(|statement| declared(statement, names)),
+                _ => (),
+            }
+        }
+
+        let mut locals = callee
+            .signature
+            .parameters
+            .get()
+            .iter()
+            .map(|parameter| parameter.name)
+            .collect::<Vec<_>>();
+        callee
+            .entry_block
+            .iter()
+            .flat_map(|block| block.statements())
+            .for_each(|statement| declared(&statement, &mut locals));
+
+        locals
+            .into_iter()
+            .map(|local| (local, InternedString::from(format!("{prefix}_{local}"))))
+            .collect()
+    }
+}
+
+impl Pass for Inline {
+    fn name(&self) -> &'static str {
+        "Inline"
+    }
+
+    fn reset(&mut self) {}
+
+    fn configuration(&self) -> String {
+        format!("threshold={}", self.threshold)
+    }
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        let mut ast = ast.get_mut();
+        let functions = ast.functions.clone();
+        let calls = CallGraph::build(&ast);
+
+        let mut names = functions.keys().copied().collect::<Vec<_>>();
+        names.sort_by_key(ToString::to_string);
+
+        let mut did_change = false;
+        for name in names {
+            did_change |= self.inline_calls(&functions[&name], &functions, &calls, &mut ast.spans);
+        }
+
+        did_change
+    }
+}
+
+/// Gets the number of statements in a function, including nested statements
+fn statement_count(def: &FunctionDefinition) -> usize {
+    fn count(statement: &Shared<Statement>) -> usize {
+        match &*statement.get() {
+            Statement::If {
+                if_body, else_body, ..
+            } => 1 + if_body.iter().chain(else_body).map(count).sum::<usize>(),
+            _ => 1,
+        }
+    }
+
+    def.entry_block
+        .iter()
+        .flat_map(|block| block.statements())
+        .map(|statement| count(&statement))
+        .sum()
+}
+
+fn rename(
+    ident: InternedString,
+    names: &HashMap<InternedString, InternedString>,
+) -> InternedString {
+    names.get(&ident).copied().unwrap_or(ident)
+}
+
+/// Copies a statement with renamed locals, the copy shares no values with the
+/// original
+fn rename_statement(
+    statement: &Shared<Statement>,
+    names: &HashMap<InternedString, InternedString>,
+) -> Shared<Statement> {
+    let value = |value: &Shared<Value>| rename_value(value, names);
+    let values = |values: &[Shared<Value>]| values.iter().map(value).collect();
+    let statements = |statements: &[Shared<Statement>]| {
+        statements
+            .iter()
+            .map(|statement| rename_statement(statement, names))
+            .collect()
+    };
+
+    match &*statement.get() {
+        Statement::TypeDeclaration { name, typ } => Statement::TypeDeclaration {
+            name: rename(*name, names),
+            typ: rename_type(typ, names),
+        },
+        Statement::Copy {
+            expression,
+            value: v,
+        } => Statement::Copy {
+            expression: rename_expression(expression, names),
+            value: value(v),
+        },
+        Statement::FunctionCall {
+            expression,
+            name,
+            arguments,
+        } => Statement::FunctionCall {
+            expression: expression
+                .as_ref()
+                .map(|expression| rename_expression(expression, names)),
+            name: *name,
+            arguments: values(arguments),
+        },
+        Statement::Jump { condition, target } => Statement::Jump {
+            condition: value(condition),
+            target: *target,
+        },
+        Statement::If {
+            condition,
+            if_body,
+            else_body,
+        } => Statement::If {
+            condition: value(condition),
+            if_body: statements(if_body),
+            else_body: statements(else_body),
+        },
+        Statement::Panic(panic_values) => Statement::Panic(values(panic_values)),
+        statement @ (Statement::Label(_)
+        | Statement::Goto(_)
+        | Statement::End(_)
+        | Statement::Undefined
+        | Statement::Exit(_)
+        | Statement::Comment(_)) => statement.clone(),
+    }
+    .into()
+}
+
+fn rename_expression(
+    expression: &Expression,
+    names: &HashMap<InternedString, InternedString>,
+) -> Expression {
+    match expression {
+        Expression::Identifier(ident) => Expression::Identifier(rename(*ident, names)),
+        Expression::Field { expression, field } => Expression::Field {
+            expression: Box::new(rename_expression(expression, names)),
+            field: *field,
+        },
+        Expression::Address(expression) => {
+            Expression::Address(Box::new(rename_expression(expression, names)))
+        }
+        Expression::TupleMember { expression, index } => Expression::TupleMember {
+            expression: Box::new(rename_expression(expression, names)),
+            index: *index,
+        },
+    }
+}
+
+/// Copies a type, renaming locals in runtime lengths
+fn rename_type(
+    typ: &Shared<Type>,
+    names: &HashMap<InternedString, InternedString>,
+) -> Shared<Type> {
+    let mut typ = typ.get().clone();
+
+    if let Some(Size::Runtime(length)) = typ.get_size_mut() {
+        *length = rename_value(length, names);
+    }
+
+    Shared::new(typ)
+}
+
+fn rename_value(
+    value: &Shared<Value>,
+    names: &HashMap<InternedString, InternedString>,
+) -> Shared<Value> {
+    let v = |value: &Shared<Value>| rename_value(value, names);
+
+    Shared::new(match &*value.get() {
+        Value::Identifier(ident) => Value::Identifier(rename(*ident, names)),
+        Value::Literal(literal) => Value::Literal(Shared::new(literal.get().clone())),
+        Value::Operation(operation) => Value::Operation(match operation {
+            Operation::Not(a) => Operation::Not(v(a)),
+            Operation::Complement(a) => Operation::Complement(v(a)),
+            Operation::Cast(a, typ) => Operation::Cast(v(a), rename_type(typ, names)),
+            Operation::Equal(a, b) => Operation::Equal(v(a), v(b)),
+            Operation::NotEqual(a, b) => Operation::NotEqual(v(a), v(b)),
+            Operation::LessThan(a, b) => Operation::LessThan(v(a), v(b)),
+            Operation::LessThanOrEqual(a, b) => Operation::LessThanOrEqual(v(a), v(b)),
+            Operation::GreaterThan(a, b) => Operation::GreaterThan(v(a), v(b)),
+            Operation::GreaterThanOrEqual(a, b) => Operation::GreaterThanOrEqual(v(a), v(b)),
+            Operation::Subtract(a, b) => Operation::Subtract(v(a), v(b)),
+            Operation::Add(a, b) => Operation::Add(v(a), v(b)),
+            Operation::Or(a, b) => Operation::Or(v(a), v(b)),
+            Operation::Multiply(a, b) => Operation::Multiply(v(a), v(b)),
+            Operation::And(a, b) => Operation::And(v(a), v(b)),
+            Operation::Xor(a, b) => Operation::Xor(v(a), v(b)),
+            Operation::Divide(a, b) => Operation::Divide(v(a), v(b)),
+            Operation::LogicalAnd(a, b) => Operation::LogicalAnd(v(a), v(b)),
+            Operation::LogicalOr(a, b) => Operation::LogicalOr(v(a), v(b)),
+            Operation::LeftShift(a, b) => Operation::LeftShift(v(a), v(b)),
+            Operation::RightShift(a, b) => Operation::RightShift(v(a), v(b)),
+            Operation::ArithmeticRightShift(a, b) => Operation::ArithmeticRightShift(v(a), v(b)),
+            Operation::RotateRight(a, b) => Operation::RotateRight(v(a), v(b)),
+            Operation::RotateLeft(a, b) => Operation::RotateLeft(v(a), v(b)),
+        }),
+        Value::Struct { name, fields } => Value::Struct {
+            name: *name,
+            fields: fields
+                .iter()
+                .map(|field| NamedValue {
+                    name: field.name,
+                    value: v(&field.value),
+                })
+                .collect(),
+        },
+        Value::Field { value, field_name } => Value::Field {
+            value: v(value),
+            field_name: *field_name,
+        },
+        Value::CtorKind {
+            value,
+            identifier,
+            types,
+        } => Value::CtorKind {
+            value: v(value),
+            identifier: *identifier,
+            types: types.clone(),
+        },
+        Value::CtorUnwrap {
+            value,
+            identifier,
+            types,
+        } => Value::CtorUnwrap {
+            value: v(value),
+            identifier: *identifier,
+            types: types.clone(),
+        },
+        Value::Tuple(values) => Value::Tuple(values.iter().map(v).collect()),
+        Value::TupleMember { value, index } => Value::TupleMember {
+            value: v(value),
+            index: *index,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            control_flow::Terminator,
+            passes::{inline::Inline, Pass},
+            pretty_print::print_statement,
+            test_utils::*,
+            Ast, Parameter, Size, Value,
+        },
+        common::{intern::InternedString, shared::Shared},
+        pretty_assertions::assert_eq,
+    };
+
+    /// Renders the statements of every block of a function in order
+    fn statements(ast: &Shared<Ast>, name: &str) -> Vec<String> {
+        ast.get().functions[&InternedString::from(name)]
+            .entry_block
+            .iter()
+            .flat_map(|block| block.statements())
+            .map(|statement| {
+                let mut buf = vec![];
+                print_statement(&mut buf, statement);
+                String::from_utf8(buf).unwrap().trim().to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn helper() {
+        init();
+
+        // g(a) = not_vec(a)
+        let g = function(
+            "g",
+            vec![
+                declare("y", bits(Size::Static(8))),
+                call("y", "not_vec", vec![ident("a")]),
+            ],
+        );
+        *g.signature.parameters.get_mut() = vec![Parameter {
+            name: "a".into(),
+            typ: bits(Size::Static(8)),
+            is_ref: false,
+        }];
+        g.entry_block
+            .set_terminator(Terminator::Return(Some(Value::Identifier("y".into()))));
+
+        let f = function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("r", bits(Size::Static(8))),
+                call("r", "g", vec![ident("x")]),
+                copy("x", ident("r")),
+            ],
+        );
+
+        let ast = ast([f, g]);
+
+        assert!(Inline::default().run(ast.clone()));
+
+        let f = ast.get().functions[&InternedString::from("f")].clone();
+        assert!(!f.entry_block.get_functions().contains(&"g".into()));
+
+        assert_eq!(
+            statements(&ast, "f"),
+            [
+                "bv8 x;",
+                "bv8 r;",
+                "bv8 g_0_a;",
+                "g_0_a = x;",
+                "bv8 g_0_y;",
+                "g_0_y = not_vec(g_0_a);",
+                "r = g_0_y;",
+                "x = r;",
+            ]
+        );
+
+        // the callee itself is unchanged
+        assert_eq!(statements(&ast, "g")[1], "y = not_vec(a);");
+    }
+
+    #[test]
+    fn spans() {
+        init();
+
+        // g(a) = { y = 1; y }
+        let assignment = copy("y", int(1));
+        let g = function(
+            "g",
+            vec![declare("y", bits(Size::Static(8))), assignment.clone()],
+        );
+        *g.signature.parameters.get_mut() = vec![Parameter {
+            name: "a".into(),
+            typ: bits(Size::Static(8)),
+            is_ref: false,
+        }];
+        g.entry_block
+            .set_terminator(Terminator::Return(Some(Value::Identifier("y".into()))));
+
+        let call = call("r", "g", vec![ident("x")]);
+        let ast = ast([function("f", vec![call.clone()]), g]);
+        ast.get_mut().spans.insert(&assignment, span(3));
+        ast.get_mut().spans.insert(&call, span(10));
+
+        assert!(Inline::default().run(ast.clone()));
+
+        let ast = ast.get();
+        let lines = ast.functions[&InternedString::from("f")]
+            .entry_block
+            .iter()
+            .flat_map(|block| block.statements())
+            .map(|statement| ast.spans.get(&statement).map(|span| span.start.line))
+            .collect::<Vec<_>>();
+
+        // argument and result assignments take the span of the call, copied
+        // statements that of the original
+        assert_eq!(lines, [Some(10), Some(10), None, Some(3), Some(10)]);
+    }
+
+    #[test]
+    fn recursive() {
+        init();
+
+        let h = function("h", vec![call("r", "h", vec![])]);
+        let f = function("f", vec![call("r", "h", vec![])]);
+        let ast = ast([f, h]);
+
+        assert!(!Inline::default().run(ast.clone()));
+        assert_eq!(statements(&ast, "f"), ["r = h();"]);
+    }
+
+    #[test]
+    fn threshold() {
+        init();
+
+        let g = function("g", vec![copy("y", int(1)), copy("y", int(2))]);
+        let f = function("f", vec![call("r", "g", vec![])]);
+        let ast = ast([f, g]);
+
+        assert!(!Inline::new(2).run(ast.clone()));
+        assert!(Inline::new(3).run(ast));
+    }
+}
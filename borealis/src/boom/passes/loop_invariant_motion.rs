@@ -0,0 +1,407 @@
+//! Moves computations that do not change between loop iterations out of loops
+//!
+//! Decode loops recompute the same widths and masks on every iteration. A copy
+//! in a loop header of a pure operation on locals that are not assigned
+//! anywhere in the loop computes the same value each time, so it is moved to a
+//! preheader block that runs once before the loop is entered.
+//!
+//! Only the header is considered, as it runs whenever the loop is entered, so
+//! hoisting never assigns a local the loop would not have assigned. A copy is
+//! not hoisted if its destination is assigned elsewhere in the loop, or read
+//! in the header before the copy (the value from the previous iteration).
+
+use {
+    crate::boom::{
+        control_flow::{graph::ControlFlowGraph, ControlFlowBlock, Terminator},
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, Expression, FunctionDefinition, Statement, Value,
+    },
+    common::{identifiable::Id, intern::InternedString, shared::Shared, HashSet},
+};
+
+/// Hoists loop-invariant copies into loop preheaders
+#[derive(Debug, Default)]
+pub struct LoopInvariantMotion;
+
+impl LoopInvariantMotion {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for LoopInvariantMotion {
+    fn name(&self) -> &'static str {
+        "LoopInvariantMotion"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get().functions.values().map(hoist_function).any()
+    }
+}
+
+/// Natural loop, the blocks that can reach a back edge to the header without
+/// passing through the header
+struct Loop {
+    header: ControlFlowBlock,
+    blocks: HashSet<Id>,
+}
+
+fn hoist_function(def: &FunctionDefinition) -> bool {
+    let locals = locals(def);
+    let mut did_change = false;
+
+    // hoisting may make further copies invariant, and changes the graph
+    loop {
+        let cfg = ControlFlowGraph::build(def);
+
+        let hoisted = loops(&cfg).into_iter().any(|lup| {
+            // the entry block has no predecessors to put a preheader between
+            lup.header.id() != def.entry_block.id() && hoist_loop(&cfg, &lup, &locals)
+        });
+
+        if !hoisted {
+            return did_change;
+        }
+
+        did_change = true;
+    }
+}
+
+/// Hoists the first invariant copy in the header of a loop, returning whether
+/// one was found
+fn hoist_loop(cfg: &ControlFlowGraph, lup: &Loop, locals: &HashSet<InternedString>) -> bool {
+    let blocks = cfg
+        .blocks()
+        .into_iter()
+        .filter(|block| lup.blocks.contains(&block.id()))
+        .collect::<Vec<_>>();
+
+    // locals assigned anywhere in the loop, with the number of assignments
+    let mut written = vec![];
+    for block in &blocks {
+        for statement in block.statements() {
+            writes(&statement, &mut written);
+        }
+    }
+
+    let statements = lup.header.statements();
+    let mut read = HashSet::default();
+
+    for (index, statement) in statements.iter().enumerate() {
+        if let Statement::Copy {
+            expression: Expression::Identifier(destination),
+            value,
+        } = &*statement.get()
+        {
+            let invariant = matches!(&*value.get(), Value::Operation(_))
+                && is_pure(value)
+                && reads(value).iter().all(|ident| {
+                    locals.contains(ident) && !written.contains(ident) && ident != destination
+                })
+                && written.iter().filter(|ident| *ident == destination).count() == 1
+                && !read.contains(destination)
+                && locals.contains(destination);
+
+            if invariant {
+                let preheader = preheader(cfg, lup);
+
+                let mut remaining = statements.clone();
+                let hoisted = remaining.remove(index);
+                lup.header.set_statements(remaining);
+
+                let mut statements = preheader.statements();
+                statements.push(hoisted);
+                preheader.set_statements(statements);
+
+                return true;
+            }
+        }
+
+        read.extend(statement_reads(statement));
+    }
+
+    false
+}
+
+/// Gets the block running once before the loop, creating one if the header has
+/// several predecessors outside the loop
+fn preheader(cfg: &ControlFlowGraph, lup: &Loop) -> ControlFlowBlock {
+    let outside = cfg
+        .predecessors(&lup.header)
+        .into_iter()
+        .filter(|predecessor| !lup.blocks.contains(&predecessor.id()))
+        .collect::<Vec<_>>();
+
+    if let [predecessor] = outside.as_slice() {
+        if matches!(predecessor.terminator(), Terminator::Unconditional { .. }) {
+            return predecessor.clone();
+        }
+    }
+
+    let preheader = ControlFlowBlock::new();
+    preheader.set_terminator(Terminator::Unconditional {
+        target: lup.header.clone(),
+    });
+
+    let retarget = |block: ControlFlowBlock| {
+        if block.id() == lup.header.id() {
+            preheader.clone()
+        } else {
+            block
+        }
+    };
+
+    for predecessor in outside {
+        let terminator = match predecessor.terminator() {
+            Terminator::Conditional {
+                condition,
+                target,
+                fallthrough,
+            } => Terminator::Conditional {
+                condition,
+                target: retarget(target),
+                fallthrough: retarget(fallthrough),
+            },
+            Terminator::Unconditional { target } => Terminator::Unconditional {
+                target: retarget(target),
+            },
+            terminator => terminator,
+        };
+
+        predecessor.set_terminator(terminator);
+    }
+
+    preheader
+}
+
+/// Finds the natural loops of a function, merging loops sharing a header
+fn loops(cfg: &ControlFlowGraph) -> Vec<Loop> {
+    let mut loops = Vec::<Loop>::new();
+
+    for latch in cfg.blocks() {
+        for header in cfg.successors(&latch) {
+            if !cfg.dominates(&header, &latch) {
+                continue;
+            }
+
+            let index = match loops.iter().position(|lup| lup.header.id() == header.id()) {
+                Some(index) => index,
+                None => {
+                    loops.push(Loop {
+                        header: header.clone(),
+                        blocks: [header.id()].into_iter().collect(),
+                    });
+                    loops.len() - 1
+                }
+            };
+
+            let blocks = &mut loops[index].blocks;
+            let mut remaining = vec![latch.clone()];
+            while let Some(block) = remaining.pop() {
+                if blocks.insert(block.id()) {
+                    remaining.extend(cfg.predecessors(&block));
+                }
+            }
+        }
+    }
+
+    loops
+}
+
+/// Gets the parameters and declared locals of a function
+fn locals(def: &FunctionDefinition) -> HashSet<InternedString> {
+    let mut locals = def
+        .signature
+        .parameters
+        .get()
+        .iter()
+        .map(|parameter| parameter.name)
+        .collect::<HashSet<_>>();
+
+    for block in def.entry_block.iter() {
+        let mut declared = vec![];
+        for statement in block.statements() {
+            writes(&statement, &mut declared);
+        }
+        locals.extend(declared);
+    }
+
+    locals
+}
+
+/// Collects every local assigned or declared by a statement, once per
+/// assignment
+fn writes(statement: &Shared<Statement>, written: &mut Vec<InternedString>) {
+    fn root(expression: &Expression) -> InternedString {
+        match expression {
+            Expression::Identifier(ident) => *ident,
+            Expression::Field { expression, .. }
+            | Expression::TupleMember { expression, .. }
+            | Expression::Address(expression) => root(expression),
+        }
+    }
+
+    match &*statement.get() {
+        Statement::TypeDeclaration { name, .. } => written.push(*name),
+        Statement::Copy { expression, .. }
+        | Statement::FunctionCall {
+            expression: Some(expression),
+            ..
+        } => written.push(root(expression)),
+        Statement::If {
+            if_body, else_body, ..
+        } => if_body
+            .iter()
+            .chain(else_body)
+            .for_each(|statement| writes(statement, written)),
+        _ => (),
+    }
+}
+
+/// Determines whether a value is built only from locals, literals and
+/// operations
+fn is_pure(value: &Shared<Value>) -> bool {
+    match &*value.get() {
+        Value::Identifier(_) | Value::Literal(_) => true,
+        Value::Operation(_) => {
+            let mut pure = true;
+            let mut operands = Operands(vec![]);
+            value.get().walk(&mut operands);
+            for operand in operands.0 {
+                pure &= is_pure(&operand);
+            }
+            pure
+        }
+        _ => false,
+    }
+}
+
+/// Collects the direct operands of an operation
+struct Operands(Vec<Shared<Value>>);
+
+impl Visitor for Operands {
+    fn visit_value(&mut self, node: Shared<Value>) {
+        self.0.push(node);
+    }
+}
+
+/// Collects the identifiers read by values
+#[derive(Default)]
+struct Reads(HashSet<InternedString>);
+
+impl Visitor for Reads {
+    fn visit_value(&mut self, node: Shared<Value>) {
+        if let Value::Identifier(ident) = &*node.get() {
+            self.0.insert(*ident);
+        }
+
+        node.get().walk(self);
+    }
+}
+
+/// Gets the identifiers read by a value
+fn reads(value: &Shared<Value>) -> HashSet<InternedString> {
+    let mut reads = Reads::default();
+    reads.visit_value(value.clone());
+    reads.0
+}
+
+/// Gets the identifiers read by a statement
+fn statement_reads(statement: &Shared<Statement>) -> HashSet<InternedString> {
+    let mut reads = Reads::default();
+    reads.visit_statement(statement.clone());
+    reads.0
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            control_flow::{ControlFlowBlock, Terminator},
+            passes::{loop_invariant_motion::LoopInvariantMotion, Pass},
+            pretty_print::print_statement,
+            test_utils::*,
+            Operation, Size, Value,
+        },
+        pretty_assertions::assert_eq,
+    };
+
+    fn statements(block: &ControlFlowBlock) -> Vec<String> {
+        block
+            .statements()
+            .into_iter()
+            .map(|statement| {
+                let mut buf = vec![];
+                print_statement(&mut buf, statement);
+                String::from_utf8(buf).unwrap().trim().to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn invariant_mask() {
+        init();
+
+        let def = function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("m", bits(Size::Static(4))),
+                declare("i", bits(Size::Static(8))),
+                copy("i", int(0)),
+            ],
+        );
+
+        // entry -> header <-> body, header -> exit
+        let (header, body, exit) = (
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+        );
+        header.set_statements(vec![
+            copy(
+                "m",
+                Operation::And(Operation::RightShift(ident("x"), int(4)).into(), int(15)).into(),
+            ),
+            copy("i", Operation::Add(ident("i"), int(1)).into()),
+        ]);
+        header.set_terminator(Terminator::Conditional {
+            condition: Value::Identifier("c".into()),
+            target: body.clone(),
+            fallthrough: exit.clone(),
+        });
+        body.set_terminator(Terminator::Unconditional {
+            target: header.clone(),
+        });
+        def.entry_block.set_terminator(Terminator::Conditional {
+            condition: Value::Identifier("c".into()),
+            target: header.clone(),
+            fallthrough: exit,
+        });
+
+        let ast = ast([def.clone()]);
+
+        assert!(LoopInvariantMotion.run(ast.clone()));
+        assert!(!LoopInvariantMotion.run(ast));
+
+        // `i` is carried between iterations so stays in the loop
+        assert_eq!(statements(&header), ["i = (i + 1);"]);
+
+        // a preheader was inserted between the entry block and the header
+        let Terminator::Conditional {
+            target: preheader, ..
+        } = def.entry_block.terminator()
+        else {
+            panic!("entry terminator changed");
+        };
+        assert_eq!(statements(&preheader), ["m = ((x >> 4) & 15);"]);
+        assert!(matches!(
+            preheader.terminator(),
+            Terminator::Unconditional { target } if target.id() == header.id()
+        ));
+    }
+}
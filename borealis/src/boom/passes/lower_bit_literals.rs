@@ -0,0 +1,195 @@
+//! Lowers bit literals to integers
+//!
+//! Bitvectors are backed by an integer, so every `Literal::Bits` is replaced by
+//! the `Literal::Int` of its value. The length of the literal is kept: copied
+//! directly into a local of unknown length, it becomes the length of that
+//! local, otherwise the integer is cast to a bitvector of that length.
+
+use {
+    crate::boom::{
+        bits_to_bigint,
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, Expression, FunctionDefinition, Literal, Operation, Size, Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap},
+    num_bigint::BigInt,
+};
+
+/// Replaces bit literals with integer literals
+#[derive(Debug, Default)]
+pub struct LowerBitLiterals {
+    did_change: bool,
+    locals: HashMap<InternedString, Shared<Type>>,
+}
+
+impl LowerBitLiterals {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for LowerBitLiterals {
+    fn name(&self) -> &'static str {
+        "LowerBitLiterals"
+    }
+
+    fn reset(&mut self) {
+        self.did_change = false;
+        self.locals.clear();
+    }
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get()
+            .functions
+            .values()
+            .map(|def| {
+                self.reset();
+                self.visit_function_definition(def);
+                self.did_change
+            })
+            .any()
+    }
+}
+
+impl Visitor for LowerBitLiterals {
+    fn visit_function_definition(&mut self, node: &FunctionDefinition) {
+        self.locals = node
+            .signature
+            .parameters
+            .get()
+            .iter()
+            .map(|parameter| (parameter.name, parameter.typ.clone()))
+            .collect();
+
+        node.walk(self);
+    }
+
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        match &*node.get() {
+            Statement::TypeDeclaration { name, typ } => {
+                self.locals.insert(*name, typ.clone());
+            }
+
+            Statement::Copy {
+                expression: Expression::Identifier(destination),
+                value,
+            } => {
+                if let Some(length) = bits_length(value) {
+                    let unknown = self.locals.get(destination).and_then(|typ| {
+                        matches!(
+                            &*typ.get(),
+                            Type::Bits {
+                                size: Size::Unknown
+                            }
+                        )
+                        .then(|| typ.clone())
+                    });
+
+                    // the destination records the length, no cast needed
+                    if let Some(typ) = unknown {
+                        *typ.get_mut() = Type::Bits {
+                            size: Size::Static(length),
+                        };
+                        let lowered = Literal::Int(bits_value(value));
+                        *value.get_mut() = Value::Literal(Shared::new(lowered));
+                        self.did_change = true;
+                    }
+                }
+            }
+
+            _ => (),
+        }
+
+        node.get().walk(self);
+    }
+
+    fn visit_value(&mut self, node: Shared<Value>) {
+        node.get().walk(self);
+
+        let Some(length) = bits_length(&node) else {
+            return;
+        };
+
+        let lowered = Operation::Cast(
+            Literal::Int(bits_value(&node)).into(),
+            Shared::new(Type::Bits {
+                size: Size::Static(length),
+            }),
+        );
+
+        *node.get_mut() = Value::Operation(lowered);
+        self.did_change = true;
+    }
+}
+
+/// Gets the length of a bit literal
+fn bits_length(value: &Shared<Value>) -> Option<usize> {
+    match &*value.get() {
+        Value::Literal(literal) => match &*literal.get() {
+            Literal::Bits(bits) => Some(bits.len()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Gets the integer value of a bit literal
+fn bits_value(value: &Shared<Value>) -> BigInt {
+    let Value::Literal(literal) = &*value.get() else {
+        unreachable!("value must be a bit literal");
+    };
+    let Literal::Bits(bits) = &*literal.get() else {
+        unreachable!("value must be a bit literal");
+    };
+
+    bits_to_bigint(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{lower_bit_literals::LowerBitLiterals, Pass},
+            pretty_print::print_statement,
+            test_utils::*,
+            Size,
+        },
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn call_argument() {
+        init();
+
+        let statement = call("y", "g", vec![ident("x"), bits_literal("0101")]);
+        let ast = ast([function("f", vec![statement.clone()])]);
+
+        assert!(LowerBitLiterals::default().run(ast.clone()));
+        assert!(!LowerBitLiterals::default().run(ast));
+
+        let mut buf = vec![];
+        print_statement(&mut buf, statement);
+        assert_eq!(
+            String::from_utf8(buf).unwrap().trim(),
+            "y = g(x, 5 as bv4);"
+        );
+    }
+
+    #[test]
+    fn copy_sets_length() {
+        init();
+
+        let statement = copy("y", bits_literal("110"));
+        let ast = ast([function(
+            "f",
+            vec![declare("y", bits(Size::Unknown)), statement.clone()],
+        )]);
+
+        assert!(LowerBitLiterals::default().run(ast.clone()));
+
+        assert_eq!(copied_value(&statement), "6");
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(3))));
+    }
+}
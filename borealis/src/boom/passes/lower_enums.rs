@@ -0,0 +1,177 @@
+//! Lowers comparisons with enum variants to comparisons of discriminants
+//!
+//! Enums are backed by integers, the discriminant of a variant being its index
+//! in the definition. A variant compared for equality, such as `c == EQ`, is
+//! replaced by its discriminant cast to the enum type, `c == 0 as cond`, so
+//! the comparison is of integers but the enum name is kept for emission.
+//!
+//! Locals sharing a name with a variant are not replaced.
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, Definition, FunctionDefinition, Literal, Operation, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap},
+};
+
+/// Replaces compared enum variants with their discriminants
+#[derive(Debug, Default)]
+pub struct LowerEnums;
+
+impl LowerEnums {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for LowerEnums {
+    fn name(&self) -> &'static str {
+        "LowerEnums"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        let ast = ast.get();
+
+        let variants = ast
+            .definitions
+            .iter()
+            .filter_map(|definition| match definition {
+                Definition::Enum { name, variants } => Some((name, variants)),
+                _ => None,
+            })
+            .flat_map(|(name, variants)| {
+                let typ = Type::Enum {
+                    name: *name,
+                    variants: variants.clone(),
+                };
+                variants
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, variant)| (*variant, (index, typ.clone())))
+            })
+            .collect::<HashMap<_, _>>();
+
+        if variants.is_empty() {
+            return false;
+        }
+
+        ast.functions
+            .values()
+            .map(|def| lower_function(def, &variants))
+            .any()
+    }
+}
+
+/// Discriminant and enum type of each variant
+type Variants = HashMap<InternedString, (usize, Type)>;
+
+fn lower_function(def: &FunctionDefinition, variants: &Variants) -> bool {
+    let mut lowerer = Lowerer {
+        def,
+        variants,
+        did_change: false,
+    };
+    lowerer.visit_function_definition(def);
+    lowerer.did_change
+}
+
+struct Lowerer<'a> {
+    def: &'a FunctionDefinition,
+    variants: &'a Variants,
+    did_change: bool,
+}
+
+impl Visitor for Lowerer<'_> {
+    fn visit_value(&mut self, node: Shared<Value>) {
+        if let Value::Operation(Operation::Equal(lhs, rhs) | Operation::NotEqual(lhs, rhs)) =
+            &*node.get()
+        {
+            for operand in [lhs, rhs] {
+                self.lower_operand(operand);
+            }
+        }
+
+        node.get().walk(self);
+    }
+}
+
+impl Lowerer<'_> {
+    fn lower_operand(&mut self, operand: &Shared<Value>) {
+        let Some(ident) = operand.get().get_ident() else {
+            return;
+        };
+
+        let Some((discriminant, typ)) = self.variants.get(&ident) else {
+            return;
+        };
+
+        if self.def.get_ident_type(ident).is_some() {
+            return;
+        }
+
+        *operand.get_mut() = Value::Operation(Operation::Cast(
+            Literal::Int((*discriminant).into()).into(),
+            Shared::new(typ.clone()),
+        ));
+        self.did_change = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{lower_enums::LowerEnums, Pass},
+            test_utils::*,
+            Definition, Operation, Type,
+        },
+        common::{intern::InternedString, shared::Shared},
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn compare_variants() {
+        init();
+
+        let variants = ["EQ", "NE", "CS", "CC"]
+            .into_iter()
+            .map(InternedString::from)
+            .collect::<Vec<_>>();
+        let cond = Shared::new(Type::Enum {
+            name: "cond".into(),
+            variants: variants.clone(),
+        });
+
+        let (equal, not_equal, locals) = (
+            copy("x", Operation::Equal(ident("c"), ident("CS")).into()),
+            copy("y", Operation::NotEqual(ident("CC"), ident("c")).into()),
+            copy("z", Operation::Equal(ident("c"), ident("d")).into()),
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("c", cond.clone()),
+                declare("d", cond),
+                equal.clone(),
+                not_equal.clone(),
+                locals.clone(),
+            ],
+        )]);
+        ast.get_mut().definitions.push(Definition::Enum {
+            name: "cond".into(),
+            variants,
+        });
+
+        assert!(LowerEnums.run(ast.clone()));
+        assert!(!LowerEnums.run(ast));
+
+        assert_eq!(copied_value(&equal), "(c == 2 as cond)");
+        assert_eq!(copied_value(&not_equal), "(3 as cond != c)");
+        assert_eq!(copied_value(&locals), "(c == d)");
+    }
+}
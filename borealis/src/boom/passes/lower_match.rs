@@ -0,0 +1,242 @@
+//! Lowers comparisons with bit patterns containing wildcards
+//!
+//! Sail compiles `match` to a chain of conditionals before BOOM, so decoding
+//! an opcode becomes a sequence of comparisons of the scrutinee with each
+//! pattern. Patterns may contain wildcard bits, such as `0b1?01`, which have no
+//! integer value. A comparison `x == 0b1?01` is replaced by the masked
+//! comparison `(x & 0b1011) == 0b1001`, the mask selecting the fixed bits and
+//! the wildcards compared as zero.
+//!
+//! A scrutinee local of unknown length takes the length of the pattern.
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, Bit, FunctionDefinition, Literal, Operation, Size, Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap},
+};
+
+/// Replaces comparisons with wildcard patterns by masked comparisons
+#[derive(Debug, Default)]
+pub struct LowerMatch;
+
+impl LowerMatch {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for LowerMatch {
+    fn name(&self) -> &'static str {
+        "LowerMatch"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get().functions.values().map(lower_function).any()
+    }
+}
+
+fn lower_function(def: &FunctionDefinition) -> bool {
+    let mut lowerer = Lowerer {
+        locals: def
+            .signature
+            .parameters
+            .get()
+            .iter()
+            .map(|parameter| (parameter.name, parameter.typ.clone()))
+            .collect(),
+        did_change: false,
+    };
+    lowerer.visit_function_definition(def);
+    lowerer.did_change
+}
+
+struct Lowerer {
+    locals: HashMap<InternedString, Shared<Type>>,
+    did_change: bool,
+}
+
+impl Visitor for Lowerer {
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        if let Statement::TypeDeclaration { name, typ } = &*node.get() {
+            self.locals.insert(*name, typ.clone());
+        }
+
+        node.get().walk(self);
+    }
+
+    fn visit_value(&mut self, node: Shared<Value>) {
+        node.get().walk(self);
+
+        let (scrutinee, pattern) = match &*node.get() {
+            Value::Operation(Operation::Equal(lhs, rhs) | Operation::NotEqual(lhs, rhs)) => {
+                match (wildcard_pattern(lhs), wildcard_pattern(rhs)) {
+                    (None, Some(pattern)) => (lhs.clone(), pattern),
+                    (Some(pattern), None) => (rhs.clone(), pattern),
+                    _ => return,
+                }
+            }
+            _ => return,
+        };
+
+        self.resolve_scrutinee(&scrutinee, pattern.len());
+
+        let (mask, value) = pattern_mask(&pattern);
+        let operation = match &*node.get() {
+            Value::Operation(Operation::Equal(..)) => Operation::Equal,
+            _ => Operation::NotEqual,
+        };
+
+        let lowered = make_pattern_match(
+            operation,
+            scrutinee,
+            Literal::Bits(mask).into(),
+            Literal::Bits(value).into(),
+        );
+        *node.get_mut() = Value::Operation(lowered);
+        self.did_change = true;
+    }
+}
+
+impl Lowerer {
+    /// Sets the length of a scrutinee local of unknown length
+    fn resolve_scrutinee(&mut self, scrutinee: &Shared<Value>, length: usize) {
+        let Some(typ) = scrutinee
+            .get()
+            .get_ident()
+            .and_then(|ident| self.locals.get(&ident).cloned())
+        else {
+            return;
+        };
+
+        let unknown = matches!(
+            &*typ.get(),
+            Type::Bits {
+                size: Size::Unknown
+            }
+        );
+        if unknown {
+            *typ.get_mut() = Type::Bits {
+                size: Size::Static(length),
+            };
+        }
+    }
+}
+
+/// Gets the mask selecting the fixed bits of a pattern, and the value of those
+/// bits, wildcards being zero in both
+pub fn pattern_mask(pattern: &[Bit]) -> (Vec<Bit>, Vec<Bit>) {
+    let mask = pattern
+        .iter()
+        .map(|bit| if bit.is_fixed() { Bit::One } else { Bit::Zero })
+        .collect();
+    let value = pattern
+        .iter()
+        .map(|bit| if bit.is_fixed() { *bit } else { Bit::Zero })
+        .collect();
+
+    (mask, value)
+}
+
+/// Builds the masked comparison `(scrutinee & mask) == pattern`, or with
+/// another `operation` such as `NotEqual`
+pub fn make_pattern_match(
+    operation: fn(Shared<Value>, Shared<Value>) -> Operation,
+    scrutinee: Shared<Value>,
+    mask: Shared<Value>,
+    pattern: Shared<Value>,
+) -> Operation {
+    operation(Operation::And(scrutinee, mask).into(), pattern)
+}
+
+/// Gets the bits of a bit literal containing at least one wildcard
+pub(crate) fn wildcard_pattern(value: &Shared<Value>) -> Option<Vec<Bit>> {
+    match &*value.get() {
+        Value::Literal(literal) => match &*literal.get() {
+            Literal::Bits(bits) if bits.iter().any(Bit::is_unknown) => Some(bits.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{
+                lower_match::{pattern_mask, LowerMatch},
+                Pass,
+            },
+            test_utils::*,
+            Literal, Operation, Size, Value,
+        },
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn wildcard_pattern() {
+        init();
+
+        let (wildcard, exact) = (
+            copy(
+                "a",
+                Operation::Equal(ident("op"), bits_literal("1x01")).into(),
+            ),
+            copy(
+                "b",
+                Operation::Equal(ident("op"), bits_literal("0011")).into(),
+            ),
+        );
+        let ast = ast([function(
+            "decode",
+            vec![
+                declare("op", bits(Size::Unknown)),
+                wildcard.clone(),
+                exact.clone(),
+            ],
+        )]);
+
+        assert!(LowerMatch.run(ast.clone()));
+        assert!(!LowerMatch.run(ast.clone()));
+
+        assert_eq!(
+            copied_value(&wildcard),
+            format!(
+                "((op & {}) == {})",
+                render(bits_literal("1011")),
+                render(bits_literal("1001"))
+            )
+        );
+        assert_eq!(
+            copied_value(&exact),
+            format!("(op == {})", render(bits_literal("0011")))
+        );
+        assert!(matches!(
+            size_of(&ast, "decode", "op"),
+            Some(Size::Static(4))
+        ));
+    }
+
+    #[test]
+    fn mask_of_pattern() {
+        init();
+
+        let bits = |value| match &*bits_literal(value).get() {
+            Value::Literal(literal) => match &*literal.get() {
+                Literal::Bits(bits) => bits.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+
+        let (mask, value) = pattern_mask(&bits("1x0x"));
+        assert!(mask == bits("1010"));
+        assert!(value == bits("1000"));
+    }
+}
@@ -0,0 +1,584 @@
+//! Lowers tuples into one local per element
+//!
+//! GenC has no tuple type, so a tuple-typed local `t` is replaced by locals
+//! `t_0`, `t_1`, ..., each declared with the type of its element (and so
+//! keeping its own size). Tuple construction and element accesses become
+//! copies into and reads of those locals, and tuple-typed parameters are
+//! split in the same way, with tuple arguments expanded at call sites.
+//!
+//! Functions returning tuples return a struct instead, with one field per
+//! element named `_0`, `_1`, .... A call assigning such a function to a tuple
+//! local declares the local with the struct type and copies each field into
+//! its element.
+//!
+//! Nested tuples are lowered one level per run.
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, Definition, Expression, FunctionDefinition, NamedType, NamedValue, Parameter, Spans,
+        Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap},
+};
+
+/// Splits tuples into one local per element
+#[derive(Debug, Default)]
+pub struct LowerTuples;
+
+impl LowerTuples {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for LowerTuples {
+    fn name(&self) -> &'static str {
+        "LowerTuples"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        let returns = lower_return_types(&ast);
+        let mut spans = std::mem::take(&mut ast.get_mut().spans);
+
+        let did_change = ast
+            .get()
+            .functions
+            .values()
+            .map(|def| lower_function(def, &returns, &mut spans))
+            .any();
+
+        ast.get_mut().spans = spans;
+        did_change || !returns.is_empty()
+    }
+}
+
+/// Name and fields of the struct returned by each function that returned a
+/// tuple
+type Returns = HashMap<InternedString, (InternedString, Vec<NamedType>)>;
+
+/// Replaces tuple return types with structs, adding their definitions to the
+/// AST
+fn lower_return_types(ast: &Shared<Ast>) -> Returns {
+    let returns = ast
+        .get()
+        .functions
+        .values()
+        .filter_map(|def| {
+            let return_type = def.signature.return_type.get().clone();
+            let Type::Tuple(elements) = return_type else {
+                return None;
+            };
+
+            let name = InternedString::from(format!("{}_tuple", def.signature.name));
+            let fields = elements
+                .iter()
+                .enumerate()
+                .map(|(index, typ)| NamedType {
+                    name: field(index),
+                    typ: typ.clone(),
+                })
+                .collect::<Vec<_>>();
+
+            *def.signature.return_type.get_mut() = Type::Struct {
+                name,
+                fields: fields.clone(),
+            };
+
+            Some((def.signature.name, (name, fields)))
+        })
+        .collect::<Returns>();
+
+    let mut definitions = returns
+        .values()
+        .map(|(name, fields)| Definition::Struct {
+            name: *name,
+            fields: fields.clone(),
+        })
+        .collect::<Vec<_>>();
+    definitions.sort_by_key(|definition| match definition {
+        Definition::Struct { name, .. } => name.to_string(),
+        _ => unreachable!(),
+    });
+    ast.get_mut().definitions.extend(definitions);
+
+    returns
+}
+
+/// Lowers the tuples of a function, the statements a statement is lowered to
+/// take its span
+fn lower_function(def: &FunctionDefinition, returns: &Returns, spans: &mut Spans) -> bool {
+    let mut lowerer = Lowerer {
+        tuples: HashMap::default(),
+        returns,
+        own_return: returns.get(&def.signature.name),
+        did_change: false,
+    };
+
+    for block in def.entry_block.iter() {
+        for statement in block.statements() {
+            if let Statement::TypeDeclaration { name, typ } = &*statement.get() {
+                if let Type::Tuple(elements) = &*typ.get() {
+                    lowerer.tuples.insert(*name, elements.clone());
+                }
+            }
+        }
+    }
+
+    lowerer.lower_parameters(def);
+
+    for block in def.entry_block.iter() {
+        let statements = block
+            .statements()
+            .into_iter()
+            .flat_map(|statement| {
+                let lowered = lowerer.lower_statement(statement.clone());
+                lowered
+                    .iter()
+                    .for_each(|lowered| spans.inherit(&statement, lowered));
+                lowered
+            })
+            .collect();
+        block.set_statements(statements);
+    }
+
+    lowerer.visit_function_definition(def);
+
+    lowerer.did_change
+}
+
+/// Gets the name of the local or struct field holding element `index`
+fn element(name: InternedString, index: usize) -> InternedString {
+    format!("{name}_{index}").into()
+}
+
+fn field(index: usize) -> InternedString {
+    format!("_{index}").into()
+}
+
+struct Lowerer<'a> {
+    /// Element types of tuple locals and parameters
+    tuples: HashMap<InternedString, Vec<Shared<Type>>>,
+    returns: &'a Returns,
+    /// Struct returned by the current function, if it returned a tuple
+    own_return: Option<&'a (InternedString, Vec<NamedType>)>,
+    did_change: bool,
+}
+
+impl Lowerer<'_> {
+    fn lower_parameters(&mut self, def: &FunctionDefinition) {
+        let parameters = def.signature.parameters.get().clone();
+
+        for parameter in &parameters {
+            if let Type::Tuple(elements) = &*parameter.typ.get() {
+                self.tuples.insert(parameter.name, elements.clone());
+            }
+        }
+
+        if !parameters
+            .iter()
+            .any(|parameter| self.tuples.contains_key(&parameter.name))
+        {
+            return;
+        }
+
+        *def.signature.parameters.get_mut() = parameters
+            .into_iter()
+            .flat_map(|parameter| match self.tuples.get(&parameter.name) {
+                Some(elements) => elements
+                    .iter()
+                    .enumerate()
+                    .map(|(index, typ)| Parameter {
+                        name: element(parameter.name, index),
+                        typ: typ.clone(),
+                        is_ref: parameter.is_ref,
+                    })
+                    .collect(),
+                None => vec![parameter],
+            })
+            .collect();
+        self.did_change = true;
+    }
+
+    fn lower_statement(&mut self, statement: Shared<Statement>) -> Vec<Shared<Statement>> {
+        let lowered = match &*statement.get() {
+            Statement::TypeDeclaration { name, .. } => self.tuples.get(name).map(|elements| {
+                elements
+                    .iter()
+                    .enumerate()
+                    .map(|(index, typ)| Statement::TypeDeclaration {
+                        name: element(*name, index),
+                        typ: typ.clone(),
+                    })
+                    .collect()
+            }),
+            Statement::Copy {
+                expression: Expression::Identifier(ident),
+                value,
+            } => self.lower_copy(*ident, value),
+            Statement::Copy { expression, value } => {
+                self.lower_expression(expression).map(|expression| {
+                    vec![Statement::Copy {
+                        expression,
+                        value: value.clone(),
+                    }]
+                })
+            }
+            Statement::FunctionCall {
+                expression,
+                name,
+                arguments,
+            } => self.lower_call(expression.as_ref(), *name, arguments),
+            _ => None,
+        };
+
+        match lowered {
+            Some(statements) => {
+                self.did_change = true;
+                statements.into_iter().map(Shared::new).collect()
+            }
+            None => vec![statement],
+        }
+    }
+
+    /// Lowers a copy of a whole tuple into `ident`
+    fn lower_copy(&self, ident: InternedString, value: &Shared<Value>) -> Option<Vec<Statement>> {
+        if let Some(elements) = self.tuples.get(&ident) {
+            let values = self.elements(value, elements.len());
+            return Some(
+                values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, value)| Statement::Copy {
+                        expression: Expression::Identifier(element(ident, index)),
+                        value,
+                    })
+                    .collect(),
+            );
+        }
+
+        let (name, fields) = self.own_return.filter(|_| ident.as_ref() == "return")?;
+        if !matches!(&*value.get(), Value::Tuple(_) | Value::Identifier(_)) {
+            return None;
+        }
+        let values = self.elements(value, fields.len());
+
+        Some(vec![Statement::Copy {
+            expression: Expression::Identifier(ident),
+            value: Shared::new(Value::Struct {
+                name: *name,
+                fields: values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, value)| NamedValue {
+                        name: field(index),
+                        value,
+                    })
+                    .collect(),
+            }),
+        }])
+    }
+
+    fn lower_call(
+        &self,
+        expression: Option<&Expression>,
+        name: InternedString,
+        arguments: &[Shared<Value>],
+    ) -> Option<Vec<Statement>> {
+        let expanded = arguments.iter().any(|argument| match &*argument.get() {
+            Value::Tuple(_) => true,
+            Value::Identifier(ident) => self.tuples.contains_key(ident),
+            _ => false,
+        });
+        let arguments = arguments
+            .iter()
+            .flat_map(|argument| match &*argument.get() {
+                Value::Tuple(values) => values.clone(),
+                Value::Identifier(ident) => match self.tuples.get(ident) {
+                    Some(elements) => (0..elements.len())
+                        .map(|index| Shared::new(Value::Identifier(element(*ident, index))))
+                        .collect(),
+                    None => vec![argument.clone()],
+                },
+                _ => vec![argument.clone()],
+            })
+            .collect::<Vec<_>>();
+
+        // tuple destination of a call returning a struct
+        if let (Some(Expression::Identifier(ident)), Some((struct_name, fields))) =
+            (expression, self.returns.get(&name))
+        {
+            if self.tuples.contains_key(ident) {
+                let mut statements = vec![
+                    Statement::TypeDeclaration {
+                        name: *ident,
+                        typ: Shared::new(Type::Struct {
+                            name: *struct_name,
+                            fields: fields.clone(),
+                        }),
+                    },
+                    Statement::FunctionCall {
+                        expression: Some(Expression::Identifier(*ident)),
+                        name,
+                        arguments,
+                    },
+                ];
+                statements.extend((0..fields.len()).map(|index| Statement::Copy {
+                    expression: Expression::Identifier(element(*ident, index)),
+                    value: Shared::new(Value::Field {
+                        value: Shared::new(Value::Identifier(*ident)),
+                        field_name: field(index),
+                    }),
+                }));
+                return Some(statements);
+            }
+        }
+
+        let lowered = expression.and_then(|expression| self.lower_expression(expression));
+        if !expanded && lowered.is_none() {
+            return None;
+        }
+
+        Some(vec![Statement::FunctionCall {
+            expression: lowered.or_else(|| expression.cloned()),
+            name,
+            arguments,
+        }])
+    }
+
+    /// Lowers a write to a tuple element, returning `None` if the expression
+    /// does not write to one
+    fn lower_expression(&self, expression: &Expression) -> Option<Expression> {
+        match expression {
+            Expression::TupleMember { expression, index } => match &**expression {
+                Expression::Identifier(ident) if self.tuples.contains_key(ident) => {
+                    Some(Expression::Identifier(element(*ident, *index)))
+                }
+                Expression::Identifier(ident)
+                    if self.own_return.is_some() && ident.as_ref() == "return" =>
+                {
+                    Some(Expression::Field {
+                        expression: expression.clone(),
+                        field: field(*index),
+                    })
+                }
+                _ => None,
+            },
+            Expression::Field { expression, field } => {
+                self.lower_expression(expression)
+                    .map(|expression| Expression::Field {
+                        expression: Box::new(expression),
+                        field: *field,
+                    })
+            }
+            Expression::Identifier(_) | Expression::Address(_) => None,
+        }
+    }
+
+    /// Gets the values of the elements of a tuple
+    fn elements(&self, value: &Shared<Value>, count: usize) -> Vec<Shared<Value>> {
+        match &*value.get() {
+            Value::Tuple(values) => return values.clone(),
+            Value::Identifier(ident) if self.tuples.contains_key(ident) => {
+                return (0..count)
+                    .map(|index| Shared::new(Value::Identifier(element(*ident, index))))
+                    .collect()
+            }
+            _ => (),
+        }
+
+        (0..count)
+            .map(|index| {
+                Shared::new(Value::TupleMember {
+                    value: value.clone(),
+                    index,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Visitor for Lowerer<'_> {
+    fn visit_value(&mut self, node: Shared<Value>) {
+        let lowered = match &*node.get() {
+            Value::TupleMember { value, index } => match &*value.get() {
+                Value::Identifier(ident) if self.tuples.contains_key(ident) => {
+                    Some(Value::Identifier(element(*ident, *index)))
+                }
+                Value::Identifier(ident)
+                    if self.own_return.is_some() && ident.as_ref() == "return" =>
+                {
+                    Some(Value::Field {
+                        value: value.clone(),
+                        field_name: field(*index),
+                    })
+                }
+                Value::Tuple(values) => values.get(*index).map(|value| value.get().clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(lowered) = lowered {
+            *node.get_mut() = lowered;
+            self.did_change = true;
+        }
+
+        node.get().walk(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{lower_tuples::LowerTuples, Pass},
+            test_utils::*,
+            Expression, Parameter, Size, Statement, Type, Value,
+        },
+        common::{intern::InternedString, shared::Shared},
+        pretty_assertions::assert_eq,
+    };
+
+    fn pair() -> Shared<Type> {
+        Shared::new(Type::Tuple(vec![
+            bits(Size::Static(8)),
+            bits(Size::Static(1)),
+        ]))
+    }
+
+    fn statements(ast: &Shared<crate::boom::Ast>, func: &str) -> Vec<String> {
+        ast.get()
+            .functions
+            .get(&InternedString::from(func))
+            .unwrap()
+            .entry_block
+            .statements()
+            .iter()
+            .map(|statement| statement.get().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn split_locals() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(1))),
+                declare("t", pair()),
+                copy("t", Shared::new(Value::Tuple(vec![ident("a"), ident("b")]))),
+                Statement::Copy {
+                    expression: Expression::TupleMember {
+                        expression: Box::new(Expression::Identifier("t".into())),
+                        index: 0,
+                    },
+                    value: ident("a"),
+                }
+                .into(),
+                copy(
+                    "b",
+                    Shared::new(Value::TupleMember {
+                        value: ident("t"),
+                        index: 1,
+                    }),
+                ),
+            ],
+        )]);
+
+        assert!(LowerTuples.run(ast.clone()));
+        assert!(!LowerTuples.run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "f", "t_0"), Some(Size::Static(8))));
+        assert!(matches!(size_of(&ast, "f", "t_1"), Some(Size::Static(1))));
+        assert!(size_of(&ast, "f", "t").is_none());
+        assert_eq!(
+            statements(&ast, "f")[2..],
+            ["bv8 t_0;", "bv1 t_1;", "t_0 = a;", "t_1 = b;", "t_0 = a;", "b = t_1;"]
+        );
+    }
+
+    #[test]
+    fn spans() {
+        init();
+
+        let assignment = copy("t", Shared::new(Value::Tuple(vec![ident("a"), ident("b")])));
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(1))),
+                declare("t", pair()),
+                assignment.clone(),
+            ],
+        )]);
+        ast.get_mut().spans.insert(&assignment, span(7));
+
+        assert!(LowerTuples.run(ast.clone()));
+
+        let ast = ast.get();
+        let statements = ast.functions[&InternedString::from("f")]
+            .entry_block
+            .statements();
+        assert_eq!(statements.len(), 6);
+
+        // both element assignments take the span of the tuple assignment
+        for statement in &statements[4..] {
+            assert_eq!(ast.spans.get(statement), Some(span(7)));
+        }
+    }
+
+    #[test]
+    fn split_signature() {
+        init();
+
+        let callee = function(
+            "g",
+            vec![Statement::Copy {
+                expression: Expression::Identifier("return".into()),
+                value: ident("p"),
+            }
+            .into()],
+        );
+        *callee.signature.parameters.get_mut() = vec![Parameter {
+            name: "p".into(),
+            typ: pair(),
+            is_ref: false,
+        }];
+        *callee.signature.return_type.get_mut() = pair().get().clone();
+
+        let ast = ast([
+            callee,
+            function(
+                "f",
+                vec![declare("t", pair()), call("t", "g", vec![ident("t")])],
+            ),
+        ]);
+
+        assert!(LowerTuples.run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "g", "p_0"), Some(Size::Static(8))));
+        assert!(matches!(size_of(&ast, "g", "p_1"), Some(Size::Static(1))));
+        assert_eq!(
+            statements(&ast, "g"),
+            ["return = struct g_tuple {_0: p_0,_1: p_1,};"]
+        );
+        assert_eq!(
+            statements(&ast, "f"),
+            [
+                "bv8 t_0;",
+                "bv1 t_1;",
+                "g_tuple t;",
+                "t = g(t_0, t_1);",
+                "t_0 = t._0;",
+                "t_1 = t._1;"
+            ]
+        );
+    }
+}
@@ -5,18 +5,24 @@ use {
         control_flow::{ControlFlowBlock, Terminator},
         passes::Pass,
         visitor::{Visitor, Walkable},
-        Ast, Expression, Literal, Statement, Type, Value,
+        Ast, Expression, Literal, Spans, Statement, Type, Value,
     },
     common::{intern::InternedString, shared::Shared},
 };
 
 /// Remove all exception handling logic
-pub struct MakeExceptionPanic;
+pub struct MakeExceptionPanic {
+    /// Spans of the AST, taken while running so panics can take the span of
+    /// the exception they replace
+    spans: Spans,
+}
 
 impl MakeExceptionPanic {
     /// Create a new Pass object
     pub fn new_boxed() -> Box<dyn Pass> {
-        Box::new(Self)
+        Box::new(Self {
+            spans: Spans::default(),
+        })
     }
 }
 
@@ -28,10 +34,14 @@ impl Pass for MakeExceptionPanic {
     fn reset(&mut self) {}
 
     fn run(&mut self, ast: Shared<Ast>) -> bool {
+        self.spans = std::mem::take(&mut ast.get_mut().spans);
+
         ast.get().functions.values().for_each(|def| {
             self.visit_function_definition(def);
         });
 
+        ast.get_mut().spans = std::mem::take(&mut self.spans);
+
         // TODO: write comment proving this only ever needs one pass
         false
     }
@@ -44,7 +54,13 @@ impl Visitor for MakeExceptionPanic {
         let statements = block
             .statements()
             .into_iter()
-            .filter_map(|statement| statement_filter(&mut deleted_exception_vars, statement))
+            .filter_map(|statement| {
+                let replacement = statement_filter(&mut deleted_exception_vars, statement.clone());
+                if let Some(replacement) = &replacement {
+                    self.spans.inherit(&statement, replacement);
+                }
+                replacement
+            })
             .collect();
 
         block.set_statements(statements);
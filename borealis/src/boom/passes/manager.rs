@@ -0,0 +1,573 @@
+//! Running a sequence of passes to a fixed point
+
+use {
+    crate::boom::{
+        call_graph::CallGraph,
+        passes::{
+            common_subexpression_elimination::CommonSubexpressionElimination,
+            copy_propagation::CopyPropagation,
+            dead_code_elimination::DeadCodeElimination,
+            fold_constants::FoldConstants,
+            function_cache::{cache_keys, context_salt, FunctionCache},
+            inline::Inline,
+            locate,
+            normalize_literals::NormalizeLiterals,
+            peephole::Peephole,
+            remove_self_assignments::RemoveSelfAssignments,
+            resolve_bitvectors::{ResolveBitvectors, SizeConflictPolicy},
+            verify_sizes::VerifySizes,
+            Diagnostic, Pass, Severity,
+        },
+        Ast,
+    },
+    common::{shared::Shared, HashMap, HashSet},
+    log::info,
+    std::{
+        cmp::Reverse,
+        time::{Duration, Instant},
+    },
+};
+
+/// Default limit on the number of rounds over all passes
+pub(crate) const DEFAULT_MAX_ROUNDS: usize = 100;
+
+/// Trades build time for quality of the generated code by selecting which
+/// passes run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptLevel {
+    /// Only the passes required for code generation, resolving and verifying
+    /// bitvector lengths
+    None,
+    /// Also folds constants, masking them to their length, and removes copies,
+    /// self-assignments and dead code
+    #[default]
+    Basic,
+    /// Also inlines small functions, eliminates common subexpressions and
+    /// simplifies shifts and masks
+    Aggressive,
+}
+
+/// Statistics about the runs of a single pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassStats {
+    pub name: &'static str,
+    /// Number of times the pass was run
+    pub invocations: usize,
+    /// Number of runs in which the pass changed the AST
+    pub changes: usize,
+    /// Total time spent running the pass
+    pub elapsed: Duration,
+}
+
+impl PassStats {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            invocations: 0,
+            changes: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Whether the pass ever changed the AST
+    pub fn did_change(&self) -> bool {
+        self.changes > 0
+    }
+}
+
+/// Runs every registered pass in order, repeating until a whole round makes no
+/// changes
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    max_rounds: usize,
+    /// Statistics for each pass, by index in `passes`
+    stats: Vec<PassStats>,
+    rounds: usize,
+    elapsed: Duration,
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self {
+            passes: vec![],
+            max_rounds: DEFAULT_MAX_ROUNDS,
+            stats: vec![],
+            rounds: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a pass manager with the preset pipeline for an optimization
+    /// level
+    ///
+    /// Bitvector lengths are resolved first and verified last at every level.
+    pub fn with_opt_level(level: OptLevel) -> Self {
+        let mut manager = Self::new();
+        manager.add(ResolveBitvectors::new_boxed(
+            SizeConflictPolicy::default(),
+            None,
+        ));
+
+        let optimizations: Vec<Box<dyn Pass>> = match level {
+            OptLevel::None => vec![],
+            OptLevel::Basic => vec![
+                FoldConstants::new_boxed(),
+                NormalizeLiterals::new_boxed(),
+                CopyPropagation::new_boxed(),
+                RemoveSelfAssignments::new_boxed(),
+                DeadCodeElimination::new_boxed(),
+            ],
+            OptLevel::Aggressive => vec![
+                Box::<Inline>::default(),
+                FoldConstants::new_boxed(),
+                NormalizeLiterals::new_boxed(),
+                Peephole::new_boxed(),
+                CopyPropagation::new_boxed(),
+                RemoveSelfAssignments::new_boxed(),
+                CommonSubexpressionElimination::new_boxed(),
+                DeadCodeElimination::new_boxed(),
+            ],
+        };
+        for pass in optimizations {
+            manager.add(pass);
+        }
+
+        manager.add(VerifySizes::new_boxed());
+        manager
+    }
+
+    /// Sets the maximum number of rounds before giving up on reaching a fixed
+    /// point
+    pub fn with_max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// Registers a pass, passes are run in the order they were added
+    pub fn add(&mut self, pass: Box<dyn Pass>) -> &mut Self {
+        self.stats.push(PassStats::new(pass.name()));
+        self.passes.push(pass);
+        self
+    }
+
+    /// Runs all passes until none change the AST
+    ///
+    /// Returns the errors and warnings emitted during the final round, and
+    /// informational diagnostics from every round.
+    pub fn run_to_fixpoint(&mut self, ast: Shared<Ast>) -> Vec<Diagnostic> {
+        let start = Instant::now();
+        let mut info = vec![];
+
+        let mut round = 0;
+        // passes that changed the AST in the previous round
+        let mut changing = vec![];
+
+        let diagnostics = loop {
+            if round == self.max_rounds {
+                info.push(round_limit_diagnostic(self.max_rounds, &changing));
+                break info;
+            }
+
+            round += 1;
+            self.rounds += 1;
+
+            let mut did_change = false;
+            let mut diagnostics = vec![];
+            changing.clear();
+
+            for (pass, stats) in self.passes.iter_mut().zip(&mut self.stats) {
+                info!("{}", pass.name());
+
+                let start = Instant::now();
+                pass.reset();
+                let pass_did_change = pass.run(ast.clone());
+                stats.elapsed += start.elapsed();
+
+                stats.invocations += 1;
+                if pass_did_change {
+                    stats.changes += 1;
+                    changing.push(pass.name());
+                }
+                did_change |= pass_did_change;
+
+                diagnostics.extend(locate(&ast, pass.take_diagnostics()));
+            }
+
+            if !did_change {
+                info.extend(diagnostics);
+                break info;
+            }
+
+            info.extend(
+                diagnostics
+                    .into_iter()
+                    .filter(|diagnostic| diagnostic.severity == Severity::Info),
+            );
+        };
+
+        self.elapsed += start.elapsed();
+
+        diagnostics
+    }
+
+    /// Runs all passes until none change the AST, reusing the results of
+    /// functions unchanged since they were cached
+    ///
+    /// Only functions missing from the cache are run, along with the cached
+    /// results of every function they call. Diagnostics are those of the
+    /// functions run, which are cached unless they reported an error.
+    pub fn run_to_fixpoint_cached(
+        &mut self,
+        ast: Shared<Ast>,
+        cache: &mut FunctionCache,
+    ) -> Vec<Diagnostic> {
+        let passes = self
+            .passes
+            .iter()
+            .map(|pass| (pass.name(), pass.configuration()))
+            .collect::<Vec<_>>();
+        let keys = {
+            let ast = ast.get();
+            cache_keys(&ast, context_salt(&ast, &passes, self.max_rounds))
+        };
+
+        let mut cached = keys
+            .iter()
+            .filter_map(|(name, key)| Some((*name, cache.load(*key)?)))
+            .collect::<HashMap<_, _>>();
+        let misses = keys
+            .keys()
+            .filter(|name| !cached.contains_key(name))
+            .copied()
+            .collect::<HashSet<_>>();
+        cache.set_processed(misses.iter().copied());
+
+        if misses.is_empty() {
+            ast.get_mut().functions.extend(cached);
+            return vec![];
+        }
+
+        // the functions run and everything they call
+        let working = {
+            let ast = ast.get();
+            let calls = CallGraph::build(&ast);
+
+            let mut functions = HashMap::default();
+            let mut worklist = misses.iter().copied().collect::<Vec<_>>();
+            while let Some(name) = worklist.pop() {
+                if functions.contains_key(&name) {
+                    continue;
+                }
+                let Some(def) = cached
+                    .remove(&name)
+                    .or_else(|| ast.functions.get(&name).cloned())
+                else {
+                    continue;
+                };
+                functions.insert(name, def);
+                worklist.extend(calls.callees(name));
+            }
+
+            let mut working = ast.clone();
+            working.functions = functions;
+            Shared::new(working)
+        };
+
+        let diagnostics = self.run_to_fixpoint(working.clone());
+
+        let working = working.get();
+        for name in &misses {
+            let failed = diagnostics.iter().any(|diagnostic| {
+                diagnostic.severity == Severity::Error && diagnostic.function == Some(*name)
+            });
+            if let (Some(def), false) = (working.functions.get(name), failed) {
+                cache.store(keys[name], def);
+            }
+        }
+
+        let mut ast = ast.get_mut();
+        ast.definitions = working.definitions.clone();
+        ast.registers = working.registers.clone();
+        ast.functions = working.functions.clone();
+        ast.functions.extend(cached);
+
+        diagnostics
+            .into_iter()
+            .filter(|diagnostic| {
+                diagnostic
+                    .function
+                    .is_none_or(|name| misses.contains(&name))
+            })
+            .collect()
+    }
+
+    /// Gets the statistics of each pass, in the order they were added
+    pub fn stats(&self) -> Vec<PassStats> {
+        self.stats.clone()
+    }
+
+    /// Gets the statistics of each pass, slowest first
+    pub fn stats_by_time(&self) -> Vec<PassStats> {
+        let mut stats = self.stats();
+        stats.sort_by_key(|stats| Reverse(stats.elapsed));
+        stats
+    }
+
+    /// Gets the number of rounds run so far
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// Gets the total time spent running passes
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Reports that the passes did not reach a fixed point, naming the passes that
+/// changed the AST in the last round
+pub(crate) fn round_limit_diagnostic(max_rounds: usize, changing: &[&'static str]) -> Diagnostic {
+    let mut message = format!("did not reach a fixed point after {max_rounds} rounds");
+    if !changing.is_empty() {
+        message.push_str(&format!(", still changed by {}", changing.join(", ")));
+    }
+
+    Diagnostic {
+        severity: Severity::Warning,
+        pass: "PassManager",
+        function: None,
+        statement: None,
+        span: None,
+        statement_id: None,
+        ident: None,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{
+                fold_constants::FoldConstants,
+                manager::{OptLevel, PassManager},
+                resolve_bitvectors::{ResolveBitvectors, SizeConflictPolicy},
+                run_fixed_point, Pass,
+            },
+            test_utils::*,
+            Ast, Operation, Position, Size, Span,
+        },
+        common::shared::Shared,
+        pretty_assertions::assert_eq,
+        std::{thread::sleep, time::Duration},
+    };
+
+    /// Pass that takes some time but never changes anything
+    struct Slow;
+
+    impl Pass for Slow {
+        fn name(&self) -> &'static str {
+            "Slow"
+        }
+
+        fn run(&mut self, _: Shared<Ast>) -> bool {
+            sleep(Duration::from_millis(1));
+            false
+        }
+
+        fn reset(&mut self) {}
+    }
+
+    #[test]
+    fn resolve_and_fold() {
+        init();
+
+        let fold = copy(
+            "z",
+            Operation::Or(Operation::LeftShift(int(0), int(8)).into(), ident("y")).into(),
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("y", bits(Size::Unknown)),
+                declare("z", bits(Size::Unknown)),
+                call("y", "Zeros", vec![int(4)]),
+                fold.clone(),
+            ],
+        )]);
+
+        let mut manager = PassManager::new();
+        manager
+            .add(ResolveBitvectors::new_boxed(
+                SizeConflictPolicy::default(),
+                None,
+            ))
+            .add(FoldConstants::new_boxed());
+
+        assert!(manager.run_to_fixpoint(ast.clone()).is_empty());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(4))));
+        assert_eq!(copied_value(&fold), "y");
+        assert!(matches!(size_of(&ast, "f", "z"), Some(Size::Static(4))));
+
+        // folding turns the operation into a copy in the first round, only then can
+        // the second round resolve `z`, and the third confirms nothing changed
+        assert_eq!(manager.rounds(), 3);
+        assert_eq!(
+            manager
+                .stats()
+                .iter()
+                .map(|stats| (stats.name, stats.invocations, stats.changes))
+                .collect::<Vec<_>>(),
+            [("ResolveBitvectors", 3, 2), ("FoldConstants", 3, 1)]
+        );
+
+        // already stable
+        manager.run_to_fixpoint(ast);
+        assert_eq!(manager.rounds(), 4);
+    }
+
+    #[test]
+    fn round_limit() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("y", bits(Size::Unknown)),
+                call("y", "Zeros", vec![int(4)]),
+            ],
+        )]);
+
+        let mut manager = PassManager::new().with_max_rounds(1);
+        manager.add(ResolveBitvectors::new_boxed(
+            SizeConflictPolicy::default(),
+            None,
+        ));
+
+        let diagnostics = manager.run_to_fixpoint(ast);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "did not reach a fixed point after 1 rounds, still changed by ResolveBitvectors"
+        );
+    }
+
+    #[test]
+    fn fixed_point_round_limit() {
+        init();
+
+        let model = || {
+            ast([function(
+                "f",
+                vec![
+                    declare("y", bits(Size::Unknown)),
+                    call("y", "Zeros", vec![int(4)]),
+                ],
+            )])
+        };
+        let passes = || {
+            vec![ResolveBitvectors::new_boxed(
+                SizeConflictPolicy::default(),
+                None,
+            )]
+        };
+
+        let diagnostics = run_fixed_point(model(), &mut passes(), Some(1));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "did not reach a fixed point after 1 rounds, still changed by ResolveBitvectors"
+        );
+
+        assert!(run_fixed_point(model(), &mut passes(), None).is_empty());
+    }
+
+    #[test]
+    fn error_span() {
+        init();
+
+        let statement = call("y", "Zeros", vec![int(4), int(8)]);
+        let ast = ast([function(
+            "f",
+            vec![declare("y", bits(Size::Unknown)), statement.clone()],
+        )]);
+
+        let span = Span {
+            file: "model.sail".into(),
+            start: Position {
+                line: 12,
+                column: 5,
+            },
+            end: Position {
+                line: 12,
+                column: 20,
+            },
+        };
+        ast.get_mut().spans.insert(&statement, span.clone());
+
+        let mut manager = PassManager::new();
+        manager.add(ResolveBitvectors::new_boxed(
+            SizeConflictPolicy::default(),
+            None,
+        ));
+
+        let diagnostics = manager.run_to_fixpoint(ast);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].span, Some(span));
+        assert!(diagnostics[0].to_string().contains("at model.sail:12:5"));
+    }
+
+    fn pipeline(level: OptLevel) -> Vec<&'static str> {
+        PassManager::with_opt_level(level)
+            .stats()
+            .iter()
+            .map(|stats| stats.name)
+            .collect()
+    }
+
+    #[test]
+    fn opt_levels() {
+        assert_eq!(
+            pipeline(OptLevel::None),
+            ["ResolveBitvectors", "VerifySizes"]
+        );
+
+        let aggressive = pipeline(OptLevel::Aggressive);
+        for pass in [
+            "Inline",
+            "FoldConstants",
+            "CommonSubexpressionElimination",
+            "DeadCodeElimination",
+        ] {
+            assert!(aggressive.contains(&pass), "{pass} not run");
+        }
+        assert_eq!(aggressive.first(), Some(&"ResolveBitvectors"));
+        assert_eq!(aggressive.last(), Some(&"VerifySizes"));
+    }
+
+    #[test]
+    fn stats() {
+        init();
+
+        let ast = ast([]);
+
+        let mut manager = PassManager::new();
+        manager.add(Box::new(Slow)).add(FoldConstants::new_boxed());
+
+        manager.run_to_fixpoint(ast.clone());
+        manager.run_to_fixpoint(ast);
+
+        let stats = manager.stats_by_time();
+        assert_eq!(stats[0].name, "Slow");
+        assert_eq!(stats[0].invocations, 2);
+        assert!(!stats[0].did_change());
+        assert!(stats[0].elapsed >= Duration::from_millis(2));
+    }
+}
@@ -6,22 +6,50 @@
 //! * Builtin function handling
 
 use {
-    crate::boom::Ast,
-    common::shared::Shared,
+    crate::boom::{Ast, Span, Statement},
+    common::{intern::InternedString, shared::Shared},
     log::info,
     std::{
+        fmt::{self, Display, Formatter},
         fs::{create_dir_all, File},
         path::PathBuf,
     },
 };
 
+pub mod alpha_rename;
 pub mod any;
+pub mod canonicalize;
+pub mod common_subexpression_elimination;
+pub mod copy_propagation;
 pub mod cycle_finder;
+pub mod dead_code_elimination;
+pub mod deduplicate_functions;
+pub mod dump_ir;
+pub mod fold_constants;
 pub mod fold_unconditionals;
+pub mod function_cache;
+pub mod inline;
+pub mod loop_invariant_motion;
+pub mod lower_bit_literals;
+pub mod lower_enums;
+pub mod lower_match;
+pub mod lower_tuples;
 pub mod make_exception_panic;
+pub mod manager;
+pub mod monomorphize_functions;
 pub mod monomorphize_vectors;
+pub mod normalize_literals;
+pub mod number_statements;
+pub mod peephole;
+pub mod range_analysis;
+pub mod reject_unsupported;
 pub mod remove_const_branch;
+pub mod remove_self_assignments;
+pub mod resolve_bitvectors;
 pub mod resolve_return_assigns;
+pub mod split_wide;
+pub mod verify_calls;
+pub mod verify_sizes;
 
 /// Pass that performs an operation on an AST
 pub trait Pass {
@@ -33,26 +61,157 @@ pub trait Pass {
 
     /// Resets any state in a pass to it's initial/empty state
     fn reset(&mut self);
+
+    /// Takes the diagnostics emitted by the pass since the last call
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        vec![]
+    }
+
+    /// Describes the options the pass was created with, two passes of the same
+    /// name and configuration must produce the same result for the same AST
+    fn configuration(&self) -> String {
+        String::new()
+    }
+}
+
+/// Severity of a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Informational, such as statistics about the changes a pass made
+    Info,
+}
+
+/// Problem found by a pass, reported instead of aborting compilation
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Name of the pass that emitted the diagnostic
+    pub pass: &'static str,
+    /// Function containing the problem
+    pub function: Option<InternedString>,
+    /// Statement containing the problem
+    pub statement: Option<Shared<Statement>>,
+    /// Location of the statement in the Sail source, filled in by the pass
+    /// runner
+    pub span: Option<Span>,
+    /// Identifier of the statement, filled in by the pass runner if
+    /// statements are numbered
+    pub statement_id: Option<usize>,
+    /// Local variable the diagnostic refers to
+    pub ident: Option<InternedString>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Sets the span and identifier to those of the statement, if it has them
+    pub fn locate(mut self, ast: &Ast) -> Self {
+        if self.span.is_none() {
+            self.span = self
+                .statement
+                .as_ref()
+                .and_then(|statement| ast.spans.get(statement));
+        }
+
+        if self.statement_id.is_none() {
+            self.statement_id = self
+                .statement
+                .as_ref()
+                .and_then(|statement| ast.statement_ids.get(statement));
+        }
+
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error")?,
+            Severity::Warning => write!(f, "warning")?,
+            Severity::Info => write!(f, "info")?,
+        }
+
+        write!(f, " [{}]", self.pass)?;
+
+        if let Some(function) = self.function {
+            write!(f, " in {function}")?;
+        }
+
+        if let Some(span) = &self.span {
+            write!(f, " at {span}")?;
+        }
+
+        write!(f, ": {}", self.message)?;
+
+        if let Some(statement) = &self.statement {
+            write!(f, "\n    ")?;
+            if let Some(id) = self.statement_id {
+                write!(f, "#{id} ")?;
+            }
+            write!(f, "{}", *statement.get())?;
+        }
+
+        Ok(())
+    }
 }
 
 /// Run each pass until it does not mutate the AST, and run the whole sequence
 /// of passes until no pass mutates the AST
-pub fn run_fixed_point(ast: Shared<Ast>, passes: &mut [Box<dyn Pass>]) {
-    // ironically, we *do* want to short-circuit here
-    // behaviour is "keep running the passes in order until none change"
-    loop {
-        if !passes
-            .iter_mut()
-            .map(|pass| {
-                info!("{}", pass.name());
-                pass.reset();
-                pass.run(ast.clone())
-            })
-            .any(|did_change| did_change)
-        {
-            break;
+///
+/// Returns the errors and warnings emitted during the final sequence of passes,
+/// earlier sequences may have reported problems that were later resolved.
+/// Informational diagnostics from every sequence are kept. If `max_rounds` is
+/// set, gives up with a warning after that many sequences, otherwise runs
+/// until a fixed point is reached.
+pub fn run_fixed_point(
+    ast: Shared<Ast>,
+    passes: &mut [Box<dyn Pass>],
+    max_rounds: Option<usize>,
+) -> Vec<Diagnostic> {
+    let mut info = vec![];
+    // pass that changed the AST in the last sequence
+    let mut changing = None;
+
+    let mut round = 0;
+    while max_rounds.is_none_or(|max_rounds| round < max_rounds) {
+        round += 1;
+        let mut diagnostics = vec![];
+
+        // ironically, we *do* want to short-circuit here
+        // behaviour is "keep running the passes in order until none change"
+        changing = passes.iter_mut().find_map(|pass| {
+            info!("{}", pass.name());
+            pass.reset();
+            let did_change = pass.run(ast.clone());
+            diagnostics.extend(locate(&ast, pass.take_diagnostics()));
+            did_change.then(|| pass.name())
+        });
+
+        if changing.is_none() {
+            info.extend(diagnostics);
+            return info;
         }
+
+        info.extend(
+            diagnostics
+                .into_iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Info),
+        );
     }
+
+    info.push(manager::round_limit_diagnostic(round, changing.as_slice()));
+    info
+}
+
+/// Fills in the spans of diagnostics taken from a pass
+fn locate(ast: &Shared<Ast>, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let ast = ast.get();
+    diagnostics
+        .into_iter()
+        .map(|diagnostic| diagnostic.locate(&ast))
+        .collect()
 }
 
 fn _dump_func_dot(ast: Shared<Ast>, func: &'static str, filename: Option<&'static str>) {
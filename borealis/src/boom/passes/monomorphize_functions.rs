@@ -0,0 +1,288 @@
+//! Monomorphize functions with bitvector parameters of unknown length
+//!
+//! If a function is called with bitvector arguments of known length, but its
+//! corresponding parameters are of unknown length, a copy of the function is
+//! made with those parameters fixed to the length of the arguments and the
+//! call is redirected to the copy. Calls with identical argument lengths share
+//! a single copy.
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        Ast, FunctionDefinition, FunctionSignature, Literal, Parameter, Size, Statement, Type,
+        Value,
+    },
+    common::{intern::InternedString, shared::Shared},
+    itertools::Itertools,
+};
+
+#[derive(Debug, Default)]
+pub struct MonomorphizeFunctions;
+
+impl MonomorphizeFunctions {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for MonomorphizeFunctions {
+    fn name(&self) -> &'static str {
+        "MonomorphizeFunctions"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        // collect first as specializations are inserted while iterating
+        let functions = ast.get().functions.values().cloned().collect::<Vec<_>>();
+
+        functions
+            .iter()
+            .map(|def| monomorphize_calls(&ast, def))
+            .any()
+    }
+}
+
+/// Redirects calls within a function to specialized copies of the callee,
+/// returning whether any call was changed
+fn monomorphize_calls(ast: &Shared<Ast>, def: &FunctionDefinition) -> bool {
+    def.entry_block
+        .iter()
+        .flat_map(|block| block.statements())
+        .map(|statement| {
+            let (callee, arguments) = match &*statement.get() {
+                Statement::FunctionCall {
+                    name, arguments, ..
+                } => (*name, arguments.clone()),
+                _ => return false,
+            };
+
+            let Some(callee) = ast.get().functions.get(&callee).cloned() else {
+                return false;
+            };
+
+            let Some(lengths) = specialized_lengths(def, &callee, &arguments) else {
+                return false;
+            };
+
+            let name = specialize(ast, &callee, &lengths);
+
+            let Statement::FunctionCall { name: target, .. } = &mut *statement.get_mut() else {
+                unreachable!();
+            };
+            *target = name;
+
+            true
+        })
+        .any()
+}
+
+/// Gets the lengths the parameters of `callee` should be fixed to for a call
+/// with the supplied arguments, or `None` if no parameter can be specialized
+fn specialized_lengths(
+    caller: &FunctionDefinition,
+    callee: &FunctionDefinition,
+    arguments: &[Shared<Value>],
+) -> Option<Vec<Option<usize>>> {
+    let parameters = callee.signature.parameters.get();
+
+    if parameters.len() != arguments.len() {
+        return None;
+    }
+
+    let lengths = parameters
+        .iter()
+        .zip(arguments)
+        .map(|(parameter, argument)| {
+            match &*parameter.typ.get() {
+                Type::Bits {
+                    size: Size::Unknown,
+                } => (),
+                _ => return None,
+            }
+
+            match &*argument.get() {
+                Value::Identifier(ident) => match caller.get_ident_type(*ident) {
+                    Some(Type::Bits {
+                        size: Size::Static(length),
+                    }) => Some(length),
+                    _ => None,
+                },
+                Value::Literal(literal) => match &*literal.get() {
+                    Literal::Bits(bits) => Some(bits.len()),
+                    _ => None,
+                },
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    lengths.iter().any(Option::is_some).then_some(lengths)
+}
+
+/// Gets the specialization of `callee` for the supplied parameter lengths,
+/// creating it if it does not already exist
+fn specialize(
+    ast: &Shared<Ast>,
+    callee: &FunctionDefinition,
+    lengths: &[Option<usize>],
+) -> InternedString {
+    let name = InternedString::from(format!(
+        "{}_mono_{}",
+        callee.signature.name,
+        lengths
+            .iter()
+            .map(|length| match length {
+                Some(length) => length.to_string(),
+                None => "x".to_owned(),
+            })
+            .join("_")
+    ));
+
+    if ast.get().functions.contains_key(&name) {
+        return name;
+    }
+
+    let parameters = callee
+        .signature
+        .parameters
+        .get()
+        .iter()
+        .zip(lengths)
+        .map(|(parameter, length)| Parameter {
+            name: parameter.name,
+            typ: Shared::new(match length {
+                Some(length) => Type::Bits {
+                    size: Size::Static(*length),
+                },
+                None => parameter.typ.get().clone(),
+            }),
+            is_ref: parameter.is_ref,
+        })
+        .collect();
+
+    let specialized = FunctionDefinition {
+        signature: FunctionSignature {
+            name,
+            parameters: Shared::new(parameters),
+            return_type: Shared::new(callee.signature.return_type.get().clone()),
+        },
+        entry_block: callee.entry_block.deep_clone(),
+    };
+
+    let mut ast = ast.get_mut();
+    ast.spans
+        .inherit_copy(&callee.entry_block, &specialized.entry_block);
+    ast.functions.insert(name, specialized);
+
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{monomorphize_functions::MonomorphizeFunctions, Pass},
+            test_utils::*,
+            Parameter, Size, Statement, Type, Value,
+        },
+        common::intern::InternedString,
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn specializations() {
+        init();
+
+        let callee = function(
+            "f",
+            vec![declare("y", bits(Size::Unknown)), copy("y", ident("bv"))],
+        );
+        *callee.signature.parameters.get_mut() = vec![Parameter {
+            name: "bv".into(),
+            typ: bits(Size::Unknown),
+            is_ref: false,
+        }];
+
+        let calls = [
+            call("r0", "f", vec![ident("a")]),
+            call("r1", "f", vec![ident("b")]),
+            call("r2", "f", vec![ident("a")]),
+        ];
+        let caller = function(
+            "g",
+            [
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(16))),
+            ]
+            .into_iter()
+            .chain(calls.iter().cloned())
+            .collect(),
+        );
+
+        let ast = ast([callee, caller]);
+
+        assert!(MonomorphizeFunctions.run(ast.clone()));
+        assert!(!MonomorphizeFunctions.run(ast.clone()));
+
+        let targets = calls
+            .iter()
+            .map(|statement| match &*statement.get() {
+                Statement::FunctionCall { name, .. } => name.to_string(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(targets, ["f_mono_8", "f_mono_16", "f_mono_8"]);
+
+        let mut functions = ast
+            .get()
+            .functions
+            .keys()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        functions.sort();
+        assert_eq!(functions, ["f", "f_mono_16", "f_mono_8", "g"]);
+
+        let parameter_size = |name: &str| {
+            ast.get().functions[&InternedString::from(name)]
+                .signature
+                .parameters
+                .get()[0]
+                .typ
+                .get()
+                .get_size()
+        };
+        assert!(matches!(parameter_size("f"), Some(Size::Unknown)));
+        assert!(matches!(parameter_size("f_mono_8"), Some(Size::Static(8))));
+        assert!(matches!(
+            parameter_size("f_mono_16"),
+            Some(Size::Static(16))
+        ));
+
+        // the specialized bodies do not share declarations with the original
+        let f = ast.get().functions[&InternedString::from("f")].clone();
+        let f_mono_8 = ast.get().functions[&InternedString::from("f_mono_8")].clone();
+        let declaration = f_mono_8.entry_block.statements()[0].clone();
+        let Statement::TypeDeclaration { typ, .. } = &*declaration.get() else {
+            panic!("expected declaration of y");
+        };
+        *typ.get_mut() = Type::Bits {
+            size: Size::Static(8),
+        };
+        assert!(matches!(
+            f.get_ident_type("y".into()),
+            Some(Type::Bits {
+                size: Size::Unknown
+            })
+        ));
+
+        // nor values, which are rewritten in place by copy propagation
+        let copy = f_mono_8.entry_block.statements()[1].clone();
+        let Statement::Copy { value, .. } = &*copy.get() else {
+            panic!("expected copy to y");
+        };
+        *value.get_mut() = Value::Identifier("z".into());
+        assert_eq!(copied_value(&f.entry_block.statements()[1]), "bv");
+    }
+}
@@ -0,0 +1,188 @@
+//! Keeps literals assigned to bitvectors within the length of the bitvector
+//!
+//! A literal copied to a bitvector of static length is masked to that length,
+//! so `x = 300` becomes `x = 44` if `x` has 8 bits, and negative literals
+//! become their two's complement. Leaving the high bits set would make later
+//! comparisons of `x` fail. A bitvector of unknown length assigned a bit
+//! literal takes the length of the literal.
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, Expression, FunctionDefinition, Literal, Size, Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap},
+    num_bigint::BigInt,
+};
+
+/// Masks literals to the length of the bitvector they are assigned to
+#[derive(Debug, Default)]
+pub struct NormalizeLiterals;
+
+impl NormalizeLiterals {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for NormalizeLiterals {
+    fn name(&self) -> &'static str {
+        "NormalizeLiterals"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get().functions.values().map(normalize_function).any()
+    }
+}
+
+fn normalize_function(def: &FunctionDefinition) -> bool {
+    let mut normalizer = Normalizer {
+        locals: def
+            .signature
+            .parameters
+            .get()
+            .iter()
+            .map(|parameter| (parameter.name, parameter.typ.clone()))
+            .collect(),
+        did_change: false,
+    };
+    normalizer.visit_function_definition(def);
+    normalizer.did_change
+}
+
+struct Normalizer {
+    locals: HashMap<InternedString, Shared<Type>>,
+    did_change: bool,
+}
+
+impl Visitor for Normalizer {
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        match &*node.get() {
+            Statement::TypeDeclaration { name, typ } => {
+                self.locals.insert(*name, typ.clone());
+            }
+            Statement::Copy {
+                expression: Expression::Identifier(destination),
+                value,
+            } => self.normalize_copy(*destination, value),
+            _ => (),
+        }
+
+        node.get().walk(self);
+    }
+}
+
+impl Normalizer {
+    fn normalize_copy(&mut self, destination: InternedString, value: &Shared<Value>) {
+        let Some(typ) = self.locals.get(&destination).cloned() else {
+            return;
+        };
+        // cloned so the type can be updated
+        let size = match &*typ.get() {
+            Type::Bits { size } => size.clone(),
+            _ => return,
+        };
+        // cloned so the value can be replaced, literals may be shared between
+        // values so are not modified
+        let literal = match &*value.get() {
+            Value::Literal(literal) => literal.clone(),
+            _ => return,
+        };
+
+        let literal = literal.get();
+        match (&size, &*literal) {
+            (Size::Static(length), Literal::Int(int)) => {
+                let mask: BigInt = (BigInt::from(1) << length) - 1;
+                let masked = int & mask;
+                if masked != *int {
+                    *value.get_mut() = Value::Literal(Shared::new(Literal::Int(masked)));
+                    self.did_change = true;
+                }
+            }
+            (Size::Unknown, Literal::Bits(bits)) => {
+                let length = bits.len();
+                *typ.get_mut() = Type::Bits {
+                    size: Size::Static(length),
+                };
+                self.did_change = true;
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{normalize_literals::NormalizeLiterals, Pass},
+            test_utils::*,
+            Literal, Size, Value,
+        },
+        common::shared::Shared,
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn literal_masked_to_length() {
+        init();
+
+        let (wide, negative, fits, unknown) = (
+            copy("x", int(300)),
+            copy("y", int(-1)),
+            copy("z", int(7)),
+            copy("w", bits_literal("101")),
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Static(4))),
+                declare("z", bits(Size::Static(4))),
+                declare("w", bits(Size::Unknown)),
+                wide.clone(),
+                negative.clone(),
+                fits.clone(),
+                unknown.clone(),
+            ],
+        )]);
+
+        assert!(NormalizeLiterals.run(ast.clone()));
+        assert!(!NormalizeLiterals.run(ast.clone()));
+
+        assert_eq!(copied_value(&wide), "44");
+        assert_eq!(copied_value(&negative), "15");
+        assert_eq!(copied_value(&fits), "7");
+        assert!(matches!(size_of(&ast, "f", "w"), Some(Size::Static(3))));
+    }
+
+    #[test]
+    fn shared_literal_replaced() {
+        init();
+
+        // both copies share the literal, as emitted masks do
+        let literal = Shared::new(Literal::Int(255.into()));
+        let (narrow, wide) = (
+            copy("x", Shared::new(Value::Literal(literal.clone()))),
+            copy("y", Shared::new(Value::Literal(literal))),
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(4))),
+                declare("y", bits(Size::Static(8))),
+                narrow.clone(),
+                wide.clone(),
+            ],
+        )]);
+
+        assert!(NormalizeLiterals.run(ast.clone()));
+
+        assert_eq!(copied_value(&narrow), "15");
+        assert_eq!(copied_value(&wide), "255");
+    }
+}
@@ -0,0 +1,133 @@
+//! Gives every statement a stable identifier
+//!
+//! Identifiers are kept in `Ast::statement_ids` and reported in diagnostics.
+//! Statements already numbered keep their identifier, so running the pass again
+//! after other passes only numbers the statements they created.
+
+use {
+    crate::boom::{
+        passes::Pass,
+        visitor::{Visitor, Walkable},
+        Ast, Statement,
+    },
+    common::shared::Shared,
+};
+
+/// Numbers statements, never changes the AST
+#[derive(Debug, Default)]
+pub struct NumberStatements;
+
+impl NumberStatements {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for NumberStatements {
+    fn name(&self) -> &'static str {
+        "NumberStatements"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        let mut statements = Statements(vec![]);
+
+        // number functions in a fixed order so identifiers are the same between runs
+        {
+            let ast = ast.get();
+            let mut functions = ast.functions.values().collect::<Vec<_>>();
+            functions.sort_by_key(|def| def.signature.name.to_string());
+            functions
+                .into_iter()
+                .for_each(|def| statements.visit_function_definition(def));
+        }
+
+        let mut ast = ast.get_mut();
+        for statement in &statements.0 {
+            ast.statement_ids.assign(statement);
+        }
+
+        false
+    }
+}
+
+/// Collects every statement, including those in nested bodies
+struct Statements(Vec<Shared<Statement>>);
+
+impl Visitor for Statements {
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        self.0.push(node.clone());
+        node.get().walk(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{
+                copy_propagation::CopyPropagation, number_statements::NumberStatements,
+                resolve_bitvectors::ResolveBitvectors, Pass,
+            },
+            test_utils::*,
+            Size,
+        },
+        common::HashSet,
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn unique_and_preserved() {
+        init();
+
+        let statements = vec![
+            declare("x", bits(Size::Static(8))),
+            declare("y", bits(Size::Static(8))),
+            declare("z", bits(Size::Unknown)),
+            copy("y", ident("x")),
+            // too many arguments, reported as an error
+            call("z", "Zeros", vec![ident("y"), int(8)]),
+        ];
+        let ast = ast([function("f", statements.clone())]);
+
+        assert!(!NumberStatements.run(ast.clone()));
+
+        let ids = statements
+            .iter()
+            .map(|statement| ast.get().statement_ids.get(statement).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), statements.len());
+
+        // `y` is replaced by `x` in place, and the copy is removed
+        assert!(CopyPropagation.run(ast.clone()));
+        NumberStatements.run(ast.clone());
+
+        let remaining = ast
+            .get()
+            .functions
+            .values()
+            .next()
+            .unwrap()
+            .entry_block
+            .statements();
+        assert_eq!(remaining.len(), 4);
+        for statement in &remaining {
+            let index = statements
+                .iter()
+                .position(|original| original.as_ptr() == statement.as_ptr())
+                .unwrap();
+            assert_eq!(ast.get().statement_ids.get(statement), Some(ids[index]));
+        }
+
+        // diagnostics refer to the statement by identifier
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+        let diagnostic = pass.take_diagnostics().remove(0).locate(&ast.get());
+        assert_eq!(diagnostic.statement_id, Some(ids[4]));
+        assert!(diagnostic
+            .to_string()
+            .contains(&format!("#{} z = ", ids[4])));
+    }
+}
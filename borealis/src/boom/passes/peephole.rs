@@ -0,0 +1,267 @@
+//! Removes redundant shifts and masks left by lowered builtins
+//!
+//! Handlers lower builtins independently, so composing them produces
+//! operations such as masking a bitvector already within the mask, or shifting
+//! a value twice. Three rules are applied, innermost values first:
+//!
+//! * `x & mask` becomes `x` if `mask` is `2^w - 1` and `x` is a bitvector of at
+//!   most `w` bits, according to the `Size` of its type
+//! * `(x >> a) >> b` becomes `x >> (a + b)`, and likewise for left shifts,
+//!   where `a` and `b` are literals summing to less than 64
+//! * shifts and rotates by zero are removed
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        visitor::Visitor,
+        Ast, FunctionDefinition, Literal, Operation, Size, Type, Value,
+    },
+    common::shared::Shared,
+    num_bigint::{BigInt, Sign},
+};
+
+/// Width of the backing value, shifting by at least this many bits is not
+/// well-defined
+const MAX_SHIFT: u32 = 64;
+
+/// Applies peephole rewrites to shifts and masks
+#[derive(Debug, Default)]
+pub struct Peephole;
+
+impl Peephole {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for Peephole {
+    fn name(&self) -> &'static str {
+        "Peephole"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get().functions.values().map(simplify_function).any()
+    }
+}
+
+fn simplify_function(def: &FunctionDefinition) -> bool {
+    let mut simplifier = Simplifier {
+        def,
+        did_change: false,
+    };
+    simplifier.visit_function_definition(def);
+    simplifier.did_change
+}
+
+struct Simplifier<'a> {
+    def: &'a FunctionDefinition,
+    did_change: bool,
+}
+
+impl Visitor for Simplifier<'_> {
+    fn visit_value_post(&mut self, node: Shared<Value>) {
+        while let Some(simplified) = self.simplify(&node) {
+            *node.get_mut() = simplified;
+            self.did_change = true;
+        }
+    }
+}
+
+impl Simplifier<'_> {
+    /// Applies the first matching rule to a value
+    fn simplify(&self, value: &Shared<Value>) -> Option<Value> {
+        let Value::Operation(operation) = &*value.get() else {
+            return None;
+        };
+
+        match operation {
+            Operation::And(lhs, rhs) => [(lhs, rhs), (rhs, lhs)]
+                .into_iter()
+                .find(|(operand, mask)| self.is_redundant_mask(operand, mask))
+                .map(|(operand, _)| operand.get().clone()),
+
+            Operation::RightShift(inner, amount)
+            | Operation::LeftShift(inner, amount)
+            | Operation::ArithmeticRightShift(inner, amount)
+            | Operation::RotateLeft(inner, amount)
+            | Operation::RotateRight(inner, amount)
+                if is_zero(amount) =>
+            {
+                Some(inner.get().clone())
+            }
+
+            Operation::RightShift(inner, outer) => match &*inner.get() {
+                Value::Operation(Operation::RightShift(value, amount)) => {
+                    combined_amount(amount, outer).map(|amount| {
+                        Value::Operation(Operation::RightShift(value.clone(), amount))
+                    })
+                }
+                _ => None,
+            },
+            Operation::LeftShift(inner, outer) => match &*inner.get() {
+                Value::Operation(Operation::LeftShift(value, amount)) => {
+                    combined_amount(amount, outer)
+                        .map(|amount| Value::Operation(Operation::LeftShift(value.clone(), amount)))
+                }
+                _ => None,
+            },
+
+            _ => None,
+        }
+    }
+
+    /// Determines whether `mask` is a contiguous low mask covering every bit
+    /// of the bitvector `operand`
+    fn is_redundant_mask(&self, operand: &Shared<Value>, mask: &Shared<Value>) -> bool {
+        let Some(width) = mask_width(mask) else {
+            return false;
+        };
+
+        let Value::Identifier(ident) = &*operand.get() else {
+            return false;
+        };
+
+        match self.def.get_ident_type(*ident) {
+            Some(Type::Bits {
+                size: Size::Static(length),
+            }) => length as u64 <= width,
+            _ => false,
+        }
+    }
+}
+
+/// Gets an integer literal
+fn literal_int(value: &Shared<Value>) -> Option<BigInt> {
+    match &*value.get() {
+        Value::Literal(literal) => match &*literal.get() {
+            Literal::Int(int) => Some(int.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_zero(value: &Shared<Value>) -> bool {
+    literal_int(value).is_some_and(|int| int == BigInt::from(0))
+}
+
+/// Gets `w` if the value is the literal `2^w - 1`
+fn mask_width(value: &Shared<Value>) -> Option<u64> {
+    let mask = literal_int(value)?;
+
+    // every bit below the highest set bit is also set
+    (mask.sign() != Sign::Minus && mask.magnitude().count_ones() == mask.bits())
+        .then(|| mask.bits())
+}
+
+/// Sums two literal shift amounts, if the total is a well-defined shift
+fn combined_amount(a: &Shared<Value>, b: &Shared<Value>) -> Option<Shared<Value>> {
+    let (a, b) = (literal_int(a)?, literal_int(b)?);
+    let zero = BigInt::from(0);
+    if a < zero || b < zero {
+        return None;
+    }
+
+    let total = a + b;
+    (total < BigInt::from(MAX_SHIFT)).then(|| Literal::Int(total).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{peephole::Peephole, Pass},
+            test_utils::*,
+            Operation, Size,
+        },
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn mask_elided_by_size() {
+        init();
+
+        let (narrow, wide, unknown) = (
+            copy("x", Operation::And(ident("a"), int(0xff)).into()),
+            copy("y", Operation::And(int(0xff), ident("b")).into()),
+            copy("z", Operation::And(ident("c"), int(0xff)).into()),
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(16))),
+                declare("c", bits(Size::Unknown)),
+                narrow.clone(),
+                wide.clone(),
+                unknown.clone(),
+            ],
+        )]);
+
+        assert!(Peephole.run(ast.clone()));
+        assert!(!Peephole.run(ast));
+
+        assert_eq!(copied_value(&narrow), "a");
+        assert_eq!(copied_value(&wide), "(255 & b)");
+        assert_eq!(copied_value(&unknown), "(c & 255)");
+    }
+
+    #[test]
+    fn nested_shifts_combined() {
+        init();
+
+        let (right, left, overflow) = (
+            copy(
+                "x",
+                Operation::RightShift(Operation::RightShift(ident("a"), int(3)).into(), int(4))
+                    .into(),
+            ),
+            copy(
+                "y",
+                Operation::LeftShift(Operation::LeftShift(ident("a"), int(1)).into(), ident("n"))
+                    .into(),
+            ),
+            copy(
+                "z",
+                Operation::RightShift(Operation::RightShift(ident("a"), int(32)).into(), int(32))
+                    .into(),
+            ),
+        );
+        let ast = ast([function(
+            "f",
+            vec![right.clone(), left.clone(), overflow.clone()],
+        )]);
+
+        assert!(Peephole.run(ast));
+
+        assert_eq!(copied_value(&right), "(a >> 7)");
+        assert_eq!(copied_value(&left), "((a << 1) << n)");
+        assert_eq!(copied_value(&overflow), "((a >> 32) >> 32)");
+    }
+
+    #[test]
+    fn shift_by_zero_removed() {
+        init();
+
+        let (shift, nested) = (
+            copy("x", Operation::LeftShift(ident("a"), int(0)).into()),
+            copy(
+                "y",
+                Operation::ArithmeticRightShift(
+                    Operation::RotateLeft(ident("a"), int(0)).into(),
+                    int(0),
+                )
+                .into(),
+            ),
+        );
+        let ast = ast([function("f", vec![shift.clone(), nested.clone()])]);
+
+        assert!(Peephole.run(ast));
+
+        assert_eq!(copied_value(&shift), "a");
+        assert_eq!(copied_value(&nested), "a");
+    }
+}
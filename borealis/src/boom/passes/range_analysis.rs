@@ -0,0 +1,285 @@
+//! Bounds the values of integer locals
+//!
+//! Shift amounts and indices are usually computed from literals and masks,
+//! such as `n = (i & 7)`. Where every assignment to an integer local is an
+//! operation on literals and other bounded locals, the union of the ranges of
+//! those values is recorded in the `Range` of its type, so handlers can rely on
+//! the bounds when lowering.
+//!
+//! Locals assigned by function calls, parameters, and locals depending on
+//! themselves (such as loop counters) are left unbounded.
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        visitor::{Visitor, Walkable},
+        Ast, Expression, FunctionDefinition, Literal, Operation, Range, Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap, HashSet},
+    num_bigint::BigInt,
+};
+
+/// Records the ranges of integer locals in their types
+#[derive(Debug, Default)]
+pub struct RangeAnalysis;
+
+impl RangeAnalysis {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for RangeAnalysis {
+    fn name(&self) -> &'static str {
+        "RangeAnalysis"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get().functions.values().map(analyse_function).any()
+    }
+}
+
+fn analyse_function(def: &FunctionDefinition) -> bool {
+    let mut assignments = Assignments::default();
+    assignments.visit_function_definition(def);
+
+    // a range is only recorded once every value assigned to the local is
+    // bounded, so each local is set at most once and this terminates
+    let mut ranges = HashMap::<InternedString, Range>::default();
+    loop {
+        let bounded = assignments
+            .values
+            .iter()
+            .filter(|(ident, _)| {
+                !ranges.contains_key(*ident) && !assignments.unbounded.contains(*ident)
+            })
+            .filter_map(|(ident, values)| {
+                values
+                    .iter()
+                    .map(|value| range_of(value, &ranges))
+                    .reduce(|a, b| Some(union(a?, b?)))
+                    .flatten()
+                    .map(|range| (*ident, range))
+            })
+            .collect::<Vec<_>>();
+
+        if bounded.is_empty() {
+            break;
+        }
+
+        ranges.extend(bounded);
+    }
+
+    let mut did_change = false;
+
+    for (ident, typ) in &assignments.integers {
+        if let Type::Integer { range, .. } = &mut *typ.get_mut() {
+            let new = ranges.get(ident).cloned();
+            if *range != new {
+                *range = new;
+                did_change = true;
+            }
+        }
+    }
+
+    did_change
+}
+
+/// Values copied into each local, along with the integer locals
+#[derive(Default)]
+struct Assignments {
+    /// Declared types of integer locals
+    integers: HashMap<InternedString, Shared<Type>>,
+    /// Values copied into each local
+    values: HashMap<InternedString, Vec<Shared<Value>>>,
+    /// Locals assigned by anything other than a copy
+    unbounded: HashSet<InternedString>,
+}
+
+impl Visitor for Assignments {
+    fn visit_function_definition(&mut self, node: &FunctionDefinition) {
+        self.unbounded.extend(
+            node.signature
+                .parameters
+                .get()
+                .iter()
+                .map(|parameter| parameter.name),
+        );
+
+        node.walk(self);
+    }
+
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        match &*node.get() {
+            Statement::TypeDeclaration { name, typ } => {
+                if let Type::Integer { .. } = &*typ.get() {
+                    self.integers.insert(*name, typ.clone());
+                }
+            }
+            Statement::Copy {
+                expression: Expression::Identifier(ident),
+                value,
+            } => self.values.entry(*ident).or_default().push(value.clone()),
+            Statement::Copy { expression, .. }
+            | Statement::FunctionCall {
+                expression: Some(expression),
+                ..
+            } => {
+                self.unbounded.insert(root(expression));
+            }
+            _ => (),
+        }
+
+        node.get().walk(self);
+    }
+}
+
+/// Gets the local written by an expression
+fn root(expression: &Expression) -> InternedString {
+    match expression {
+        Expression::Identifier(ident) => *ident,
+        Expression::Field { expression, .. }
+        | Expression::TupleMember { expression, .. }
+        | Expression::Address(expression) => root(expression),
+    }
+}
+
+/// Gets the range of a value, given the ranges of locals
+fn range_of(value: &Shared<Value>, ranges: &HashMap<InternedString, Range>) -> Option<Range> {
+    match &*value.get() {
+        Value::Literal(literal) => match &*literal.get() {
+            Literal::Int(int) => Some(Range {
+                min: int.clone(),
+                max: int.clone(),
+            }),
+            _ => None,
+        },
+        Value::Identifier(ident) => ranges.get(ident).cloned(),
+        Value::Operation(operation) => match operation {
+            // masking by a non-negative literal bounds the result by the mask,
+            // whatever the other operand
+            Operation::And(lhs, rhs) => [lhs, rhs]
+                .into_iter()
+                .filter_map(|value| range_of(value, ranges))
+                .find(|range| range.min == range.max && range.min >= BigInt::from(0))
+                .map(|mask| Range {
+                    min: 0.into(),
+                    max: mask.max,
+                }),
+            Operation::Add(lhs, rhs) => {
+                let (lhs, rhs) = (range_of(lhs, ranges)?, range_of(rhs, ranges)?);
+                Some(Range {
+                    min: lhs.min + rhs.min,
+                    max: lhs.max + rhs.max,
+                })
+            }
+            Operation::Subtract(lhs, rhs) => {
+                let (lhs, rhs) = (range_of(lhs, ranges)?, range_of(rhs, ranges)?);
+                Some(Range {
+                    min: lhs.min - rhs.max,
+                    max: lhs.max - rhs.min,
+                })
+            }
+            Operation::Multiply(lhs, rhs) => {
+                let (lhs, rhs) = (range_of(lhs, ranges)?, range_of(rhs, ranges)?);
+                let products = [
+                    &lhs.min * &rhs.min,
+                    &lhs.min * &rhs.max,
+                    &lhs.max * &rhs.min,
+                    &lhs.max * &rhs.max,
+                ];
+                Some(Range {
+                    min: products.iter().min().unwrap().clone(),
+                    max: products.iter().max().unwrap().clone(),
+                })
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Gets the smallest range containing both ranges
+fn union(a: Range, b: Range) -> Range {
+    Range {
+        min: a.min.min(b.min),
+        max: a.max.max(b.max),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{range_analysis::RangeAnalysis, Pass},
+            test_utils::*,
+            Operation, Range, Size, Type,
+        },
+        common::shared::Shared,
+        pretty_assertions::assert_eq,
+    };
+
+    fn integer() -> Shared<Type> {
+        Shared::new(Type::Integer {
+            size: Size::Static(64),
+            range: None,
+        })
+    }
+
+    fn range_of(typ: &Shared<Type>) -> Option<(i64, i64)> {
+        match &*typ.get() {
+            Type::Integer {
+                range: Some(Range { min, max }),
+                ..
+            } => Some((min.try_into().unwrap(), max.try_into().unwrap())),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn masked_and_offset() {
+        init();
+
+        let (n, m, i) = (integer(), integer(), integer());
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("i", i.clone()),
+                declare("n", n.clone()),
+                declare("m", m.clone()),
+                call("i", "g", vec![]),
+                copy("n", Operation::And(ident("i"), int(7)).into()),
+                copy("m", Operation::Add(ident("n"), int(8)).into()),
+                copy("m", int(2)),
+            ],
+        )]);
+
+        assert!(RangeAnalysis.run(ast.clone()));
+        assert!(!RangeAnalysis.run(ast));
+
+        assert_eq!(range_of(&i), None);
+        assert_eq!(range_of(&n), Some((0, 7)));
+        assert_eq!(range_of(&m), Some((2, 15)));
+    }
+
+    #[test]
+    fn loop_counter_unbounded() {
+        init();
+
+        let i = integer();
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("i", i.clone()),
+                copy("i", int(0)),
+                copy("i", Operation::Add(ident("i"), int(1)).into()),
+            ],
+        )]);
+
+        assert!(!RangeAnalysis.run(ast));
+        assert_eq!(range_of(&i), None);
+    }
+}
@@ -0,0 +1,180 @@
+//! Reports uses of types that cannot be lowered to integers
+//!
+//! Builtin handlers assume values are integers or bitvectors, so a model using
+//! real or floating-point numbers fails deep inside a handler. Run first, this
+//! pass reports each parameter, local and return type that is, or contains, a
+//! real or float, in the function and statement declaring it.
+
+use {
+    crate::boom::{
+        passes::{Diagnostic, Pass, Severity},
+        visitor::{Visitor, Walkable},
+        Ast, FunctionDefinition, FunctionSignature, Parameter, Statement, Type,
+    },
+    common::{intern::InternedString, shared::Shared},
+};
+
+/// Reports real and float types, never changes the AST
+#[derive(Debug, Default)]
+pub struct RejectUnsupported {
+    current_func: Option<InternedString>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl RejectUnsupported {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+
+    fn check(
+        &mut self,
+        ident: Option<InternedString>,
+        typ: &Shared<Type>,
+        statement: Option<Shared<Statement>>,
+    ) {
+        let Some(feature) = unsupported_feature(typ) else {
+            return;
+        };
+
+        let subject = match ident {
+            Some(ident) => ident.to_string(),
+            None => "return value".to_owned(),
+        };
+
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            pass: self.name(),
+            function: self.current_func,
+            statement,
+            span: None,
+            statement_id: None,
+            ident,
+            message: format!("{subject} uses unsupported type {feature}"),
+        });
+    }
+}
+
+impl Pass for RejectUnsupported {
+    fn name(&self) -> &'static str {
+        "RejectUnsupported"
+    }
+
+    fn reset(&mut self) {
+        self.current_func = None;
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        self.reset();
+
+        let mut functions = ast.get().functions.values().cloned().collect::<Vec<_>>();
+        functions.sort_by_key(|def| def.signature.name.to_string());
+
+        functions
+            .iter()
+            .for_each(|def| self.visit_function_definition(def));
+
+        false
+    }
+}
+
+impl Visitor for RejectUnsupported {
+    fn visit_function_definition(&mut self, node: &FunctionDefinition) {
+        self.current_func = Some(node.signature.name);
+        node.walk(self);
+    }
+
+    fn visit_function_signature(&mut self, node: &FunctionSignature) {
+        node.parameters
+            .get()
+            .iter()
+            .for_each(|Parameter { name, typ, .. }| self.check(Some(*name), typ, None));
+
+        self.check(None, &node.return_type, None);
+    }
+
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        if let Statement::TypeDeclaration { name, typ } = &*node.get() {
+            self.check(Some(*name), typ, Some(node.clone()));
+        }
+
+        node.get().walk(self);
+    }
+}
+
+/// Gets the name of the first unsupported type within a type
+fn unsupported_feature(typ: &Shared<Type>) -> Option<&'static str> {
+    let mut finder = Finder(None);
+    finder.visit_type(typ.clone());
+    finder.0
+}
+
+struct Finder(Option<&'static str>);
+
+impl Visitor for Finder {
+    fn visit_type(&mut self, node: Shared<Type>) {
+        if self.0.is_some() {
+            return;
+        }
+
+        match &*node.get() {
+            Type::Real => self.0 = Some("real"),
+            Type::Float => self.0 = Some("float"),
+            _ => node.walk(self),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{reject_unsupported::RejectUnsupported, Pass},
+            test_utils::*,
+            Size, Type,
+        },
+        common::shared::Shared,
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn float_local() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", Shared::new(Type::Float)),
+                declare(
+                    "z",
+                    Shared::new(Type::Vector {
+                        element_type: Shared::new(Type::Real),
+                    }),
+                ),
+            ],
+        )]);
+
+        let mut pass = RejectUnsupported::default();
+        assert!(!pass.run(ast));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| d.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "y uses unsupported type float",
+                "z uses unsupported type real"
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|d| d.function == Some("f".into()) && d.statement.is_some()));
+    }
+}
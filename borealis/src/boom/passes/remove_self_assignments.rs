@@ -0,0 +1,114 @@
+//! Removes copies of a local to itself
+//!
+//! Copy propagation and inlining can leave `x = x` behind, which has no effect
+//! but is still emitted.
+
+use {
+    crate::boom::{
+        passes::{any::AnyExt, Pass},
+        Ast, Expression, FunctionDefinition, Statement, Value,
+    },
+    common::shared::Shared,
+};
+
+/// Removes statements assigning a local to itself
+#[derive(Debug, Default)]
+pub struct RemoveSelfAssignments;
+
+impl RemoveSelfAssignments {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+}
+
+impl Pass for RemoveSelfAssignments {
+    fn name(&self) -> &'static str {
+        "RemoveSelfAssignments"
+    }
+
+    fn reset(&mut self) {}
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        ast.get()
+            .functions
+            .values()
+            .map(remove_self_assignments)
+            .any()
+    }
+}
+
+fn remove_self_assignments(def: &FunctionDefinition) -> bool {
+    let mut did_change = false;
+
+    for block in def.entry_block.iter() {
+        let statements = block.statements();
+        let len = statements.len();
+
+        let remaining = statements
+            .into_iter()
+            .filter(|statement| !is_self_assignment(statement))
+            .collect::<Vec<_>>();
+
+        if remaining.len() != len {
+            block.set_statements(remaining);
+            did_change = true;
+        }
+    }
+
+    did_change
+}
+
+fn is_self_assignment(statement: &Shared<Statement>) -> bool {
+    match &*statement.get() {
+        Statement::Copy {
+            expression: Expression::Identifier(dest),
+            value,
+        } => matches!(&*value.get(), Value::Identifier(src) if src == dest),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{remove_self_assignments::RemoveSelfAssignments, Pass},
+            pretty_print::print_statement,
+            test_utils::*,
+            Size,
+        },
+        common::intern::InternedString,
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn self_assignment_removed() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Static(8))),
+                copy("x", ident("x")),
+                copy("y", ident("x")),
+            ],
+        )]);
+
+        assert!(RemoveSelfAssignments.run(ast.clone()));
+        assert!(!RemoveSelfAssignments.run(ast.clone()));
+
+        let statements = ast.get().functions[&InternedString::from("f")]
+            .entry_block
+            .statements()
+            .into_iter()
+            .map(|statement| {
+                let mut buf = vec![];
+                print_statement(&mut buf, statement);
+                String::from_utf8(buf).unwrap().trim().to_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(statements[2..], ["y = x;"]);
+    }
+}
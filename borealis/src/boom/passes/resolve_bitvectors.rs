@@ -0,0 +1,5866 @@
+//! Resolves the lengths of bitvectors
+//!
+//! Bitvectors are lowered to a `uint64` backing value, with the length of each
+//! bitvector tracked in the `Size` of its type. Lengths flow from bit literals
+//! and builtin calls (`Zeros`, `Ones`, `bitvector_concat`, ...) into the locals
+//! they are assigned to, and calls to those builtins are rewritten into
+//! equivalent operations on the backing value.
+//!
+//! Bitvectors longer than 64 bits do not fit in the backing value. Where their
+//! length can be resolved it is still recorded, but the builtin call producing
+//! them is left intact for codegen to pick a wider storage type (perhaps a
+//! `uint128` with the length stored in the upper bits). Each such local is
+//! reported with a warning and flagged as wide.
+//!
+//! Some immediates have a length that is only known at runtime, these are
+//! recorded as `Size::Runtime` rather than treated as fatal.
+
+use {
+    crate::boom::{
+        bits_to_bigint,
+        call_graph::CallGraph,
+        control_flow::{graph::ControlFlowGraph, ControlFlowBlock, Terminator},
+        passes::{
+            any::AnyExt,
+            lower_match::{make_pattern_match, pattern_mask, wildcard_pattern},
+            Diagnostic, Pass, Severity,
+        },
+        structural::StructuralEq,
+        visitor::{Visitor, Walkable},
+        Ast, Bit, Expression, FunctionDefinition, FunctionSignature, Literal, Operation, Parameter,
+        Range, Size, Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap, HashSet},
+    num_bigint::{BigInt, Sign},
+    once_cell::sync::Lazy,
+    parking_lot::RwLock,
+    rayon::iter::{IntoParallelRefIterator, ParallelIterator},
+    std::{collections::VecDeque, ptr::fn_addr_eq},
+};
+
+/// Maximum length of a bitvector that fits in the `uint64` backing value
+pub(crate) const MAX_LENGTH: usize = 64;
+
+/// Default limit on the number of sweeps over a single function
+const DEFAULT_MAX_ITERATIONS: usize = 32;
+
+/// Lowers a call to a builtin, with the call statement, its destination and
+/// its arguments
+pub type HandlerFunction =
+    fn(&mut ResolveBitvectors, &Shared<Statement>, &Expression, &[Shared<Value>]);
+
+/// Resolves the operands of a call to a builtin without a result, with the
+/// call statement and its arguments
+///
+/// The call itself is kept, so once every local of a function is static there
+/// is nothing left for these to do.
+pub type VoidHandlerFunction = fn(&mut ResolveBitvectors, &Shared<Statement>, &[Shared<Value>]);
+
+/// Handlers of builtins without a result, keyed by function name
+static VOID_HANDLERS: Lazy<HashMap<InternedString, VoidHandlerFunction>> = Lazy::new(|| {
+    let mut handlers = HashMap::<InternedString, VoidHandlerFunction>::default();
+
+    handlers.insert("sail_assert".into(), assert_handler);
+
+    handlers
+});
+
+/// Names used for builtins by other Sail versions and models, with the name
+/// their handler is registered under
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("sail_zeros", "Zeros"),
+    ("zeros", "Zeros"),
+    ("sail_ones", "Ones"),
+    ("ones", "Ones"),
+    ("sail_zero_extend", "ZeroExtend"),
+    ("zero_extend", "ZeroExtend"),
+    ("sail_sign_extend", "SignExtend"),
+    ("sign_extend", "SignExtend"),
+    ("sail_truncate", "truncate"),
+];
+
+/// Builtin function handlers, keyed by function name
+static HANDLERS: Lazy<HashMap<InternedString, HandlerFunction>> = Lazy::new(|| {
+    let mut handlers = HashMap::<InternedString, HandlerFunction>::default();
+
+    handlers.insert("Zeros".into(), zeros_handler);
+    handlers.insert("Ones".into(), ones_handler);
+    handlers.insert("ZeroExtend".into(), zero_extend_handler);
+    handlers.insert("SignExtend".into(), sign_extend_handler);
+    handlers.insert("truncate".into(), truncate_handler);
+    handlers.insert("sail_unsigned".into(), unsigned_handler);
+    handlers.insert("sail_signed".into(), signed_handler);
+    handlers.insert("not_vec".into(), not_handler);
+    handlers.insert("and_vec".into(), and_handler);
+    handlers.insert("or_vec".into(), or_handler);
+    handlers.insert("xor_vec".into(), xor_handler);
+    handlers.insert("add_bits".into(), add_bits_handler);
+    handlers.insert("sub_bits".into(), sub_bits_handler);
+    handlers.insert("shiftl".into(), shiftl_handler);
+    handlers.insert("shiftr".into(), shiftr_handler);
+    handlers.insert("bitvector_concat".into(), concat_handler);
+    handlers.insert("vector_subrange".into(), subrange_handler);
+    handlers.insert("bitvector_access".into(), bit_access_handler);
+    handlers.insert("get_slice_int".into(), get_slice_int_handler);
+    handlers.insert("set_slice_int".into(), set_slice_int_handler);
+    handlers.insert("plain_vector_access".into(), vector_access_handler);
+    handlers.insert("plain_vector_update".into(), vector_update_handler);
+    handlers.insert("replicate_bits".into(), replicate_handler);
+    handlers.insert("eq_vec".into(), eq_handler);
+    handlers.insert("neq_vec".into(), neq_handler);
+    handlers.insert("lt_vec".into(), lt_handler);
+    handlers.insert("lteq_vec".into(), lteq_handler);
+    handlers.insert("gt_vec".into(), gt_handler);
+    handlers.insert("gteq_vec".into(), gteq_handler);
+    handlers.insert("slt_vec".into(), slt_handler);
+    handlers.insert("slteq_vec".into(), slteq_handler);
+    handlers.insert("sgt_vec".into(), sgt_handler);
+    handlers.insert("sgteq_vec".into(), sgteq_handler);
+    handlers.insert("undefined_bitvector".into(), undefined_handler);
+    handlers.insert("and_bool".into(), and_bool_handler);
+    handlers.insert("or_bool".into(), or_bool_handler);
+
+    handlers
+});
+
+/// How to resolve a local assigned from sources of different static lengths
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeConflictPolicy {
+    /// Keep the length the local was first resolved to, reporting a wider
+    /// source as a warning
+    #[default]
+    KeepExisting,
+    /// Use the longest length of any source
+    PreferLongest,
+    /// Use the shortest length of any source
+    PreferShortest,
+    /// Report the conflicting lengths as an error
+    Error,
+}
+
+/// Resolves the lengths of bitvectors
+///
+/// Each function is swept repeatedly until no more sizes are resolved, so
+/// lengths propagate through copies regardless of statement order.
+#[derive(Debug)]
+pub struct ResolveBitvectors {
+    max_iterations: usize,
+    policy: SizeConflictPolicy,
+    /// Whether functions are resolved on multiple threads
+    parallel: bool,
+    /// Whether changes are only reported rather than made
+    dry_run: bool,
+    /// Whether functions are resolved from a worklist of changed functions and
+    /// their callers
+    incremental: bool,
+    /// Whether calls to builtins without a handler are errors rather than
+    /// warnings
+    strict_builtins: bool,
+    did_change: bool,
+    /// Function being resolved, cloning a definition only clones the handles
+    /// to its signature and blocks, which remain shared with the AST
+    current_func: Option<FunctionDefinition>,
+    current_statement: Option<Shared<Statement>>,
+    locals: HashMap<InternedString, Shared<Type>>,
+    /// Types of registers, register files are accessed directly
+    registers: HashMap<InternedString, Shared<Type>>,
+    /// Return types of the functions defined in the AST, calls to any other
+    /// function are to builtins
+    defined: HashMap<InternedString, Shared<Type>>,
+    diagnostics: Vec<Diagnostic>,
+    /// Bitvectors longer than the backing value, by function and local
+    wide: HashSet<(InternedString, InternedString)>,
+    /// Builtin function handlers, the defaults and any registered
+    handlers: HashMap<InternedString, HandlerFunction>,
+    /// Handlers of builtins without a result, the defaults and any registered
+    void_handlers: HashMap<InternedString, VoidHandlerFunction>,
+    /// Diagnostics of functions left fully resolved by a previous run, by
+    /// function, these are not swept again
+    resolved: HashMap<InternedString, Vec<Diagnostic>>,
+    /// Functions changed by the previous run
+    changed: HashSet<InternedString>,
+    /// Number of sweeps over function bodies, across all runs
+    sweeps: usize,
+    /// Number of times each function was resolved, across all runs
+    visits: HashMap<InternedString, usize>,
+    /// Sizes of every sized local after the last sweep, by function
+    sizes: HashMap<InternedString, HashMap<InternedString, Size>>,
+}
+
+impl Default for ResolveBitvectors {
+    fn default() -> Self {
+        Self::with_max_iterations(DEFAULT_MAX_ITERATIONS)
+    }
+}
+
+impl ResolveBitvectors {
+    /// Create a new Pass object, with handlers for builtins in addition to the
+    /// defaults
+    pub fn new_boxed(
+        policy: SizeConflictPolicy,
+        handlers: Option<HashMap<InternedString, HandlerFunction>>,
+    ) -> Box<dyn Pass> {
+        let mut pass = Self::default().with_policy(policy);
+        handlers
+            .into_iter()
+            .flatten()
+            .for_each(|(name, handler)| pass.register_handler(name, handler));
+
+        Box::new(pass)
+    }
+
+    /// Adds a handler for calls to the builtin `name`, replacing any existing
+    /// handler
+    pub fn register_handler(&mut self, name: InternedString, handler: HandlerFunction) {
+        self.handlers.insert(name, handler);
+    }
+
+    /// Adds a handler for calls to the builtin `name` without a destination,
+    /// replacing any existing handler
+    pub fn register_void_handler(&mut self, name: InternedString, handler: VoidHandlerFunction) {
+        self.void_handlers.insert(name, handler);
+    }
+
+    /// Sets how conflicting lengths of copy sources are resolved
+    pub fn with_policy(mut self, policy: SizeConflictPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets whether each function is resolved on its own thread
+    ///
+    /// Functions are resolved independently, so the result is the same as
+    /// resolving them one after another.
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Sets whether the AST is left unchanged, with each statement that would
+    /// have been rewritten and each parameter that would have been resized
+    /// reported as an informational diagnostic instead
+    ///
+    /// Functions are resolved on a copy, so the report matches the changes a
+    /// normal run would make. Diagnostics refer to the original statements.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Sets whether each run resolves functions from a worklist until no
+    /// function changes, rather than resolving every function once
+    ///
+    /// The worklist starts with the functions not left fully resolved by a
+    /// previous run, and the callers of each function that changes are added
+    /// to it. Functions are resolved one after another, ignoring
+    /// `with_parallel`.
+    pub fn with_incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    /// Sets whether a call to a builtin without a handler, leaving the length
+    /// of its destination unknown, is reported as an error rather than a
+    /// warning
+    pub fn with_strict_builtins(mut self, strict_builtins: bool) -> Self {
+        self.strict_builtins = strict_builtins;
+        self
+    }
+
+    /// Creates a pass that sweeps each function at most `max_iterations` times
+    pub fn with_max_iterations(max_iterations: usize) -> Self {
+        Self {
+            max_iterations,
+            policy: SizeConflictPolicy::default(),
+            parallel: false,
+            dry_run: false,
+            incremental: false,
+            strict_builtins: false,
+            did_change: false,
+            current_func: None,
+            current_statement: None,
+            locals: HashMap::default(),
+            registers: HashMap::default(),
+            defined: HashMap::default(),
+            diagnostics: vec![],
+            wide: HashSet::default(),
+            handlers: HANDLERS.clone(),
+            void_handlers: VOID_HANDLERS.clone(),
+            resolved: HashMap::default(),
+            changed: HashSet::default(),
+            sweeps: 0,
+            visits: HashMap::default(),
+            sizes: HashMap::default(),
+        }
+    }
+
+    /// Determines whether a local was resolved to a length that requires wide
+    /// storage
+    pub fn is_wide(&self, function: InternedString, ident: InternedString) -> bool {
+        self.wide.contains(&(function, ident))
+    }
+
+    /// Gets the sizes of the parameters and locals of a function, as resolved
+    /// by the last run
+    ///
+    /// Locals without a size, such as bools and structs, are not included.
+    /// Empty if the function has not been resolved.
+    pub fn resolved_sizes(&self, function: InternedString) -> HashMap<InternedString, Size> {
+        self.sizes.get(&function).cloned().unwrap_or_default()
+    }
+
+    /// Gets the number of sweeps over function bodies made by every run so far
+    pub fn sweeps(&self) -> usize {
+        self.sweeps
+    }
+
+    /// Gets the number of times a function has been resolved by every run so
+    /// far, skipped functions are not counted
+    pub fn visits(&self, function: InternedString) -> usize {
+        self.visits.get(&function).copied().unwrap_or_default()
+    }
+
+    fn current_func(&self) -> &FunctionDefinition {
+        self.current_func
+            .as_ref()
+            .expect("statement visited outside of a function")
+    }
+
+    /// Reports an error in the current statement, resolution continues with
+    /// the next statement
+    pub fn error<S: Into<String>>(&mut self, ident: Option<InternedString>, message: S) {
+        self.diagnostic(Severity::Error, ident, message);
+    }
+
+    fn diagnostic<S: Into<String>>(
+        &mut self,
+        severity: Severity,
+        ident: Option<InternedString>,
+        message: S,
+    ) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            pass: self.name(),
+            function: self.current_func.as_ref().map(|func| func.signature.name),
+            statement: self.current_statement.clone(),
+            span: None,
+            statement_id: None,
+            ident,
+            message: message.into(),
+        });
+    }
+
+    /// Tracks a declared local if its type carries a size, or is a vector or
+    /// struct whose elements or fields may
+    ///
+    /// Locals of other types, such as bools and enums, have nothing to resolve.
+    fn add_type_declaration(&mut self, name: InternedString, typ: Shared<Type>) {
+        let tracked = match &*typ.get() {
+            Type::Vector { .. } | Type::FixedVector { .. } | Type::Struct { .. } => true,
+            typ => typ.get_size().is_some(),
+        };
+
+        if tracked {
+            self.locals.insert(name, typ);
+        }
+    }
+
+    /// Gets the size of a local, if it is declared with one
+    pub fn get_size(&self, ident: InternedString) -> Option<Size> {
+        self.locals.get(&ident).and_then(|typ| typ.get().get_size())
+    }
+
+    /// Gets the size of a value if it is a local, or a field of a local or
+    /// register
+    pub fn get_value_size(&self, value: &Shared<Value>) -> Option<Size> {
+        if let Value::Field { value, field_name } = &*value.get() {
+            let typ = self.get_value_type(value)?;
+            return field_type(&typ, *field_name)?.get().get_size();
+        }
+
+        value
+            .get()
+            .get_ident()
+            .and_then(|ident| self.get_size(ident))
+    }
+
+    /// Determines whether the backing value of a local may have bits set above
+    /// its length, as it is assigned the result of unmasked arithmetic
+    fn may_have_dirty_high_bits(&self, value: &Shared<Value>) -> bool {
+        let Some(ident) = value.get().get_ident() else {
+            return false;
+        };
+
+        self.current_func()
+            .entry_block
+            .iter()
+            .flat_map(|block| block.statements())
+            .any(|statement| match &*statement.get() {
+                Statement::Copy {
+                    expression: Expression::Identifier(destination),
+                    value,
+                } if *destination == ident => matches!(
+                    &*value.get(),
+                    Value::Operation(
+                        Operation::Add(..)
+                            | Operation::Subtract(..)
+                            | Operation::Multiply(..)
+                            | Operation::LeftShift(..)
+                            | Operation::Complement(_)
+                    )
+                ),
+                _ => false,
+            })
+    }
+
+    /// Gets the declared type of a local or register, or of a field of one
+    fn get_value_type(&self, value: &Shared<Value>) -> Option<Shared<Type>> {
+        match &*value.get() {
+            Value::Identifier(ident) => self
+                .locals
+                .get(ident)
+                .or_else(|| self.registers.get(ident))
+                .cloned(),
+            Value::Field { value, field_name } => {
+                field_type(&self.get_value_type(value)?, *field_name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Gets the declared type of the local, register or field written by an
+    /// expression
+    fn get_expression_type(&self, expression: &Expression) -> Option<Shared<Type>> {
+        match expression {
+            Expression::Identifier(ident) => self
+                .locals
+                .get(ident)
+                .or_else(|| self.registers.get(ident))
+                .cloned(),
+            Expression::Field { expression, field } => {
+                field_type(&self.get_expression_type(expression)?, *field)
+            }
+            Expression::Address(_) | Expression::TupleMember { .. } => None,
+        }
+    }
+
+    /// Sets the size of a local
+    ///
+    /// Does nothing if the local is declared with a type without a size,
+    /// reporting an informational diagnostic, and reports an error if it is
+    /// not declared.
+    pub fn set_size(&mut self, ident: InternedString, size: Size) {
+        let Some(typ) = self.locals.get(&ident).cloned() else {
+            let declared = self
+                .current_func
+                .as_ref()
+                .is_some_and(|def| def.get_ident_type(ident).is_some());
+
+            if declared {
+                self.diagnostic(
+                    Severity::Info,
+                    Some(ident),
+                    format!("{ident} does not have a size"),
+                );
+            } else {
+                self.error(Some(ident), format!("{ident} not declared"));
+            }
+            return;
+        };
+
+        let mut typ = typ.get_mut();
+        let Some(current) = typ.get_size_mut() else {
+            drop(typ);
+            self.diagnostic(
+                Severity::Info,
+                Some(ident),
+                format!("{ident} does not have a size"),
+            );
+            return;
+        };
+
+        // only report a change if the size is actually different, otherwise handlers
+        // that re-set the same size would never reach a fixed point
+        let is_same = match (&*current, &size) {
+            (Size::Static(a), Size::Static(b)) => a == b,
+            (Size::Runtime(a), Size::Runtime(b)) => Shared::ptr_eq(a, b),
+            (Size::Unknown, Size::Unknown) => true,
+            _ => false,
+        };
+
+        if !is_same {
+            *current = size;
+            self.did_change = true;
+        }
+    }
+
+    /// Replaces the declared type of a local
+    pub fn set_type(&mut self, ident: InternedString, typ: Type) {
+        let Some(current) = self.locals.get(&ident).cloned() else {
+            self.error(Some(ident), format!("{ident} not declared"));
+            return;
+        };
+
+        let mut current = current.get_mut();
+        if !current.structural_eq(&typ) {
+            *current = typ;
+            self.did_change = true;
+        }
+    }
+
+    /// Gets the type of the elements of a vector local or register
+    pub fn get_element_type(&mut self, vector: &Shared<Value>) -> Option<Shared<Type>> {
+        let Some(ident) = vector.get().get_ident() else {
+            self.error(None, "expected vector local or register");
+            return None;
+        };
+
+        let Some(typ) = self
+            .locals
+            .get(&ident)
+            .or_else(|| self.registers.get(&ident))
+            .cloned()
+        else {
+            self.error(Some(ident), format!("{ident} not declared"));
+            return None;
+        };
+
+        let element_type = match &*typ.get() {
+            Type::Vector { element_type } | Type::FixedVector { element_type, .. } => {
+                Some(element_type.clone())
+            }
+            _ => None,
+        };
+
+        if element_type.is_none() {
+            self.error(Some(ident), format!("{ident} is not a vector"));
+        }
+
+        element_type
+    }
+
+    /// Gets the range of an integer local, as found by `RangeAnalysis`
+    pub fn get_range(&self, value: &Shared<Value>) -> Option<Range> {
+        let ident = value.get().get_ident()?;
+        let typ = self.locals.get(&ident)?.get();
+
+        match &*typ {
+            Type::Integer { range, .. } => range.clone(),
+            _ => None,
+        }
+    }
+
+    /// Replaces the supplied statement with a copy of `value` into
+    /// `expression`
+    pub fn rewrite(
+        &mut self,
+        statement: &Shared<Statement>,
+        expression: &Expression,
+        value: Shared<Value>,
+    ) {
+        self.rewrite_to_copy(statement, expression, |_| Some(value));
+    }
+
+    /// Replaces the supplied statement with a copy into `expression` of the
+    /// value `lower` computes, leaving it in place if `lower` computes none
+    pub fn rewrite_to_copy<F>(
+        &mut self,
+        statement: &Shared<Statement>,
+        expression: &Expression,
+        lower: F,
+    ) where
+        F: FnOnce(&mut Self) -> Option<Shared<Value>>,
+    {
+        let rewritten = self.map_statement(statement, |pass| {
+            lower(pass).map(|value| Statement::Copy {
+                expression: expression.clone(),
+                value,
+            })
+        });
+
+        if rewritten {
+            self.did_change = true;
+        }
+    }
+
+    /// Gets the identifier of a call destination, which must be a local
+    pub fn destination_ident(&mut self, expression: &Expression) -> Option<InternedString> {
+        match expression {
+            Expression::Identifier(ident) => Some(*ident),
+            _ => {
+                self.error(
+                    None,
+                    format!("expected local destination, found {expression:?}"),
+                );
+                None
+            }
+        }
+    }
+
+    /// Checks the number of arguments supplied to a builtin
+    pub fn check_arguments(&mut self, arguments: &[Shared<Value>], expected: usize) -> bool {
+        if arguments.len() == expected {
+            true
+        } else {
+            self.error(
+                None,
+                format!("expected {expected} arguments, found {}", arguments.len()),
+            );
+            false
+        }
+    }
+
+    /// Converts an evaluated length to a `usize`, reporting negative lengths
+    pub fn length_to_usize(&mut self, length: &BigInt) -> Option<usize> {
+        if length.sign() == Sign::Minus {
+            self.error(None, format!("negative bitvector length {length}"));
+            return None;
+        }
+
+        let converted = usize::try_from(length).ok();
+
+        if converted.is_none() {
+            self.error(None, format!("invalid bitvector length {length}"));
+        }
+
+        converted
+    }
+
+    /// Adds lengths, reporting an error in the current statement if the total
+    /// overflows
+    pub fn checked_length_sum<I: IntoIterator<Item = usize>>(
+        &mut self,
+        ident: Option<InternedString>,
+        lengths: I,
+    ) -> Option<usize> {
+        let sum = lengths
+            .into_iter()
+            .try_fold(0usize, |sum, length| sum.checked_add(length));
+
+        if sum.is_none() {
+            self.error(ident, "bitvector length overflows");
+        }
+
+        sum
+    }
+
+    /// Multiplies lengths, reporting an error in the current statement if the
+    /// product overflows
+    pub fn checked_length_product(
+        &mut self,
+        ident: Option<InternedString>,
+        a: usize,
+        b: usize,
+    ) -> Option<usize> {
+        let product = a.checked_mul(b);
+
+        if product.is_none() {
+            self.error(ident, "bitvector length overflows");
+        }
+
+        product
+    }
+
+    /// Evaluates a bitvector length argument to a constant, either directly
+    /// from a literal, from the value assigned to a local, or from integer
+    /// arithmetic on those
+    pub fn evaluate_length(&self, value: &Shared<Value>, at: &Shared<Statement>) -> Option<BigInt> {
+        self.evaluate_length_visiting(value, at, &mut vec![])
+    }
+
+    /// Evaluates a length, falling back to the range of a local proven to
+    /// hold a single value
+    pub fn evaluate_bound(&self, value: &Shared<Value>, at: &Shared<Statement>) -> Option<BigInt> {
+        self.evaluate_length(value, at).or_else(|| {
+            let range = self.get_range(value)?;
+            (range.min == range.max).then_some(range.min)
+        })
+    }
+
+    /// Evaluates a length, `visiting` holding the assignments being evaluated
+    /// so a local assigned from itself (such as `n = (n - 1)`) is not
+    /// followed forever
+    fn evaluate_length_visiting(
+        &self,
+        value: &Shared<Value>,
+        at: &Shared<Statement>,
+        visiting: &mut Vec<Shared<Statement>>,
+    ) -> Option<BigInt> {
+        match &*value.get() {
+            Value::Literal(literal) => match &*literal.get() {
+                Literal::Int(length) => Some(length.clone()),
+                _ => None,
+            },
+            Value::Identifier(ident) => {
+                let (assignment, value) =
+                    self.current_func().get_assignment_any_block(*ident, at)?;
+
+                if visiting
+                    .iter()
+                    .any(|statement| Shared::ptr_eq(statement, &assignment))
+                {
+                    return None;
+                }
+
+                visiting.push(assignment.clone());
+                let length = self.evaluate_length_visiting(&value, &assignment, visiting);
+                visiting.pop();
+
+                length
+            }
+            Value::Operation(Operation::Add(lhs, rhs)) => Some(
+                self.evaluate_length_visiting(lhs, at, visiting)?
+                    + self.evaluate_length_visiting(rhs, at, visiting)?,
+            ),
+            Value::Operation(Operation::Subtract(lhs, rhs)) => Some(
+                self.evaluate_length_visiting(lhs, at, visiting)?
+                    - self.evaluate_length_visiting(rhs, at, visiting)?,
+            ),
+            Value::Operation(Operation::Multiply(lhs, rhs)) => Some(
+                self.evaluate_length_visiting(lhs, at, visiting)?
+                    * self.evaluate_length_visiting(rhs, at, visiting)?,
+            ),
+            _ => None,
+        }
+    }
+
+    /// Unifies the lengths of the operands of a comparison branched on
+    fn resolve_terminator(&mut self, block: &ControlFlowBlock) {
+        self.current_statement = None;
+
+        if let Terminator::Conditional { condition, .. } = block.terminator() {
+            if let Some((left, right)) = comparison_operands(&Shared::new(condition)) {
+                self.unify_sizes(&left, &right);
+            }
+        }
+    }
+
+    fn resolve_from_copy(
+        &mut self,
+        statement: &Shared<Statement>,
+        destination: InternedString,
+        value: Shared<Value>,
+    ) {
+        // bit literals are lowered to integers, their length becoming the destination
+        // size
+        let bits = match &*value.get() {
+            Value::Literal(literal) => match &*literal.get() {
+                Literal::Bits(bits) => Some(bits.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(bits) = bits {
+            if self.get_size(destination).is_some() {
+                self.set_size(destination, Size::Static(bits.len()));
+            }
+
+            self.rewrite(
+                statement,
+                &Expression::Identifier(destination),
+                Literal::Int(bits_to_bigint(&bits)).into(),
+            );
+            return;
+        }
+
+        // comparisons imply both operands have the same length
+        if let Some((left, right)) = comparison_operands(&value) {
+            self.unify_sizes(&left, &right);
+            return;
+        }
+
+        // fields are read with the size declared in their struct
+        let source = match &*value.get() {
+            Value::Identifier(_) | Value::Field { .. } => value.get().to_string(),
+            _ => return,
+        };
+
+        if let Some(size) = self.get_value_size(&value) {
+            self.assign_size(destination, size, &source);
+        }
+    }
+
+    /// Resolves the size of `destination` when it is assigned `source` of
+    /// `size`, applying the conflict policy if both lengths are static.
+    /// Returns false if a conflict was reported.
+    fn assign_size(&mut self, destination: InternedString, size: Size, source: &str) -> bool {
+        match (self.get_size(destination), size) {
+            (Some(Size::Static(existing)), Size::Static(length)) if existing != length => {
+                match self.policy {
+                    SizeConflictPolicy::KeepExisting => {
+                        let message = format!(
+                            "conflicting lengths, {destination} has {existing} bits but is assigned {source} with {length}, keeping {existing}"
+                        );
+                        self.diagnostic(Severity::Warning, Some(destination), message);
+                        return false;
+                    }
+                    SizeConflictPolicy::PreferLongest if length > existing => {
+                        self.set_size(destination, Size::Static(length))
+                    }
+                    SizeConflictPolicy::PreferShortest if length < existing => {
+                        self.set_size(destination, Size::Static(length))
+                    }
+                    SizeConflictPolicy::PreferLongest | SizeConflictPolicy::PreferShortest => (),
+                    SizeConflictPolicy::Error => {
+                        let message = format!(
+                            "conflicting lengths, {destination} has {existing} bits but is assigned {source} with {length}"
+                        );
+                        self.error(Some(destination), message);
+                        return false;
+                    }
+                }
+            }
+            (Some(Size::Static(_)), _) => (),
+
+            // runtime sizes are only replaced by static ones
+            (Some(Size::Runtime(_)), size @ Size::Static(_))
+            | (Some(Size::Unknown), size @ (Size::Static(_) | Size::Runtime(_))) => {
+                self.set_size(destination, size)
+            }
+
+            _ => (),
+        }
+
+        true
+    }
+
+    /// Resolves a copy into a field of a struct, which keeps the size declared
+    /// in the struct
+    ///
+    /// Bit literals are lowered to integers, and a local of unknown length
+    /// copied into the field takes the length of the field.
+    fn resolve_field_update(
+        &mut self,
+        statement: &Shared<Statement>,
+        expression: &Expression,
+        field: InternedString,
+        value: Shared<Value>,
+    ) {
+        let Some(Size::Static(length)) = self
+            .get_expression_type(expression)
+            .and_then(|typ| typ.get().get_size())
+        else {
+            return;
+        };
+
+        let bits = match &*value.get() {
+            Value::Literal(literal) => match &*literal.get() {
+                Literal::Bits(bits) => Some(bits.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        if let Some(bits) = bits {
+            self.rewrite(
+                statement,
+                expression,
+                Literal::Int(bits_to_bigint(&bits)).into(),
+            );
+            return;
+        }
+
+        let Value::Identifier(source) = &*value.get() else {
+            return;
+        };
+
+        match self.get_size(*source) {
+            Some(Size::Unknown) => self.set_size(*source, Size::Static(length)),
+            Some(Size::Static(existing)) if existing != length => {
+                let message = format!(
+                    "conflicting lengths, field {field} has {length} bits but is assigned {source} with {existing}"
+                );
+                self.error(Some(*source), message);
+            }
+            _ => (),
+        }
+    }
+
+    /// Unifies the sizes of two values that must have the same length
+    ///
+    /// If only one is static, the other takes its size; if both are static but
+    /// differ, a conflict is reported.
+    fn unify_sizes(&mut self, a: &Shared<Value>, b: &Shared<Value>) {
+        let (Some(a_ident), Some(b_ident)) = (a.get().get_ident(), b.get().get_ident()) else {
+            return;
+        };
+
+        match (self.get_size(a_ident), self.get_size(b_ident)) {
+            (Some(Size::Static(a_length)), Some(Size::Static(b_length)))
+                if a_length != b_length =>
+            {
+                let message = format!(
+                    "conflicting lengths, {a_ident} has {a_length} bits but {b_ident} has {b_length}"
+                );
+                self.error(Some(a_ident), message);
+            }
+            (Some(size @ Size::Static(_)), Some(Size::Unknown)) => self.set_size(b_ident, size),
+            (Some(Size::Unknown), Some(size @ Size::Static(_))) => self.set_size(a_ident, size),
+            _ => (),
+        }
+    }
+
+    /// Resolves the operands of an assertion's condition like any comparison
+    ///
+    /// The call itself is kept, it is lowered to a runtime check along with its
+    /// message.
+    fn resolve_assert(&mut self, statement: &Shared<Statement>, arguments: &[Shared<Value>]) {
+        if !self.check_arguments(arguments, 2) {
+            return;
+        }
+
+        let condition = &arguments[0];
+
+        // the condition is usually a temporary assigned the comparison beforehand
+        let comparison = match &*condition.get() {
+            Value::Identifier(ident) => self
+                .current_func()
+                .get_assignment_any_block(*ident, statement)
+                .and_then(|(_, value)| comparison_operands(&value)),
+            _ => comparison_operands(condition),
+        };
+
+        if let Some((left, right)) = comparison {
+            self.unify_sizes(&left, &right);
+        }
+
+        let is_string = match &*arguments[1].get() {
+            Value::Literal(literal) => matches!(&*literal.get(), Literal::String(_)),
+            _ => false,
+        };
+
+        if !is_string {
+            self.error(None, "assertion message is not a string literal");
+        }
+    }
+
+    /// Resolves the operands of builtin function calls without a destination
+    fn resolve_void_fn(
+        &mut self,
+        statement: &Shared<Statement>,
+        name: InternedString,
+        arguments: &[Shared<Value>],
+    ) {
+        if let Some(handler) = lookup_handler(&self.void_handlers, name) {
+            handler(self, statement, arguments);
+        }
+    }
+
+    /// Resolves bitvector lengths of builtin function calls, rewriting them
+    /// into operations on the backing value
+    ///
+    /// The destination of a call to another function takes the length of the
+    /// bitvector it returns if that is static, otherwise the call is left
+    /// alone and `MonomorphizeFunctions` specializes it for the bitvector
+    /// lengths of its arguments
+    fn resolve_fn(
+        &mut self,
+        statement: &Shared<Statement>,
+        expression: &Expression,
+        name: InternedString,
+        arguments: &[Shared<Value>],
+    ) {
+        if let Some(handler) = self.get_handler(name) {
+            handler(self, statement, expression, arguments);
+            return;
+        }
+
+        if let Some(return_type) = self.defined.get(&name).cloned() {
+            self.resolve_from_return_type(expression, &return_type);
+            return;
+        }
+
+        // the builtin determines the length of a bitvector, but nothing here knows
+        // how
+        let Expression::Identifier(destination) = expression else {
+            return;
+        };
+        let unknown = self.locals.get(destination).is_some_and(|typ| {
+            matches!(
+                &*typ.get(),
+                Type::Bits {
+                    size: Size::Unknown
+                }
+            )
+        });
+        if !unknown {
+            return;
+        }
+
+        let severity = if self.strict_builtins {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        self.diagnostic(
+            severity,
+            Some(*destination),
+            format!("no handler for builtin {name}, length of {destination} is unknown"),
+        );
+    }
+
+    /// Sets the length of an unresolved call destination to the static length
+    /// returned by the callee
+    fn resolve_from_return_type(&mut self, expression: &Expression, return_type: &Shared<Type>) {
+        let Expression::Identifier(destination) = expression else {
+            return;
+        };
+        let Some(Size::Static(length)) = return_type.get().get_size() else {
+            return;
+        };
+
+        if let Some(Size::Unknown) = self.get_size(*destination) {
+            self.set_size(*destination, Size::Static(length));
+        }
+    }
+
+    /// Gets the handler for calls to a builtin
+    fn get_handler(&self, name: InternedString) -> Option<HandlerFunction> {
+        lookup_handler(&self.handlers, name)
+    }
+
+    /// Determines whether a function is still fully resolved since it was last
+    /// swept: every local is static, no builtin calls remain to be lowered,
+    /// and none of its callees have changed since
+    fn is_cached(&self, def: &FunctionDefinition, changed: &HashSet<InternedString>) -> bool {
+        if !self.resolved.contains_key(&def.signature.name) {
+            return false;
+        }
+
+        let callees = def.entry_block.get_functions();
+        if callees.iter().any(|callee| {
+            self.get_handler(*callee).is_some()
+                || self.changed.contains(callee)
+                || changed.contains(callee)
+        }) {
+            return false;
+        }
+
+        let parameters_static = def
+            .signature
+            .parameters
+            .get()
+            .iter()
+            .all(|parameter| is_static(&parameter.typ));
+
+        parameters_static
+            && def
+                .entry_block
+                .iter()
+                .flat_map(|block| block.statements())
+                .all(|statement| match &*statement.get() {
+                    Statement::TypeDeclaration { typ, .. } => is_static(typ),
+                    _ => true,
+                })
+    }
+
+    /// Records whether the function just swept is fully resolved, along with
+    /// the diagnostics it reported from `diagnostics_start`
+    fn cache(&mut self, def: &FunctionDefinition, diagnostics_start: usize) {
+        let name = def.signature.name;
+
+        if self.locals.values().all(is_static) {
+            let diagnostics = self.diagnostics[diagnostics_start..].to_vec();
+            self.resolved.insert(name, diagnostics);
+        } else {
+            self.resolved.remove(&name);
+        }
+    }
+
+    /// Resolves a copy of a function, reporting the differences from the
+    /// original
+    fn dry_run_function(&mut self, def: &FunctionDefinition) {
+        let parameters = def
+            .signature
+            .parameters
+            .get()
+            .iter()
+            .map(|parameter| Parameter {
+                typ: Shared::new(parameter.typ.get().clone()),
+                ..parameter.clone()
+            })
+            .collect::<Vec<_>>();
+        let copy = FunctionDefinition {
+            signature: FunctionSignature {
+                parameters: Shared::new(parameters),
+                ..def.signature.clone()
+            },
+            entry_block: def.entry_block.deep_clone(),
+        };
+
+        // blocks are copied in the order they are iterated
+        let statements = |def: &FunctionDefinition| {
+            def.entry_block
+                .iter()
+                .flat_map(|block| block.statements())
+                .collect::<Vec<_>>()
+        };
+        let originals = statements(def)
+            .into_iter()
+            .zip(statements(&copy))
+            .map(|(original, copy)| (copy.as_ptr(), original))
+            .collect::<HashMap<_, _>>();
+
+        let diagnostics_start = self.diagnostics.len();
+        self.run_function(&copy);
+
+        let sizes = self.local_sizes();
+        self.sizes.insert(def.signature.name, sizes);
+
+        for diagnostic in &mut self.diagnostics[diagnostics_start..] {
+            if let Some(statement) = &diagnostic.statement {
+                diagnostic.statement = originals.get(&statement.as_ptr()).cloned();
+            }
+        }
+
+        self.current_func = Some(def.clone());
+        self.current_statement = None;
+
+        let parameters = def.signature.parameters.get().clone();
+        for (original, resolved) in parameters
+            .iter()
+            .zip(copy.signature.parameters.get().iter())
+        {
+            if !original.typ.get().structural_eq(&resolved.typ.get()) {
+                let declaration = Statement::TypeDeclaration {
+                    name: resolved.name,
+                    typ: resolved.typ.clone(),
+                };
+                self.diagnostic(
+                    Severity::Info,
+                    Some(original.name),
+                    format!("would change parameter to `{declaration}`"),
+                );
+            }
+        }
+
+        for (original, resolved) in statements(def).into_iter().zip(statements(&copy)) {
+            let (before, after) = (original.get().to_string(), resolved.get().to_string());
+            if before != after {
+                self.current_statement = Some(original);
+                self.diagnostic(Severity::Info, None, format!("would rewrite to `{after}`"));
+            }
+        }
+    }
+
+    /// Sweeps a function unless it is cached, in which case its diagnostics
+    /// are reported again
+    fn run_function_cached(
+        &mut self,
+        def: &FunctionDefinition,
+        changed: &mut HashSet<InternedString>,
+    ) -> bool {
+        if self.is_cached(def, changed) {
+            let diagnostics = self.resolved[&def.signature.name].clone();
+            self.diagnostics.extend(diagnostics);
+            return false;
+        }
+
+        let did_change = self.resolve_function(def);
+
+        if did_change {
+            changed.insert(def.signature.name);
+        }
+
+        did_change
+    }
+
+    /// Sweeps a function, recording whether it was left fully resolved and the
+    /// sizes of its locals
+    fn resolve_function(&mut self, def: &FunctionDefinition) -> bool {
+        *self.visits.entry(def.signature.name).or_default() += 1;
+
+        let diagnostics_start = self.diagnostics.len();
+        let did_change = self.run_function(def);
+        self.cache(def, diagnostics_start);
+
+        let sizes = self.local_sizes();
+        self.sizes.insert(def.signature.name, sizes);
+
+        did_change
+    }
+
+    /// Resolves functions from a worklist until none change, revisiting the
+    /// callers of each function that does
+    fn run_incremental(&mut self, ast: &Ast) -> bool {
+        let calls = CallGraph::build(ast);
+
+        let mut worklist = ast
+            .functions
+            .values()
+            .filter(|def| !self.is_cached(def, &HashSet::default()))
+            .map(|def| def.signature.name)
+            .collect::<VecDeque<_>>();
+        let mut queued = worklist.iter().copied().collect::<HashSet<_>>();
+
+        // diagnostics of the last time each function was resolved in this run
+        let mut reported = HashMap::<InternedString, Vec<Diagnostic>>::default();
+        let mut did_change = false;
+
+        while let Some(name) = worklist.pop_front() {
+            queued.remove(&name);
+
+            let diagnostics_start = self.diagnostics.len();
+            let changed = self.resolve_function(&ast.functions[&name]);
+            reported.insert(name, self.diagnostics.split_off(diagnostics_start));
+
+            if !changed {
+                continue;
+            }
+
+            did_change = true;
+            for caller in calls.callers(name) {
+                if queued.insert(caller) {
+                    worklist.push_back(caller);
+                }
+            }
+        }
+
+        for name in ast.functions.keys() {
+            let diagnostics = reported
+                .remove(name)
+                .or_else(|| self.resolved.get(name).cloned());
+            self.diagnostics.extend(diagnostics.into_iter().flatten());
+        }
+
+        // callers of changed functions were already revisited
+        self.changed.clear();
+
+        did_change
+    }
+
+    /// Flags locals of the function longer than the backing value, these need
+    /// wide storage rather than being truncated
+    fn report_wide(&mut self, def: &FunctionDefinition) {
+        self.current_statement = None;
+
+        let mut wide = self
+            .locals
+            .iter()
+            .filter_map(|(ident, typ)| match typ.get().get_size() {
+                Some(Size::Static(length)) if length > MAX_LENGTH => Some((*ident, length)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        wide.sort_by_key(|(ident, _)| ident.to_string());
+
+        for (ident, length) in wide {
+            self.wide.insert((def.signature.name, ident));
+            self.diagnostic(
+                Severity::Warning,
+                Some(ident),
+                format!(
+                    "{ident} has {length} bits, exceeds maximum length of {MAX_LENGTH} and requires wide storage"
+                ),
+            );
+        }
+    }
+
+    /// Sweeps a function until no more sizes are resolved, returning whether
+    /// any sweep made a change
+    fn run_function(&mut self, def: &FunctionDefinition) -> bool {
+        let diagnostics_start = self.diagnostics.len();
+        let mut changed = false;
+        // sizes after the previous sweep, and the locals whose sizes it changed
+        let mut sizes = HashMap::default();
+        let mut changing = vec![];
+
+        for _ in 0..self.max_iterations {
+            // only keep the diagnostics of the final sweep, earlier ones may
+            // have been resolved since
+            self.diagnostics.truncate(diagnostics_start);
+
+            self.reset();
+            self.sweeps += 1;
+            self.visit_function_definition(def);
+
+            if !self.did_change {
+                self.report_wide(def);
+                return changed;
+            }
+
+            changed = true;
+
+            let previous = std::mem::replace(&mut sizes, self.local_sizes());
+            changing = sizes
+                .iter()
+                .filter(|(ident, size)| {
+                    !previous
+                        .get(*ident)
+                        .is_some_and(|previous: &Size| previous.structural_eq(size))
+                })
+                .map(|(ident, _)| *ident)
+                .collect::<Vec<_>>();
+            changing.sort_by_key(ToString::to_string);
+        }
+
+        self.current_statement = None;
+
+        let mut message = format!(
+            "did not reach a fixed point after {} iterations",
+            self.max_iterations
+        );
+        if !changing.is_empty() {
+            let names = changing
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            message.push_str(&format!(", sizes of {names} still changing"));
+        }
+        self.error(changing.first().copied(), message);
+
+        changed
+    }
+
+    /// Gets the sizes of the sized locals of the current function
+    fn local_sizes(&self) -> HashMap<InternedString, Size> {
+        self.locals
+            .iter()
+            .filter_map(|(ident, typ)| typ.get().get_size().map(|size| (*ident, size)))
+            .collect()
+    }
+}
+
+impl Pass for ResolveBitvectors {
+    fn name(&self) -> &'static str {
+        "ResolveBitvectors"
+    }
+
+    fn reset(&mut self) {
+        self.did_change = false;
+        self.current_func = None;
+        self.current_statement = None;
+        self.locals.clear();
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Registered handlers are identified by the builtin they handle, only
+    /// those replacing or adding to the defaults are included
+    fn configuration(&self) -> String {
+        let mut handlers = self
+            .handlers
+            .iter()
+            .filter(|(name, handler)| {
+                !HANDLERS
+                    .get(*name)
+                    .is_some_and(|default| fn_addr_eq(*default, **handler))
+            })
+            .map(|(name, _)| name.to_string())
+            .chain(
+                self.void_handlers
+                    .iter()
+                    .filter(|(name, handler)| {
+                        !VOID_HANDLERS
+                            .get(*name)
+                            .is_some_and(|default| fn_addr_eq(*default, **handler))
+                    })
+                    .map(|(name, _)| format!("void {name}")),
+            )
+            .collect::<Vec<_>>();
+        handlers.sort();
+
+        format!(
+            "max_iterations={} policy={:?} dry_run={} strict_builtins={} handlers={handlers:?}",
+            self.max_iterations, self.policy, self.dry_run, self.strict_builtins
+        )
+    }
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        self.registers = ast
+            .get()
+            .registers
+            .iter()
+            .map(|(name, (typ, _))| (*name, typ.clone()))
+            .collect();
+        self.defined = ast
+            .get()
+            .functions
+            .iter()
+            .map(|(name, def)| (*name, def.signature.return_type.clone()))
+            .collect();
+
+        if self.dry_run {
+            ast.get()
+                .functions
+                .values()
+                .for_each(|def| self.dry_run_function(def));
+            return false;
+        }
+
+        if self.incremental {
+            return self.run_incremental(&ast.get());
+        }
+
+        let mut changed = HashSet::default();
+
+        if !self.parallel {
+            let ast = ast.get();
+
+            // callees first, so a change is seen by its callers in the same run
+            let did_change = CallGraph::build(&ast)
+                .strongly_connected_components()
+                .iter()
+                .flatten()
+                .map(|name| self.run_function_cached(&ast.functions[name], &mut changed))
+                .any();
+            self.changed = changed;
+            return did_change;
+        }
+
+        let functions = ast.get().functions.values().cloned().collect::<Vec<_>>();
+
+        // each thread resolves one function with its own state, the results are
+        // merged in order afterwards
+        let this = &*self;
+        let results = functions
+            .par_iter()
+            .map(|def| {
+                let mut pass = Self::with_max_iterations(this.max_iterations)
+                    .with_policy(this.policy)
+                    .with_strict_builtins(this.strict_builtins);
+                pass.handlers = this.handlers.clone();
+                pass.void_handlers = this.void_handlers.clone();
+                pass.registers = this.registers.clone();
+                pass.defined = this.defined.clone();
+
+                // only changes from the previous run are known between threads
+                if let Some(diagnostics) = this.resolved.get(&def.signature.name) {
+                    pass.resolved
+                        .insert(def.signature.name, diagnostics.clone());
+                }
+                pass.changed = this.changed.clone();
+
+                let mut changed = HashSet::default();
+                let did_change = pass.run_function_cached(def, &mut changed);
+                (def.signature.name, did_change, pass)
+            })
+            .collect::<Vec<_>>();
+
+        let did_change = results
+            .into_iter()
+            .map(|(name, did_change, mut pass)| {
+                self.diagnostics.extend(pass.diagnostics);
+                self.wide.extend(pass.wide);
+                self.sizes.extend(pass.sizes);
+                self.sweeps += pass.sweeps;
+                for (name, visits) in pass.visits {
+                    *self.visits.entry(name).or_default() += visits;
+                }
+
+                match pass.resolved.remove(&name) {
+                    Some(diagnostics) => self.resolved.insert(name, diagnostics),
+                    None => self.resolved.remove(&name),
+                };
+
+                if did_change {
+                    changed.insert(name);
+                }
+
+                did_change
+            })
+            .any();
+
+        self.changed = changed;
+        did_change
+    }
+}
+
+impl Visitor for ResolveBitvectors {
+    fn visit_function_definition(&mut self, node: &FunctionDefinition) {
+        self.current_func = Some(node.clone());
+
+        node.signature
+            .parameters
+            .get()
+            .iter()
+            .for_each(|parameter| self.add_type_declaration(parameter.name, parameter.typ.clone()));
+
+        self.visit_function_signature(&node.signature);
+
+        // predecessors first, so lengths resolved before a branch are seen by
+        // its successors in the same sweep
+        for block in ControlFlowGraph::build(node).blocks_in_reverse_postorder() {
+            self.visit_control_flow_block(&block);
+            self.resolve_terminator(&block);
+        }
+    }
+
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        // clone to avoid holding a lock on the statement while rewriting it
+        let statement = node.get().clone();
+        self.current_statement = Some(node.clone());
+
+        match statement {
+            Statement::TypeDeclaration { name, typ } => self.add_type_declaration(name, typ),
+            Statement::Copy {
+                expression: Expression::Identifier(destination),
+                value,
+            } => self.resolve_from_copy(&node, destination, value),
+            Statement::Copy {
+                expression: expression @ Expression::Field { field, .. },
+                value,
+            } => self.resolve_field_update(&node, &expression, field, value),
+            Statement::FunctionCall {
+                expression: Some(expression),
+                name,
+                arguments,
+            } => self.resolve_fn(&node, &expression, name, &arguments),
+            Statement::FunctionCall {
+                expression: None,
+                name,
+                arguments,
+            } => self.resolve_void_fn(&node, name, &arguments),
+            _ => (),
+        }
+
+        node.get().walk(self);
+    }
+}
+
+/// Gets the handler for calls to a builtin
+fn lookup_handler<H: Copy>(
+    handlers: &HashMap<InternedString, H>,
+    name: InternedString,
+) -> Option<H> {
+    // generic builtins are named for their type arguments, such as
+    // `plain_vector_access<RBits>`
+    let generic = name
+        .as_ref()
+        .split_once('<')
+        .map(|(name, _)| InternedString::from(name));
+
+    // otherwise by the name used for the builtin elsewhere
+    let canonical = BUILTIN_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name.as_ref())
+        .map(|(_, canonical)| InternedString::from(*canonical));
+
+    handlers
+        .get(&name)
+        .or_else(|| generic.and_then(|name| handlers.get(&name)))
+        .or_else(|| canonical.and_then(|name| handlers.get(&name)))
+        .copied()
+}
+
+/// Whether calls to a builtin are lowered or resolved by one of the default
+/// handlers
+pub fn has_default_handler(name: InternedString) -> bool {
+    lookup_handler(&HANDLERS, name).is_some() || lookup_handler(&VOID_HANDLERS, name).is_some()
+}
+
+/// Masks of every length that fits in the backing value, by length
+///
+/// Masks are built for most lowered builtins, so are computed once rather than
+/// by shifting and subtracting each time.
+static MASKS: Lazy<Vec<BigInt>> = Lazy::new(|| {
+    (0..=MAX_LENGTH)
+        .map(|width| (BigInt::from(1) << width) - 1)
+        .collect()
+});
+
+/// Masks wider than the backing value, interned when first used
+static WIDE_MASKS: Lazy<RwLock<HashMap<usize, &'static BigInt>>> = Lazy::new(Default::default);
+
+/// Literals of the masks in `MASKS`, shared by every value they are emitted in
+static MASK_LITERALS: Lazy<Vec<Shared<Literal>>> = Lazy::new(|| {
+    MASKS
+        .iter()
+        .map(|mask| Shared::new(Literal::Int(mask.clone())))
+        .collect()
+});
+
+/// Gets the mask covering the low `width` bits
+pub(crate) fn mask(width: usize) -> &'static BigInt {
+    if let Some(mask) = MASKS.get(width) {
+        return mask;
+    }
+
+    if let Some(mask) = WIDE_MASKS.read().get(&width) {
+        return mask;
+    }
+
+    WIDE_MASKS
+        .write()
+        .entry(width)
+        .or_insert_with(|| Box::leak(Box::new((BigInt::from(1) << width) - 1)))
+}
+
+/// Gets a literal of the mask covering the low `width` bits, shared with every
+/// other mask of the same width that fits in the backing value
+pub(crate) fn mask_literal(width: usize) -> Shared<Value> {
+    let literal = match MASK_LITERALS.get(width) {
+        Some(literal) => literal.clone(),
+        None => Shared::new(Literal::Int(mask(width).clone())),
+    };
+
+    Shared::new(Value::Literal(literal))
+}
+
+/// Builds the mask covering the low `length` bits for a length only known at
+/// runtime, all ones shifted down to the length and cleared if the length is
+/// zero as shifting by the full width is undefined
+fn runtime_mask(length: Shared<Value>) -> Shared<Value> {
+    // (ones >> ((64 - length) & 63)) * (length != 0)
+    let shift = Operation::And(
+        Operation::Subtract(Literal::Int(MAX_LENGTH.into()).into(), length.clone()).into(),
+        Literal::Int((MAX_LENGTH - 1).into()).into(),
+    );
+    let nonzero = Operation::Cast(
+        Operation::NotEqual(length, Literal::Int(0.into()).into()).into(),
+        Shared::new(integer()),
+    );
+
+    Operation::Multiply(
+        Operation::RightShift(mask_literal(MAX_LENGTH), shift.into()).into(),
+        nonzero.into(),
+    )
+    .into()
+}
+
+/// Masks a value to its low `length` bits, the mask is elided if it would
+/// cover the whole backing value
+fn mask_value(value: Shared<Value>, length: usize) -> Shared<Value> {
+    if length >= MAX_LENGTH {
+        return value;
+    }
+
+    Operation::And(value, mask_literal(length)).into()
+}
+
+/// Gets the operands of a comparison, which must have the same length
+fn comparison_operands(value: &Shared<Value>) -> Option<(Shared<Value>, Shared<Value>)> {
+    match &*value.get() {
+        Value::Operation(
+            Operation::Equal(left, right)
+            | Operation::NotEqual(left, right)
+            | Operation::LessThan(left, right)
+            | Operation::LessThanOrEqual(left, right)
+            | Operation::GreaterThan(left, right)
+            | Operation::GreaterThanOrEqual(left, right),
+        ) => Some((left.clone(), right.clone())),
+        _ => None,
+    }
+}
+
+fn assert_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    arguments: &[Shared<Value>],
+) {
+    pass.resolve_assert(statement, arguments);
+}
+
+fn zeros_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    pass.rewrite_to_copy(statement, expression, |pass| {
+        if !pass.check_arguments(arguments, 1) {
+            return None;
+        }
+
+        let destination = pass.destination_ident(expression)?;
+
+        match pass.evaluate_length(&arguments[0], statement) {
+            Some(length) => {
+                let length = pass.length_to_usize(&length)?;
+                pass.set_size(destination, Size::Static(length));
+            }
+
+            // zeros are the same value at any length, only the width is stored
+            None => {
+                if let Some(Size::Unknown) = pass.get_size(destination) {
+                    pass.set_size(destination, Size::Runtime(arguments[0].clone()));
+                }
+            }
+        }
+
+        Some(Literal::Int(0.into()).into())
+    });
+}
+
+fn ones_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    pass.rewrite_to_copy(statement, expression, |pass| {
+        if !pass.check_arguments(arguments, 1) {
+            return None;
+        }
+
+        let destination = pass.destination_ident(expression)?;
+
+        let source = format!("Ones({})", *arguments[0].get());
+
+        // runtime length, the mask is computed by the generated code
+        let Some(length) = pass.evaluate_length(&arguments[0], statement) else {
+            // a runtime length never conflicts with a static one
+            pass.assign_size(destination, Size::Runtime(arguments[0].clone()), &source);
+            return Some(runtime_mask(arguments[0].clone()));
+        };
+        let length = pass.length_to_usize(&length)?;
+
+        // all ones does not fit the backing value, unlike zeros which are the same
+        // value at any length
+        if length > MAX_LENGTH {
+            pass.error(
+                Some(destination),
+                format!("cannot create {length} ones, exceeds maximum length of {MAX_LENGTH}"),
+            );
+            return None;
+        }
+
+        // a conflicting call is left in place, so the conflict is reported by every
+        // sweep
+        pass.assign_size(destination, Size::Static(length), &source)
+            .then(|| mask_literal(length))
+    });
+}
+
+/// Casts a value to `typ` if it has a different static length, so the copy
+/// made of it is not mistaken for an assignment of a conflicting length
+fn widen(pass: &ResolveBitvectors, value: &Shared<Value>, typ: Type) -> Shared<Value> {
+    match (pass.get_value_size(value), typ.get_size()) {
+        (Some(Size::Static(source)), Some(Size::Static(length))) if source != length => {
+            Operation::Cast(value.clone(), Shared::new(typ)).into()
+        }
+        _ => value.clone(),
+    }
+}
+
+/// Zero-extension is a no-op on the backing value, only the length changes
+fn zero_extend_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    let Some(length) = pass.evaluate_length(&arguments[1], statement) else {
+        return;
+    };
+
+    let Some(length) = pass.length_to_usize(&length) else {
+        return;
+    };
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    if length > MAX_LENGTH {
+        pass.error(
+            Some(destination),
+            format!("cannot zero extend to {length} bits, exceeds maximum length of {MAX_LENGTH}"),
+        );
+        return;
+    }
+
+    if let Some(Size::Static(source_length)) = pass.get_value_size(&arguments[0]) {
+        if source_length > length {
+            pass.error(
+                Some(destination),
+                format!("cannot zero extend {source_length} bit bitvector to {length} bits"),
+            );
+            return;
+        }
+    }
+
+    let value = widen(
+        pass,
+        &arguments[0],
+        Type::Bits {
+            size: Size::Static(length),
+        },
+    );
+
+    pass.set_size(destination, Size::Static(length));
+    pass.rewrite(statement, expression, value);
+}
+
+/// Sign-extension shifts the sign bit of the source up to the top of the
+/// backing value, then arithmetically shifts it back down
+fn sign_extend_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    // leave the call untouched until the source length is resolved
+    let Some(Size::Static(source_length)) = pass.get_value_size(&arguments[0]) else {
+        return;
+    };
+
+    let Some(length) = pass.evaluate_length(&arguments[1], statement) else {
+        return;
+    };
+
+    let Some(length) = pass.length_to_usize(&length) else {
+        return;
+    };
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    if length > MAX_LENGTH {
+        pass.error(
+            Some(destination),
+            format!("cannot sign extend to {length} bits, exceeds maximum length of {MAX_LENGTH}"),
+        );
+        return;
+    }
+
+    if source_length == 0 || source_length > length {
+        pass.error(
+            Some(destination),
+            format!("cannot sign extend {source_length} bit bitvector to {length} bits"),
+        );
+        return;
+    }
+
+    let shift: Shared<Value> = Literal::Int((MAX_LENGTH - source_length).into()).into();
+
+    // ((value << (64 - n)) >> (64 - n)) & mask(length)
+    let value = Operation::ArithmeticRightShift(
+        Operation::LeftShift(arguments[0].clone(), shift.clone()).into(),
+        shift,
+    );
+
+    pass.set_size(destination, Size::Static(length));
+    pass.rewrite(statement, expression, mask_value(value.into(), length));
+}
+
+/// Truncation keeps the low bits of the source, clearing the rest of the
+/// backing value
+fn truncate_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    let Some(length) = pass.evaluate_length(&arguments[1], statement) else {
+        return;
+    };
+
+    let Some(length) = pass.length_to_usize(&length) else {
+        return;
+    };
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    if length > MAX_LENGTH {
+        pass.error(
+            Some(destination),
+            format!("cannot truncate to {length} bits, exceeds maximum length of {MAX_LENGTH}"),
+        );
+        return;
+    }
+
+    pass.set_size(destination, Size::Static(length));
+    pass.rewrite(
+        statement,
+        expression,
+        mask_value(arguments[0].clone(), length),
+    );
+}
+
+/// The backing value of a bitvector already holds its unsigned value, so it is
+/// copied through unchanged into an integer
+fn unsigned_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 1) {
+        return;
+    }
+
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    let value = widen(pass, &arguments[0], integer());
+    pass.set_type(destination, integer());
+    pass.rewrite(statement, expression, value);
+}
+
+/// Sign-extends from the operand length into an integer, by flipping the sign
+/// bit and subtracting its weight
+fn signed_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 1) {
+        return;
+    }
+
+    // leave the call untouched until the operand length is resolved
+    let Some(Size::Static(length)) = pass.get_value_size(&arguments[0]) else {
+        return;
+    };
+
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    if length == 0 || length > MAX_LENGTH {
+        pass.error(
+            Some(destination),
+            format!("cannot convert {length} bit bitvector to a signed integer"),
+        );
+        return;
+    }
+
+    pass.set_type(destination, integer());
+    pass.rewrite(statement, expression, sign_extend(&arguments[0], length));
+}
+
+/// Sign extends the backing value of a bitvector of `length` bits
+fn sign_extend(value: &Shared<Value>, length: usize) -> Shared<Value> {
+    let sign: Shared<Value> = Literal::Int(BigInt::from(1) << (length - 1)).into();
+
+    // (value ^ sign) - sign
+    Operation::Subtract(Operation::Xor(value.clone(), sign.clone()).into(), sign).into()
+}
+
+/// Gets the type of a field of a struct type
+fn field_type(typ: &Shared<Type>, field: InternedString) -> Option<Shared<Type>> {
+    match &*typ.get() {
+        Type::Struct { fields, .. } => fields
+            .iter()
+            .find(|named| named.name == field)
+            .map(|named| named.typ.clone()),
+        _ => None,
+    }
+}
+
+/// Determines whether a type has a static size, or no size at all
+fn is_static(typ: &Shared<Type>) -> bool {
+    !matches!(typ.get().get_size(), Some(Size::Unknown | Size::Runtime(_)))
+}
+
+/// Integer holding the result of converting a bitvector
+fn integer() -> Type {
+    Type::Integer {
+        size: Size::Static(MAX_LENGTH),
+        range: None,
+    }
+}
+
+/// Complementing sets the unused high bits of the backing value, so the result
+/// is masked back down to the operand length
+fn not_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 1) {
+        return;
+    }
+
+    // leave the call untouched until the operand length is resolved
+    let Some(Size::Static(length)) = pass.get_value_size(&arguments[0]) else {
+        return;
+    };
+
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    // ~value & mask
+    let value = mask_value(Operation::Complement(arguments[0].clone()).into(), length);
+
+    pass.set_size(destination, Size::Static(length));
+    pass.rewrite(statement, expression, value);
+}
+
+fn and_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    binary_operation(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::And,
+        false,
+    );
+}
+
+fn or_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    binary_operation(pass, statement, expression, arguments, Operation::Or, true);
+}
+
+fn xor_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    binary_operation(pass, statement, expression, arguments, Operation::Xor, true);
+}
+
+/// Addition wraps modulo the length, so the carry out of the top bit is masked
+fn add_bits_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    binary_operation(pass, statement, expression, arguments, Operation::Add, true);
+}
+
+/// Subtraction wraps modulo the length, so the borrow into the high bits is
+/// masked
+fn sub_bits_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    binary_operation(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::Subtract,
+        true,
+    );
+}
+
+/// Operations between two bitvectors of the same length, optionally masking
+/// the result to that length
+fn binary_operation(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+    operation: fn(Shared<Value>, Shared<Value>) -> Operation,
+    masked: bool,
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    pass.unify_sizes(&arguments[0], &arguments[1]);
+
+    // leave the call untouched until the operand length is resolved
+    let Some(Size::Static(length)) = pass.get_value_size(&arguments[0]) else {
+        return;
+    };
+
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    let value: Shared<Value> = operation(arguments[0].clone(), arguments[1].clone()).into();
+    let value = if masked {
+        mask_value(value, length)
+    } else {
+        value
+    };
+
+    pass.set_size(destination, Size::Static(length));
+    pass.rewrite(statement, expression, value);
+}
+
+/// Bits shifted above the length are masked off, unlike shifts of integers
+fn shiftl_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    shift(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::LeftShift,
+        true,
+    );
+}
+
+/// The high bits of the backing value are always clear, so shifting right
+/// needs no mask
+fn shiftr_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    shift(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::RightShift,
+        false,
+    );
+}
+
+/// Shifts a bitvector by an integer amount, which may be a literal or a local
+fn shift(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+    operation: fn(Shared<Value>, Shared<Value>) -> Operation,
+    masked: bool,
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    // leave the call untouched until the operand length is resolved
+    let Some(Size::Static(length)) = pass.get_value_size(&arguments[0]) else {
+        return;
+    };
+
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    // a constant amount is a range of one value
+    let range = match pass.evaluate_length(&arguments[1], statement) {
+        Some(amount) => Some(Range {
+            min: amount.clone(),
+            max: amount,
+        }),
+        None => pass.get_range(&arguments[1]),
+    };
+
+    let value = match range {
+        // shifting by at least the length clears every bit
+        Some(range) if range.min >= BigInt::from(length) => Literal::Int(0.into()).into(),
+
+        Some(Range { min, max }) if min == max => {
+            // shifting by zero leaves the value unchanged
+            if min == BigInt::from(0) {
+                arguments[0].clone()
+            } else {
+                shifted(
+                    operation(arguments[0].clone(), Literal::Int(min).into()),
+                    length,
+                    masked,
+                )
+            }
+        }
+
+        // an amount proven to be below the length stays within the backing value
+        Some(range) if range.min >= BigInt::from(0) && range.max < BigInt::from(length) => shifted(
+            operation(arguments[0].clone(), arguments[1].clone()),
+            length,
+            masked,
+        ),
+
+        // native shifts are only defined below the width of the backing value,
+        // so the amount is taken modulo that width and the result cleared once
+        // the amount reaches the length
+        _ => {
+            let amount = Operation::And(
+                arguments[1].clone(),
+                Literal::Int((MAX_LENGTH - 1).into()).into(),
+            );
+            let within = Operation::Cast(
+                Operation::LessThan(arguments[1].clone(), Literal::Int(length.into()).into())
+                    .into(),
+                Shared::new(integer()),
+            );
+
+            Operation::Multiply(
+                shifted(
+                    operation(arguments[0].clone(), amount.into()),
+                    length,
+                    masked,
+                ),
+                within.into(),
+            )
+            .into()
+        }
+    };
+
+    pass.set_size(destination, Size::Static(length));
+    pass.rewrite(statement, expression, value);
+}
+
+/// Masks a left shift back down to the operand length
+fn shifted(operation: Operation, length: usize, masked: bool) -> Shared<Value> {
+    if masked {
+        mask_value(operation.into(), length)
+    } else {
+        operation.into()
+    }
+}
+
+/// Concatenation of any number of operands, each shifted above the operands
+/// following it
+fn concat_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    pass.rewrite_to_copy(statement, expression, |pass| {
+        if arguments.len() < 2 {
+            pass.error(
+                None,
+                format!("expected at least 2 arguments, found {}", arguments.len()),
+            );
+            return None;
+        }
+
+        // report every unresolved operand before giving up
+        let lengths = arguments
+            .iter()
+            .map(|operand| concat_operand_length(pass, operand))
+            .collect::<Vec<_>>();
+        let lengths = lengths.into_iter().collect::<Option<Vec<_>>>()?;
+
+        let destination = pass.destination_ident(expression)?;
+
+        let statics = lengths
+            .iter()
+            .map(|length| match length {
+                Size::Static(length) => Some(*length),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+
+        // operands contributing to the value with their shift, the total length of
+        // the operands following them
+        let operands: Vec<(Shared<Value>, Shared<Value>)> = match statics {
+            Some(lengths) => {
+                let length = pass.checked_length_sum(Some(destination), lengths.iter().copied())?;
+
+                pass.set_size(destination, Size::Static(length));
+
+                // the result does not fit in the backing value, so the call is kept intact
+                // with its full width recorded for codegen to pick a wider storage type
+                if length > MAX_LENGTH {
+                    return None;
+                }
+
+                // zero-length operands are empty, contributing no bits
+                (0..lengths.len())
+                    .filter(|i| lengths[*i] != 0)
+                    .map(|i| {
+                        let shift = lengths[i + 1..].iter().sum::<usize>();
+                        (arguments[i].clone(), Literal::Int(shift.into()).into())
+                    })
+                    .collect()
+            }
+
+            // runtime lengths, the destination length is computed by the generated code
+            None => {
+                let lengths = lengths.into_iter().map(length_value).collect::<Vec<_>>();
+                let sum = |lengths: &[Shared<Value>]| {
+                    lengths
+                        .iter()
+                        .cloned()
+                        .reduce(|sum, length| Operation::Add(sum, length).into())
+                };
+
+                if matches!(pass.get_size(destination), Some(Size::Unknown)) {
+                    let length = sum(&lengths).expect("at least 2 operands");
+                    pass.set_size(destination, Size::Runtime(length));
+                }
+
+                (0..lengths.len())
+                    .map(|i| {
+                        let shift =
+                            sum(&lengths[i + 1..]).unwrap_or_else(|| Literal::Int(0.into()).into());
+                        (arguments[i].clone(), shift)
+                    })
+                    .collect()
+            }
+        };
+
+        // ((first << shift) | (second << shift)) | ... | last, or the empty value
+        // if every operand is empty
+        let last = operands.len().saturating_sub(1);
+        let value = operands
+            .into_iter()
+            .enumerate()
+            .map(|(i, (operand, shift))| {
+                if i == last {
+                    operand
+                } else {
+                    Operation::LeftShift(operand, shift).into()
+                }
+            })
+            .reduce(|value, operand| Operation::Or(value, operand).into())
+            .unwrap_or_else(|| Literal::Int(0.into()).into());
+
+        Some(value)
+    });
+}
+
+/// Gets the static or runtime length of a concatenation operand
+///
+/// A bit literal operand has the length of its bits.
+fn concat_operand_length(pass: &mut ResolveBitvectors, operand: &Shared<Value>) -> Option<Size> {
+    if let Value::Literal(literal) = &*operand.get() {
+        if let Literal::Bits(bits) = &*literal.get() {
+            return Some(Size::Static(bits.len()));
+        }
+    }
+
+    let Some(ident) = operand.get().get_ident() else {
+        pass.error(None, "concatenation operand is not a local");
+        return None;
+    };
+
+    match pass.get_size(ident) {
+        Some(size @ (Size::Static(_) | Size::Runtime(_))) => Some(size),
+        _ => {
+            pass.error(
+                Some(ident),
+                format!("concatenation operand {ident} does not have a resolved length"),
+            );
+            None
+        }
+    }
+}
+
+/// Gets a resolved length as a value
+fn length_value(size: Size) -> Shared<Value> {
+    match size {
+        Size::Static(length) => Literal::Int(length.into()).into(),
+        Size::Runtime(length) => length,
+        Size::Unknown => unreachable!("length must be resolved"),
+    }
+}
+
+fn subrange_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 3) {
+        return;
+    }
+
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    let (source, high, low) = (&arguments[0], &arguments[1], &arguments[2]);
+
+    let bounds = pass
+        .evaluate_bound(high, statement)
+        .zip(pass.evaluate_bound(low, statement));
+
+    let (low, mask) = match bounds {
+        Some((high, low)) => {
+            let (Some(high), Some(low)) = (pass.length_to_usize(&high), pass.length_to_usize(&low))
+            else {
+                return;
+            };
+
+            if high < low {
+                pass.error(
+                    Some(destination),
+                    format!("subrange [{high}..{low}] has a negative length"),
+                );
+                return;
+            }
+
+            let Some(length) = pass.checked_length_sum(Some(destination), [high - low, 1]) else {
+                return;
+            };
+            pass.set_size(destination, Size::Static(length));
+
+            (Literal::Int(low.into()).into(), mask_literal(length))
+        }
+
+        // runtime bounds, the length and mask are computed by the generated code
+        None => {
+            let length: Shared<Value> = Operation::Add(
+                Operation::Subtract(high.clone(), low.clone()).into(),
+                Literal::Int(1.into()).into(),
+            )
+            .into();
+
+            if matches!(pass.get_size(destination), Some(Size::Unknown)) {
+                pass.set_size(destination, Size::Runtime(length.clone()));
+            }
+
+            (low.clone(), runtime_mask(length))
+        }
+    };
+
+    // (value >> lo) & mask
+    let value = Operation::And(Operation::RightShift(source.clone(), low).into(), mask);
+
+    pass.rewrite(statement, expression, value.into());
+}
+
+/// Gets the length and mask of an integer slice, the length being `None` if
+/// only known at runtime
+fn slice_mask(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    length: &Shared<Value>,
+) -> Option<(Option<usize>, Shared<Value>)> {
+    match pass.evaluate_length(length, statement) {
+        Some(length) => {
+            let length = pass.length_to_usize(&length)?;
+            Some((Some(length), mask_literal(length)))
+        }
+
+        None => Some((None, runtime_mask(length.clone()))),
+    }
+}
+
+fn get_slice_int_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 3) {
+        return;
+    }
+
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    let (length, source, start) = (&arguments[0], &arguments[1], &arguments[2]);
+
+    let Some((static_length, mask)) = slice_mask(pass, statement, length) else {
+        return;
+    };
+
+    match static_length {
+        Some(length) => pass.set_size(destination, Size::Static(length)),
+        None => {
+            if matches!(pass.get_size(destination), Some(Size::Unknown)) {
+                pass.set_size(destination, Size::Runtime(length.clone()));
+            }
+        }
+    }
+
+    // fold constant starts, otherwise shift by the runtime start
+    let start = match pass.evaluate_length(start, statement) {
+        Some(start) => Literal::Int(start).into(),
+        None => start.clone(),
+    };
+
+    // (value >> start) & mask
+    let value = Operation::And(Operation::RightShift(source.clone(), start).into(), mask);
+
+    pass.rewrite(statement, expression, value.into());
+}
+
+fn set_slice_int_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 4) {
+        return;
+    }
+
+    let (length, target, start, slice) =
+        (&arguments[0], &arguments[1], &arguments[2], &arguments[3]);
+
+    let Some((static_length, mask)) = slice_mask(pass, statement, length) else {
+        return;
+    };
+
+    // the inserted slice is of the supplied length, fields keep their declared
+    // size
+    if let (Some(length), Some(Size::Unknown), Some(ident)) = (
+        static_length,
+        pass.get_value_size(slice),
+        slice.get().get_ident(),
+    ) {
+        pass.set_size(ident, Size::Static(length));
+    }
+
+    let start = match pass.evaluate_length(start, statement) {
+        Some(start) => Literal::Int(start).into(),
+        None => start.clone(),
+    };
+
+    // (value & ~(mask << start)) | ((slice & mask) << start)
+    let cleared = Operation::And(
+        target.clone(),
+        Operation::Complement(Operation::LeftShift(mask.clone(), start.clone()).into()).into(),
+    );
+    let inserted = Operation::LeftShift(Operation::And(slice.clone(), mask).into(), start);
+    let value = Operation::Or(cleared.into(), inserted.into());
+
+    pass.rewrite(statement, expression, value.into());
+}
+
+fn bit_access_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    // fold constant indices, otherwise shift by the runtime index
+    let index = match pass.evaluate_length(&arguments[1], statement) {
+        Some(index) => Literal::Int(index).into(),
+        None => arguments[1].clone(),
+    };
+
+    // (value >> index) & 1
+    let value = Operation::And(
+        Operation::RightShift(arguments[0].clone(), index).into(),
+        Literal::Int(1.into()).into(),
+    );
+
+    pass.set_size(destination, Size::Static(1));
+    pass.rewrite(statement, expression, value.into());
+}
+
+fn vector_access_handler(
+    pass: &mut ResolveBitvectors,
+    _: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    let Some(element_type) = pass.get_element_type(&arguments[0]) else {
+        return;
+    };
+
+    // the access itself is lowered later, only the element length is resolved
+    let size = element_type.get().get_size();
+    if let Some(size @ (Size::Static(_) | Size::Runtime(_))) = size {
+        pass.set_size(destination, size);
+    }
+}
+
+fn vector_update_handler(
+    pass: &mut ResolveBitvectors,
+    _: &Shared<Statement>,
+    _: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 3) {
+        return;
+    }
+
+    let Some(element_type) = pass.get_element_type(&arguments[0]) else {
+        return;
+    };
+
+    let Some(element) = element_type.get().get_size() else {
+        return;
+    };
+
+    let value = &arguments[2];
+    match (pass.get_value_size(value), element) {
+        // fields keep their declared size
+        (Some(Size::Unknown), element @ (Size::Static(_) | Size::Runtime(_))) => {
+            if let Some(ident) = value.get().get_ident() {
+                pass.set_size(ident, element);
+            }
+        }
+        (Some(Size::Static(length)), Size::Static(element)) if length != element => pass.error(
+            value.get().get_ident(),
+            format!("cannot write {length} bits to vector of {element} bit elements"),
+        ),
+        _ => (),
+    }
+}
+
+fn replicate_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    // leave the call untouched until the pattern length is resolved
+    let Some(Size::Static(pattern_length)) = pass.get_value_size(&arguments[0]) else {
+        return;
+    };
+
+    let Some(count) = pass.evaluate_length(&arguments[1], statement) else {
+        return;
+    };
+    let Some(count) = pass.length_to_usize(&count) else {
+        return;
+    };
+    let Some(destination) = pass.destination_ident(expression) else {
+        return;
+    };
+
+    let Some(length) = pass.checked_length_product(Some(destination), pattern_length, count) else {
+        return;
+    };
+
+    pass.set_size(destination, Size::Static(length));
+
+    // wider results keep the replicate call for codegen to handle, as do huge
+    // counts of an empty pattern
+    if length > MAX_LENGTH || count > MAX_LENGTH {
+        return;
+    }
+
+    // pattern | (pattern << n) | (pattern << 2n) | ...
+    let value = (1..count).fold(
+        match count {
+            0 => Literal::Int(0.into()).into(),
+            _ => arguments[0].clone(),
+        },
+        |value, i| {
+            let shifted = Operation::LeftShift(
+                arguments[0].clone(),
+                Literal::Int((i * pattern_length).into()).into(),
+            );
+
+            Operation::Or(value, shifted.into()).into()
+        },
+    );
+
+    pass.rewrite(statement, expression, value);
+}
+
+fn eq_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    pass.rewrite_to_copy(statement, expression, |pass| {
+        equality(pass, expression, arguments, Operation::Equal)
+    });
+}
+
+fn neq_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    pass.rewrite_to_copy(statement, expression, |pass| {
+        equality(pass, expression, arguments, Operation::NotEqual)
+    });
+}
+
+/// Lowers an equality comparison to `operation`, on the masked operand if the
+/// other is a bit pattern containing wildcards
+fn equality(
+    pass: &mut ResolveBitvectors,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+    operation: fn(Shared<Value>, Shared<Value>) -> Operation,
+) -> Option<Shared<Value>> {
+    let pattern = match arguments {
+        [left, right] => match (wildcard_pattern(left), wildcard_pattern(right)) {
+            (None, Some(pattern)) => Some((left, pattern)),
+            (Some(pattern), None) => Some((right, pattern)),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    match pattern {
+        Some((scrutinee, pattern)) => {
+            pattern_comparison(pass, expression, scrutinee, &pattern, operation)
+        }
+        None => compared(pass, expression, arguments, operation),
+    }
+}
+
+/// Lowers a comparison with a bit pattern containing wildcards to `operation`
+/// on the masked scrutinee
+///
+/// The mask and pattern cover the static length of the scrutinee, a local of
+/// unknown length takes the length of the pattern.
+fn pattern_comparison(
+    pass: &mut ResolveBitvectors,
+    expression: &Expression,
+    scrutinee: &Shared<Value>,
+    pattern: &[Bit],
+    operation: fn(Shared<Value>, Shared<Value>) -> Operation,
+) -> Option<Shared<Value>> {
+    match pass.get_value_size(scrutinee) {
+        Some(Size::Static(length)) if length != pattern.len() => {
+            pass.error(
+                scrutinee.get().get_ident(),
+                format!(
+                    "pattern of {} bits compared with {length} bit bitvector",
+                    pattern.len()
+                ),
+            );
+            return None;
+        }
+        Some(Size::Static(_)) => (),
+        Some(Size::Unknown) => {
+            let ident = scrutinee.get().get_ident()?;
+            pass.set_size(ident, Size::Static(pattern.len()));
+        }
+        // leave the call untouched until the length is resolved
+        _ => return None,
+    }
+
+    let (mask, value) = pattern_mask(pattern);
+    let value = make_pattern_match(
+        operation,
+        scrutinee.clone(),
+        Literal::Int(bits_to_bigint(&mask)).into(),
+        Literal::Int(bits_to_bigint(&value)).into(),
+    );
+
+    set_comparison_size(pass, expression);
+    Some(value.into())
+}
+
+/// Lowers an unsigned comparison of two bitvectors, which must be the same
+/// length, to `operation`
+fn comparison(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+    operation: fn(Shared<Value>, Shared<Value>) -> Operation,
+) {
+    pass.rewrite_to_copy(statement, expression, |pass| {
+        compared(pass, expression, arguments, operation)
+    });
+}
+
+/// Gets the value of an unsigned comparison of two bitvectors of the same
+/// length
+fn compared(
+    pass: &mut ResolveBitvectors,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+    operation: fn(Shared<Value>, Shared<Value>) -> Operation,
+) -> Option<Shared<Value>> {
+    if !pass.check_arguments(arguments, 2) {
+        return None;
+    }
+
+    pass.unify_sizes(&arguments[0], &arguments[1]);
+
+    // high bits left set by unmasked arithmetic would change the result
+    let [left, right] =
+        [&arguments[0], &arguments[1]].map(|operand| match pass.get_value_size(operand) {
+            Some(Size::Static(length)) if pass.may_have_dirty_high_bits(operand) => {
+                mask_value(operand.clone(), length)
+            }
+            _ => operand.clone(),
+        });
+    let value = operation(left, right);
+
+    set_comparison_size(pass, expression);
+    Some(value.into())
+}
+
+/// Lowers a signed comparison of two bitvectors to `operation` on the sign
+/// extended operands
+fn signed_comparison(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+    operation: fn(Shared<Value>, Shared<Value>) -> Operation,
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    pass.unify_sizes(&arguments[0], &arguments[1]);
+
+    // leave the call untouched until the operand length is resolved
+    let length = pass
+        .get_value_size(&arguments[0])
+        .or_else(|| pass.get_value_size(&arguments[1]));
+    let Some(Size::Static(length)) = length else {
+        return;
+    };
+
+    if length == 0 || length > MAX_LENGTH {
+        pass.error(
+            None,
+            format!("cannot compare {length} bit bitvectors as signed integers"),
+        );
+        return;
+    }
+
+    let value = operation(
+        sign_extend(&arguments[0], length),
+        sign_extend(&arguments[1], length),
+    );
+
+    set_comparison_size(pass, expression);
+    pass.rewrite(statement, expression, value.into());
+}
+
+/// The result of a comparison is a single bit, if the destination is a
+/// bitvector rather than a bool
+fn set_comparison_size(pass: &mut ResolveBitvectors, expression: &Expression) {
+    if let Expression::Identifier(destination) = expression {
+        if pass.get_size(*destination).is_some() {
+            pass.set_size(*destination, Size::Static(1));
+        }
+    }
+}
+
+/// Booleans are combined with logical rather than bitwise operations, the
+/// result of a comparison being a boolean rather than a bitvector
+fn and_bool_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    let value = Operation::LogicalAnd(arguments[0].clone(), arguments[1].clone());
+    pass.rewrite(statement, expression, value.into());
+}
+
+fn or_bool_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    if !pass.check_arguments(arguments, 2) {
+        return;
+    }
+
+    let value = Operation::LogicalOr(arguments[0].clone(), arguments[1].clone());
+    pass.rewrite(statement, expression, value.into());
+}
+
+fn lt_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    comparison(pass, statement, expression, arguments, Operation::LessThan);
+}
+
+fn lteq_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    comparison(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::LessThanOrEqual,
+    );
+}
+
+fn gt_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    comparison(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::GreaterThan,
+    );
+}
+
+fn gteq_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    comparison(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::GreaterThanOrEqual,
+    );
+}
+
+fn slt_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    signed_comparison(pass, statement, expression, arguments, Operation::LessThan);
+}
+
+fn slteq_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    signed_comparison(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::LessThanOrEqual,
+    );
+}
+
+fn sgt_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    signed_comparison(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::GreaterThan,
+    );
+}
+
+fn sgteq_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    signed_comparison(
+        pass,
+        statement,
+        expression,
+        arguments,
+        Operation::GreaterThanOrEqual,
+    );
+}
+
+fn undefined_handler(
+    pass: &mut ResolveBitvectors,
+    statement: &Shared<Statement>,
+    expression: &Expression,
+    arguments: &[Shared<Value>],
+) {
+    pass.rewrite_to_copy(statement, expression, |pass| {
+        if !pass.check_arguments(arguments, 1) {
+            return None;
+        }
+
+        let destination = pass.destination_ident(expression)?;
+
+        match pass.evaluate_length(&arguments[0], statement) {
+            Some(length) => {
+                let length = pass.length_to_usize(&length)?;
+                pass.set_size(destination, Size::Static(length));
+            }
+
+            // the length depends on a parameter or a value only known at runtime
+            None => {
+                if let Some(Size::Unknown) = pass.get_size(destination) {
+                    pass.set_size(destination, Size::Runtime(arguments[0].clone()));
+                }
+            }
+        }
+
+        Some(Literal::Int(0.into()).into())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            control_flow::{ControlFlowBlock, Terminator},
+            passes::{
+                fold_constants::FoldConstants,
+                range_analysis::RangeAnalysis,
+                resolve_bitvectors::{mask, ResolveBitvectors, SizeConflictPolicy},
+                Pass, Severity,
+            },
+            test_utils::*,
+            Ast, Expression, NamedType, Operation, Size, Statement, Type, Value,
+        },
+        common::shared::Shared,
+        num_bigint::BigInt,
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn zero_extend() {
+        init();
+
+        let statement = call("y", "ZeroExtend", vec![ident("x"), int(32)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(16))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+        assert!(pass.take_diagnostics().is_empty());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(32))));
+        assert_eq!(copied_value(&statement), "x as bv32");
+    }
+
+    /// Resolves `d = ZeroExtend(s, 32)` of a 16-bit `s`, returning the size of
+    /// `d` and the number of diagnostics
+    fn zero_extended(policy: SizeConflictPolicy) -> (Option<Size>, usize) {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("s", bits(Size::Static(16))),
+                declare("d", bits(Size::Unknown)),
+                call("d", "ZeroExtend", vec![ident("s"), int(32)]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default().with_policy(policy);
+        pass.run(ast.clone());
+
+        (size_of(&ast, "f", "d"), pass.take_diagnostics().len())
+    }
+
+    #[test]
+    fn zero_extend_keep_existing() {
+        let (size, diagnostics) = zero_extended(SizeConflictPolicy::KeepExisting);
+        assert!(matches!(size, Some(Size::Static(32))));
+        assert_eq!(diagnostics, 0);
+    }
+
+    #[test]
+    fn zero_extend_prefer_longest() {
+        let (size, diagnostics) = zero_extended(SizeConflictPolicy::PreferLongest);
+        assert!(matches!(size, Some(Size::Static(32))));
+        assert_eq!(diagnostics, 0);
+    }
+
+    #[test]
+    fn zero_extend_prefer_shortest() {
+        let (size, diagnostics) = zero_extended(SizeConflictPolicy::PreferShortest);
+        assert!(matches!(size, Some(Size::Static(32))));
+        assert_eq!(diagnostics, 0);
+    }
+
+    #[test]
+    fn zero_extend_error() {
+        let (size, diagnostics) = zero_extended(SizeConflictPolicy::Error);
+        assert!(matches!(size, Some(Size::Static(32))));
+        assert_eq!(diagnostics, 0);
+    }
+
+    #[test]
+    fn zeros_aliases() {
+        init();
+
+        let statements = ["Zeros", "sail_zeros", "zeros"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| call(&format!("x{i}"), name, vec![int(8)]))
+            .collect::<Vec<_>>();
+        let ast = ast([function(
+            "f",
+            (0..3)
+                .map(|i| declare(&format!("x{i}"), bits(Size::Unknown)))
+                .chain(statements.iter().cloned())
+                .collect(),
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        for (i, statement) in statements.iter().enumerate() {
+            let name = format!("x{i}");
+            assert!(matches!(size_of(&ast, "f", &name), Some(Size::Static(8))));
+            assert_eq!(copied_value(statement), "0");
+        }
+    }
+
+    #[test]
+    fn unhandled_builtin() {
+        init();
+
+        let ast = || {
+            ast([function(
+                "f",
+                vec![
+                    declare("x", bits(Size::Unknown)),
+                    declare("y", bits(Size::Static(8))),
+                    call("x", "frobnicate", vec![int(4)]),
+                    call("y", "frobnicate", vec![int(4)]),
+                ],
+            )])
+        };
+
+        for (strict, severity) in [(false, Severity::Warning), (true, Severity::Error)] {
+            let mut pass = ResolveBitvectors::default().with_strict_builtins(strict);
+            pass.run(ast());
+
+            let diagnostics = pass.take_diagnostics();
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(diagnostics[0].severity, severity);
+            assert_eq!(diagnostics[0].ident, Some("x".into()));
+            assert_eq!(
+                diagnostics[0].message,
+                "no handler for builtin frobnicate, length of x is unknown"
+            );
+        }
+    }
+
+    #[test]
+    fn ones_full_width() {
+        init();
+
+        let statement = call("y", "Ones", vec![int(64)]);
+        let ast = ast([function(
+            "f",
+            vec![declare("y", bits(Size::Unknown)), statement.clone()],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(64))));
+        assert_eq!(copied_value(&statement), u64::MAX.to_string());
+    }
+
+    #[test]
+    fn ones_too_long() {
+        init();
+
+        let statement = call("y", "Ones", vec![int(128)]);
+        let ast = ast([function(
+            "f",
+            vec![declare("y", bits(Size::Unknown)), statement.clone()],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("exceeds maximum length"));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn ones_conflicting_length() {
+        init();
+
+        let statement = call("y", "Ones", vec![int(32)]);
+        let ast = ast([function(
+            "f",
+            vec![declare("y", bits(Size::Static(16))), statement.clone()],
+        )]);
+
+        let mut pass = ResolveBitvectors::default().with_policy(SizeConflictPolicy::Error);
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "conflicting lengths, y has 16 bits but is assigned Ones(32) with 32"
+        );
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(16))));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn zero_extend_too_long() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(16))),
+                declare("y", bits(Size::Unknown)),
+                call("y", "ZeroExtend", vec![ident("x"), int(128)]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("exceeds maximum length"));
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Unknown)));
+    }
+
+    #[test]
+    fn sign_extend() {
+        init();
+
+        let statement = call("y", "SignExtend", vec![ident("x"), int(32)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(16))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(32))));
+        assert_eq!(
+            copied_value(&statement),
+            "(((x << 48) >>a 48) & 4294967295)"
+        );
+    }
+
+    #[test]
+    fn zero_extend_wider_source() {
+        init();
+
+        let statement = call("y", "ZeroExtend", vec![ident("x"), int(16)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(32))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.message == "cannot zero extend 32 bit bitvector to 16 bits"));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Unknown)));
+    }
+
+    #[test]
+    fn sign_extend_wider_source() {
+        init();
+
+        let statement = call("y", "SignExtend", vec![ident("x"), int(64)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(100))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.message == "cannot sign extend 100 bit bitvector to 64 bits"));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Unknown)));
+    }
+
+    #[test]
+    fn parallel() {
+        init();
+
+        let functions = || {
+            (0..8).map(|i| {
+                function(
+                    &format!("f{i}"),
+                    vec![
+                        declare("x", bits(Size::Unknown)),
+                        declare("y", bits(Size::Unknown)),
+                        declare("z", bits(Size::Unknown)),
+                        call("x", "Zeros", vec![int(i + 1)]),
+                        copy("y", ident("x")),
+                        call("z", "bitvector_concat", vec![ident("x"), ident("y")]),
+                    ],
+                )
+            })
+        };
+
+        let sequential = ast(functions());
+        let parallel = ast(functions());
+
+        assert!(ResolveBitvectors::default().run(sequential.clone()));
+        assert!(ResolveBitvectors::default()
+            .with_parallel(true)
+            .run(parallel.clone()));
+
+        // block labels differ between the two ASTs, so compare statements
+        let render = |ast: &Shared<Ast>| {
+            (0..8)
+                .flat_map(|i| {
+                    ast.get().functions[&format!("f{i}").into()]
+                        .entry_block
+                        .statements()
+                })
+                .map(|statement| statement.get().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(render(&sequential), render(&parallel));
+        assert!(matches!(
+            size_of(&parallel, "f7", "z"),
+            Some(Size::Static(16))
+        ));
+    }
+
+    #[test]
+    fn mask_widths() {
+        assert_eq!(*mask(0), BigInt::from(0));
+        assert_eq!(*mask(1), BigInt::from(1));
+        assert_eq!(*mask(63), BigInt::from(i64::MAX));
+        assert_eq!(*mask(64), BigInt::from(u64::MAX));
+        assert_eq!(*mask(72), BigInt::from(u128::MAX >> 56));
+    }
+
+    #[test]
+    fn masks_cached() {
+        init();
+
+        let (first, second) = (
+            call("y", "not_vec", vec![ident("x")]),
+            call("z", "not_vec", vec![ident("x")]),
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                declare("z", bits(Size::Unknown)),
+                first.clone(),
+                second.clone(),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        // gets the mask literal of a copy of `(~x & mask)`
+        let mask_of = |statement: &Shared<Statement>| {
+            let Statement::Copy { value, .. } = &*statement.get() else {
+                panic!("statement is not a copy");
+            };
+            let Value::Operation(Operation::And(_, mask)) = &*value.get() else {
+                panic!("value is not masked");
+            };
+            let Value::Literal(literal) = &*mask.get() else {
+                panic!("mask is not a literal");
+            };
+            literal.clone()
+        };
+
+        assert_eq!(copied_value(&first), "(~x & 255)");
+        assert!(Shared::ptr_eq(&mask_of(&first), &mask_of(&second)));
+
+        // wider masks are interned when first used
+        assert!(std::ptr::eq(mask(72), mask(72)));
+    }
+
+    #[test]
+    fn unsigned() {
+        init();
+
+        let statement = call("y", "sail_unsigned", vec![ident("x")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+        assert!(pass.take_diagnostics().is_empty());
+
+        assert_eq!(copied_value(&statement), "x as i64");
+        let typ = ast.get().functions[&"f".into()].get_ident_type("y".into());
+        assert!(matches!(
+            typ,
+            Some(Type::Integer {
+                size: Size::Static(64),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn signed() {
+        init();
+
+        let statement = call("y", "sail_signed", vec![ident("x")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+        assert_eq!(copied_value(&statement), "((x ^ 128) - 128)");
+
+        // substitute `x = 0x80` and fold
+        if let Statement::Copy { value, .. } = &*statement.get() {
+            if let Value::Operation(Operation::Subtract(xor, _)) = &*value.get() {
+                if let Value::Operation(Operation::Xor(x, _)) = &*xor.get() {
+                    *x.get_mut() = int(0x80).get().clone();
+                }
+            }
+        }
+        FoldConstants::default().run(ast);
+
+        // the backing value of -128
+        assert_eq!(copied_value(&statement), "18446744073709551488");
+    }
+
+    #[test]
+    fn signed_unknown_source() {
+        init();
+
+        let statement = call("y", "sail_signed", vec![ident("x")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(!ResolveBitvectors::default().run(ast));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn sign_extend_unknown_source() {
+        init();
+
+        let statement = call("y", "SignExtend", vec![ident("x"), int(32)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(!ResolveBitvectors::default().run(ast.clone()));
+
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Unknown)));
+    }
+
+    #[test]
+    fn custom_handler() {
+        init();
+
+        /// Stand-in for a model-specific lowering, copies the operand through
+        fn handler(
+            pass: &mut ResolveBitvectors,
+            statement: &Shared<Statement>,
+            expression: &Expression,
+            arguments: &[Shared<Value>],
+        ) {
+            let Some(destination) = pass.destination_ident(expression) else {
+                return;
+            };
+
+            pass.set_size(destination, Size::Static(64));
+            pass.rewrite(statement, expression, arguments[0].clone());
+        }
+
+        let statement = call("y", "riscv_sign_extend", vec![ident("x")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(12))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(!ResolveBitvectors::default().run(ast.clone()));
+
+        let mut pass = ResolveBitvectors::default();
+        pass.register_handler("riscv_sign_extend".into(), handler);
+        assert!(pass.run(ast.clone()));
+
+        assert_eq!(copied_value(&statement), "x");
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(64))));
+    }
+
+    /// Program with a register file of 32 64-bit registers
+    fn register_file(statements: Vec<Shared<Statement>>) -> Shared<Ast> {
+        let ast = ast([function("f", statements)]);
+        ast.get_mut().registers.insert(
+            "R".into(),
+            (
+                Shared::new(Type::FixedVector {
+                    length: 32,
+                    element_type: bits(Size::Static(64)),
+                }),
+                ControlFlowBlock::new(),
+            ),
+        );
+        ast
+    }
+
+    #[test]
+    fn vector_access() {
+        init();
+
+        let statement = call(
+            "x",
+            "plain_vector_access<RBits>",
+            vec![ident("R"), ident("n")],
+        );
+        let ast = register_file(vec![declare("x", bits(Size::Unknown)), statement.clone()]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+        assert!(pass.take_diagnostics().is_empty());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(64))));
+        assert!(matches!(&*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn vector_update() {
+        init();
+
+        let ast = register_file(vec![
+            declare("v", bits(Size::Unknown)),
+            declare("w", bits(Size::Static(32))),
+            call(
+                "R",
+                "plain_vector_update<RBits>",
+                vec![ident("R"), int(1), ident("v")],
+            ),
+            call(
+                "R",
+                "plain_vector_update<RBits>",
+                vec![ident("R"), int(2), ident("w")],
+            ),
+        ]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "f", "v"), Some(Size::Static(64))));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "cannot write 32 bits to vector of 64 bit elements"
+        );
+    }
+
+    #[test]
+    fn vector_update_field() {
+        init();
+
+        let typ = Shared::new(Type::Struct {
+            name: "pair".into(),
+            fields: vec![NamedType {
+                name: "f".into(),
+                typ: bits(Size::Unknown),
+            }],
+        });
+        let field = Shared::new(Value::Field {
+            value: ident("s"),
+            field_name: "f".into(),
+        });
+        let ast = register_file(vec![
+            declare("s", typ),
+            call(
+                "R",
+                "plain_vector_update<RBits>",
+                vec![ident("R"), int(1), field],
+            ),
+        ]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast);
+        assert!(pass.take_diagnostics().is_empty());
+    }
+
+    #[test]
+    fn resolved_function_cached() {
+        init();
+
+        let def = function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                copy("y", ident("x")),
+            ],
+        );
+        let ast = ast([def.clone()]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+        let sweeps = pass.sweeps();
+
+        // every local is static, the body is not visited again
+        assert!(!pass.run(ast.clone()));
+        assert!(!pass.run(ast.clone()));
+        assert_eq!(pass.sweeps(), sweeps);
+
+        // declaring a local with an unknown length sweeps the function again
+        let mut statements = def.entry_block.statements();
+        statements.push(declare("w", bits(Size::Unknown)));
+        def.entry_block.set_statements(statements);
+
+        assert!(!pass.run(ast.clone()));
+        assert!(pass.sweeps() > sweeps);
+    }
+
+    #[test]
+    fn changed_callee_invalidates_cache() {
+        init();
+
+        let g = function(
+            "g",
+            vec![
+                declare("a", bits(Size::Unknown)),
+                call("a", "Zeros", vec![int(4)]),
+            ],
+        );
+        let ast = ast([function("f", vec![call("b", "g", vec![])]), g.clone()]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+        pass.run(ast.clone());
+        let sweeps = pass.sweeps();
+
+        // `g` changes, taking two sweeps, and its caller `f` is swept after it
+        g.entry_block.set_statements(vec![
+            declare("a", bits(Size::Unknown)),
+            call("a", "Ones", vec![int(2)]),
+        ]);
+        assert!(pass.run(ast.clone()));
+        assert_eq!(pass.sweeps(), sweeps + 3);
+
+        // `g` changed in the previous run, so `f` is swept once more
+        let sweeps = pass.sweeps();
+        assert!(!pass.run(ast.clone()));
+        assert_eq!(pass.sweeps(), sweeps + 1);
+    }
+
+    #[test]
+    fn incremental_revisits_callers() {
+        init();
+
+        // `f` calls `g`, which calls `h`
+        let h = function("h", vec![declare("x", bits(Size::Static(8)))]);
+        let ast = ast([
+            function("f", vec![call("a", "g", vec![])]),
+            function("g", vec![call("b", "h", vec![])]),
+            h.clone(),
+        ]);
+
+        let mut pass = ResolveBitvectors::default().with_incremental(true);
+        assert!(!pass.run(ast.clone()));
+        assert!(!pass.run(ast.clone()));
+        for name in ["f", "g", "h"] {
+            assert_eq!(pass.visits(name.into()), 1);
+        }
+
+        // `h` changes, so its caller `g` is revisited, but `g` does not
+        let mut statements = h.entry_block.statements();
+        statements.extend([declare("y", bits(Size::Unknown)), copy("y", ident("x"))]);
+        h.entry_block.set_statements(statements);
+
+        assert!(pass.run(ast.clone()));
+        assert_eq!(pass.visits("h".into()), 2);
+        assert_eq!(pass.visits("g".into()), 2);
+        assert_eq!(pass.visits("f".into()), 1);
+        assert!(matches!(size_of(&ast, "h", "y"), Some(Size::Static(8))));
+    }
+
+    #[test]
+    fn resolved_sizes() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                declare("z", bits(Size::Unknown)),
+                declare("n", bits(Size::Unknown)),
+                declare("b", Shared::new(Type::Bool)),
+                copy("y", ident("x")),
+                call("z", "undefined_bitvector", vec![ident("n")]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.resolved_sizes("f".into()).is_empty());
+        pass.run(ast);
+
+        let sizes = pass.resolved_sizes("f".into());
+        let mut sizes = sizes
+            .iter()
+            .map(|(ident, size)| (ident.to_string(), size))
+            .collect::<Vec<_>>();
+        sizes.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        assert!(matches!(
+            sizes.as_slice(),
+            [
+                (n, Size::Unknown),
+                (x, Size::Static(8)),
+                (y, Size::Static(8)),
+                (z, Size::Runtime(_)),
+            ] if n == "n" && x == "x" && y == "y" && z == "z"
+        ));
+    }
+
+    #[test]
+    fn void_call() {
+        init();
+
+        /// Stand-in for a model's memory write, which stores a 64-bit word
+        fn handler(
+            pass: &mut ResolveBitvectors,
+            _: &Shared<Statement>,
+            arguments: &[Shared<Value>],
+        ) {
+            if let Some(data) = arguments[1].get().get_ident() {
+                pass.set_size(data, Size::Static(64));
+            }
+        }
+
+        let statement: Shared<Statement> = Statement::FunctionCall {
+            expression: None,
+            name: "write_mem".into(),
+            arguments: vec![ident("addr"), ident("data")],
+        }
+        .into();
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("addr", bits(Size::Static(64))),
+                declare("data", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(!ResolveBitvectors::default().run(ast.clone()));
+
+        let mut pass = ResolveBitvectors::default();
+        pass.register_void_handler("write_mem".into(), handler);
+        assert!(pass.run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "f", "data"), Some(Size::Static(64))));
+        assert!(matches!(&*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn shift_amount_range() {
+        init();
+
+        let integer = || {
+            Shared::new(Type::Integer {
+                size: Size::Static(64),
+                range: None,
+            })
+        };
+
+        // `n` is proven to be 0..7, `m` 8..15, and `i` is unbounded
+        let within = call("y", "shiftl", vec![ident("x"), ident("n")]);
+        let beyond = call("z", "shiftl", vec![ident("x"), ident("m")]);
+        let unbounded = call("w", "shiftl", vec![ident("x"), ident("i")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                declare("z", bits(Size::Unknown)),
+                declare("w", bits(Size::Unknown)),
+                declare("i", integer()),
+                declare("n", integer()),
+                declare("m", integer()),
+                call("i", "g", vec![]),
+                copy("n", Operation::And(ident("i"), int(7)).into()),
+                copy("m", Operation::Add(ident("n"), int(8)).into()),
+                within.clone(),
+                beyond.clone(),
+                unbounded.clone(),
+            ],
+        )]);
+
+        assert!(RangeAnalysis.run(ast.clone()));
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        // the shift stays within the 8 bits, so needs only the mask
+        assert_eq!(copied_value(&within), "((x << n) & 255)");
+        assert_eq!(
+            copied_value(&unbounded),
+            "(((x << (i & 63)) & 255) * (i < 8) as i64)"
+        );
+        assert_eq!(copied_value(&beyond), "0");
+        assert!(matches!(size_of(&ast, "f", "z"), Some(Size::Static(8))));
+    }
+
+    #[test]
+    fn dry_run() {
+        init();
+
+        let program = || {
+            ast([function(
+                "f",
+                vec![
+                    declare("x", bits(Size::Static(8))),
+                    declare("y", bits(Size::Unknown)),
+                    declare("z", bits(Size::Unknown)),
+                    copy("y", ident("x")),
+                    call("z", "Zeros", vec![int(4)]),
+                ],
+            )])
+        };
+        let rendered = |ast: &Shared<Ast>| {
+            ast.get().functions[&"f".into()]
+                .entry_block
+                .statements()
+                .iter()
+                .map(|statement| statement.get().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let dry = program();
+        let before = rendered(&dry);
+        let mut pass = ResolveBitvectors::default().with_dry_run(true);
+        assert!(!pass.run(dry.clone()));
+        assert_eq!(rendered(&dry), before);
+
+        let mut report = pass
+            .take_diagnostics()
+            .into_iter()
+            .map(|diagnostic| {
+                assert_eq!(diagnostic.severity, Severity::Info);
+                let statement = diagnostic.statement.unwrap().get().to_string();
+                (statement, diagnostic.message)
+            })
+            .collect::<Vec<_>>();
+        report.sort();
+
+        // the report matches what an actual run changes
+        let actual = program();
+        assert!(ResolveBitvectors::default().run(actual.clone()));
+        let mut changes = before
+            .iter()
+            .zip(rendered(&actual))
+            .filter(|(before, after)| before != &after)
+            .map(|(before, after)| (before.clone(), format!("would rewrite to `{after}`")))
+            .collect::<Vec<_>>();
+        changes.sort();
+
+        assert_eq!(report.len(), 3);
+        assert_eq!(report, changes);
+    }
+
+    #[test]
+    fn undefined_constant_length() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                call("x", "undefined_bitvector", vec![int(8)]),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(8))));
+    }
+
+    #[test]
+    fn undefined_computed_length() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("x", bits(Size::Unknown)),
+                copy("n", Operation::Add(int(4), int(4)).into()),
+                call("x", "undefined_bitvector", vec![ident("n")]),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(8))));
+    }
+
+    #[test]
+    fn concat_three() {
+        init();
+
+        let statement = call(
+            "d",
+            "bitvector_concat",
+            vec![ident("a"), ident("b"), ident("c")],
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(8))),
+                declare("c", bits(Size::Static(8))),
+                declare("d", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "f", "d"), Some(Size::Static(24))));
+        assert_eq!(copied_value(&statement), "(((a << 16) | (b << 8)) | c)");
+    }
+
+    #[test]
+    fn current_function_shared() {
+        init();
+
+        let statement = call("z", "Zeros", vec![int(8)]);
+        let def = function(
+            "f",
+            vec![declare("z", bits(Size::Unknown)), statement.clone()],
+        );
+        let parameters = def.signature.parameters.clone();
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast([def]));
+
+        // the blocks resolved are those of the AST, not copies
+        let current = pass.current_func.as_ref().unwrap();
+        assert!(Shared::ptr_eq(&current.signature.parameters, &parameters));
+        assert!(current
+            .entry_block
+            .statements()
+            .iter()
+            .any(|s| Shared::ptr_eq(s, &statement)));
+    }
+
+    #[test]
+    fn eq_wildcard_pattern() {
+        init();
+
+        let (matched, mismatched) = (
+            call("a", "eq_vec", vec![ident("op"), bits_literal("1x0x")]),
+            call("b", "neq_vec", vec![bits_literal("1x0x"), ident("op")]),
+        );
+        let ast = ast([function(
+            "decode",
+            vec![
+                declare("op", bits(Size::Static(4))),
+                declare("a", Shared::new(Type::Bool)),
+                declare("b", Shared::new(Type::Bool)),
+                matched.clone(),
+                mismatched.clone(),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        assert_eq!(copied_value(&matched), "((op & 10) == 8)");
+        assert_eq!(copied_value(&mismatched), "((op & 10) != 8)");
+    }
+
+    #[test]
+    fn length_from_return_type() {
+        init();
+
+        let callee = function("g", vec![]);
+        *callee.signature.return_type.get_mut() = bits(Size::Static(12)).get().clone();
+        let ast = ast([
+            callee,
+            function(
+                "f",
+                vec![
+                    declare("x", bits(Size::Unknown)),
+                    declare("y", bits(Size::Unknown)),
+                    call("x", "g", vec![]),
+                    copy("y", ident("x")),
+                ],
+            ),
+        ]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+        assert!(pass.take_diagnostics().is_empty());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(12))));
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(12))));
+    }
+
+    #[test]
+    fn sizes_across_branches() {
+        init();
+
+        // entry branches on `y == x` to left and right, which both jump to join
+        let def = function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                declare("a", bits(Size::Unknown)),
+                declare("b", bits(Size::Unknown)),
+                declare("d", bits(Size::Unknown)),
+                call("x", "Zeros", vec![int(8)]),
+            ],
+        );
+        let (left, right, join) = (
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+        );
+        left.set_statements(vec![call(
+            "a",
+            "bitvector_concat",
+            vec![ident("x"), ident("y")],
+        )]);
+        right.set_statements(vec![call(
+            "b",
+            "bitvector_concat",
+            vec![ident("y"), ident("x")],
+        )]);
+        join.set_statements(vec![call(
+            "d",
+            "bitvector_concat",
+            vec![ident("a"), ident("b")],
+        )]);
+        for block in [&left, &right] {
+            block.set_terminator(Terminator::Unconditional {
+                target: join.clone(),
+            });
+        }
+        def.entry_block.set_terminator(Terminator::Conditional {
+            condition: Value::Operation(Operation::Equal(ident("y"), ident("x"))),
+            target: left,
+            fallthrough: right,
+        });
+
+        let ast = ast([def]);
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+        assert!(pass.take_diagnostics().is_empty());
+
+        for (name, length) in [("y", 8), ("a", 16), ("b", 16), ("d", 32)] {
+            assert!(
+                matches!(size_of(&ast, "f", name), Some(Size::Static(l)) if l == length),
+                "{name}"
+            );
+        }
+
+        // resolved in a single sweep and confirmed by a second
+        assert_eq!(pass.sweeps, 2);
+    }
+
+    #[test]
+    fn concat_literal() {
+        init();
+
+        let statement = call(
+            "pos",
+            "bitvector_concat",
+            vec![ident("hw"), bits_literal("0000")],
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("hw", bits(Size::Static(2))),
+                declare("pos", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "f", "pos"), Some(Size::Static(6))));
+        assert_eq!(
+            copied_value(&statement),
+            format!("((hw << 4) | {})", render(bits_literal("0000")))
+        );
+    }
+
+    #[test]
+    fn concat_wide() {
+        init();
+
+        let statement = call("z", "bitvector_concat", vec![ident("x"), ident("y")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(48))),
+                declare("y", bits(Size::Static(48))),
+                declare("z", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+        assert!(matches!(size_of(&ast, "f", "z"), Some(Size::Static(96))));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+
+        // width already recorded, so a second run must not report a change
+        assert!(!ResolveBitvectors::default().run(ast.clone()));
+    }
+
+    #[test]
+    fn concat_unresolved_operands() {
+        init();
+
+        let statement = call("z", "bitvector_concat", vec![ident("x"), ident("y")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                declare("z", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(!pass.run(ast.clone()));
+
+        // both operands are reported in a single run
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| d.ident.unwrap().to_string())
+                .collect::<Vec<_>>(),
+            ["x", "y"]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|d| { d.function.unwrap().as_ref() == "f" && d.statement.is_some() }));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn concat_zero_length_operand() {
+        init();
+
+        let statement = call(
+            "d",
+            "bitvector_concat",
+            vec![ident("a"), ident("empty"), ident("b")],
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("empty", bits(Size::Static(0))),
+                declare("b", bits(Size::Static(8))),
+                declare("d", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "f", "d"), Some(Size::Static(16))));
+        assert_eq!(copied_value(&statement), "((a << 8) | b)");
+    }
+
+    #[test]
+    fn zeros_negative_length() {
+        init();
+
+        let statement = call("z", "Zeros", vec![int(-4)]);
+        let ast = ast([function(
+            "f",
+            vec![declare("z", bits(Size::Unknown)), statement.clone()],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| d.message.as_str())
+                .collect::<Vec<_>>(),
+            ["negative bitvector length -4"]
+        );
+        assert!(matches!(size_of(&ast, "f", "z"), Some(Size::Unknown)));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn copy_chain() {
+        init();
+
+        // sizes flow backwards through the statement order, so a single sweep
+        // only resolves `a`
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Unknown)),
+                declare("b", bits(Size::Unknown)),
+                declare("c", bits(Size::Unknown)),
+                copy("c", ident("b")),
+                copy("b", ident("a")),
+                call("a", "Zeros", vec![int(8)]),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        for name in ["a", "b", "c"] {
+            assert!(matches!(size_of(&ast, "f", name), Some(Size::Static(8))));
+        }
+    }
+
+    #[test]
+    fn copy_chain_iteration_limit() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Unknown)),
+                declare("b", bits(Size::Unknown)),
+                copy("b", ident("a")),
+                call("a", "Zeros", vec![int(8)]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::with_max_iterations(1);
+        assert!(pass.run(ast.clone()));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("fixed point"));
+        assert!(matches!(size_of(&ast, "f", "b"), Some(Size::Unknown)));
+    }
+
+    #[test]
+    fn oscillating_size_reported() {
+        init();
+
+        // alternates the length of its destination between 8 and 16 bits
+        fn flip(
+            pass: &mut ResolveBitvectors,
+            _: &Shared<Statement>,
+            expression: &Expression,
+            _: &[Shared<Value>],
+        ) {
+            let Some(ident) = pass.destination_ident(expression) else {
+                return;
+            };
+            let length = match pass.get_size(ident) {
+                Some(Size::Static(8)) => 16,
+                _ => 8,
+            };
+            pass.set_size(ident, Size::Static(length));
+        }
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                call("y", "Zeros", vec![int(4)]),
+                call("x", "flip", vec![]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::with_max_iterations(4);
+        pass.register_handler("flip".into(), flip);
+        assert!(pass.run(ast));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].function, Some("f".into()));
+        assert_eq!(diagnostics[0].ident, Some("x".into()));
+        assert_eq!(
+            diagnostics[0].message,
+            "did not reach a fixed point after 4 iterations, sizes of x still changing"
+        );
+    }
+
+    #[test]
+    fn set_size_of_bool() {
+        init();
+
+        // sets the length of its destination regardless of its type
+        fn stray(
+            pass: &mut ResolveBitvectors,
+            _: &Shared<Statement>,
+            expression: &Expression,
+            _: &[Shared<Value>],
+        ) {
+            if let Some(ident) = pass.destination_ident(expression) {
+                pass.set_size(ident, Size::Static(8));
+            }
+        }
+
+        let flag = Shared::new(Type::Bool);
+        let ast = ast([function(
+            "f",
+            vec![declare("b", flag.clone()), call("b", "stray", vec![])],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.register_handler("stray".into(), stray);
+        assert!(!pass.run(ast));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Info);
+        assert_eq!(diagnostics[0].ident, Some("b".into()));
+        assert!(matches!(*flag.get(), Type::Bool));
+        assert!(pass.resolved_sizes("f".into()).is_empty());
+    }
+
+    #[test]
+    fn wide_literal() {
+        init();
+
+        let digits = format!("{:08b}{:064b}", 0xab, 0x0123_4567_89ab_cdef_u64);
+        let statement = copy("x", bits_literal(&digits));
+        let ast = ast([function(
+            "f",
+            vec![declare("x", bits(Size::Unknown)), statement.clone()],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        let expected: BigInt = BigInt::from(0xab) << 64 | BigInt::from(0x0123_4567_89ab_cdef_u64);
+        assert_eq!(copied_value(&statement), expected.to_string());
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(72))));
+    }
+
+    #[test]
+    fn struct_field_widths() {
+        init();
+
+        let typ = Shared::new(Type::Struct {
+            name: "pair".into(),
+            fields: vec![
+                NamedType {
+                    name: "f".into(),
+                    typ: bits(Size::Static(8)),
+                },
+                NamedType {
+                    name: "g".into(),
+                    typ: Shared::new(Type::Bool),
+                },
+            ],
+        });
+        let field = || {
+            Shared::new(Value::Field {
+                value: ident("s"),
+                field_name: "f".into(),
+            })
+        };
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("s", typ),
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                copy("x", field()),
+                Statement::Copy {
+                    expression: Expression::Field {
+                        expression: Box::new(Expression::Identifier("s".into())),
+                        field: "f".into(),
+                    },
+                    value: ident("y"),
+                }
+                .into(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+        assert!(pass.take_diagnostics().is_empty());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(8))));
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(8))));
+    }
+
+    #[test]
+    fn eq_matching_lengths() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Static(8))),
+                declare("z", Shared::new(Type::Bool)),
+                call("z", "eq_vec", vec![ident("x"), ident("y")]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+
+        assert!(pass.take_diagnostics().is_empty());
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(8))));
+    }
+
+    #[test]
+    fn eq_masks_dirty_operand() {
+        init();
+
+        let statement = call("z", "eq_vec", vec![ident("x"), ident("b")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(8))),
+                declare("x", bits(Size::Static(8))),
+                declare("z", Shared::new(Type::Bool)),
+                copy("x", Operation::Add(ident("a"), ident("b")).into()),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast);
+
+        assert_eq!(copied_value(&statement), "((x & 255) == b)");
+    }
+
+    #[test]
+    fn and_of_comparisons() {
+        init();
+
+        let (and, or) = (
+            call("r", "and_bool", vec![ident("p"), ident("q")]),
+            call("s", "or_bool", vec![ident("p"), ident("q")]),
+        );
+        let flag = || Shared::new(Type::Bool);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(8))),
+                declare("p", flag()),
+                declare("q", flag()),
+                declare("r", flag()),
+                declare("s", flag()),
+                call("p", "eq_vec", vec![ident("a"), ident("b")]),
+                call("q", "eq_vec", vec![ident("b"), ident("a")]),
+                and.clone(),
+                or.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast);
+
+        assert_eq!(copied_value(&and), "(p && q)");
+        assert_eq!(copied_value(&or), "(p || q)");
+    }
+
+    #[test]
+    fn neq_vec() {
+        init();
+
+        let statement = call("z", "neq_vec", vec![ident("x"), ident("y")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                declare("z", Shared::new(Type::Bool)),
+                statement.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+
+        assert!(pass.take_diagnostics().is_empty());
+        assert_eq!(copied_value(&statement), "(x != y)");
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(8))));
+    }
+
+    #[test]
+    fn neq_vec_constant() {
+        init();
+
+        let statement = call("z", "neq_vec", vec![int(3), int(3)]);
+        let ast = ast([function(
+            "f",
+            vec![declare("z", Shared::new(Type::Bool)), statement.clone()],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+        assert!(FoldConstants::default().run(ast));
+
+        assert_eq!(copied_value(&statement), "0");
+    }
+
+    #[test]
+    fn unsigned_comparison() {
+        init();
+
+        let statement = call("z", "gt_vec", vec![int(0xff), int(1)]);
+        let ast = ast([function(
+            "f",
+            vec![declare("z", bits(Size::Unknown)), statement.clone()],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+        assert_eq!(copied_value(&statement), "(255 > 1)");
+        assert!(matches!(size_of(&ast, "f", "z"), Some(Size::Static(1))));
+
+        FoldConstants::default().run(ast);
+        assert_eq!(copied_value(&statement), "1");
+    }
+
+    #[test]
+    fn signed_comparison() {
+        init();
+
+        let statement = call("z", "slt_vec", vec![ident("x"), int(0)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("z", Shared::new(Type::Bool)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+        assert_eq!(
+            copied_value(&statement),
+            "(((x ^ 128) - 128) < ((0 ^ 128) - 128))"
+        );
+
+        // substitute `x = 0xff`, which is -1 as a signed integer
+        if let Statement::Copy { value, .. } = &*statement.get() {
+            if let Value::Operation(Operation::LessThan(lhs, _)) = &*value.get() {
+                if let Value::Operation(Operation::Subtract(xor, _)) = &*lhs.get() {
+                    if let Value::Operation(Operation::Xor(x, _)) = &*xor.get() {
+                        *x.get_mut() = int(0xff).get().clone();
+                    }
+                }
+            }
+        }
+        FoldConstants::default().run(ast);
+
+        // both are sign extended, but the backing value of -1 has the top bit set
+        // so is only compared by the generated code
+        assert_eq!(copied_value(&statement), "(18446744073709551615 < 0)");
+    }
+
+    #[test]
+    fn signed_comparison_unknown_length() {
+        init();
+
+        let statement = call("z", "sgteq_vec", vec![ident("x"), ident("y")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                declare("z", Shared::new(Type::Bool)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(!ResolveBitvectors::default().run(ast));
+        assert!(matches!(&*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn eq_unknown_length() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Static(16))),
+                declare("z", Shared::new(Type::Bool)),
+                call("z", "eq_vec", vec![ident("x"), ident("y")]),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(16))));
+    }
+
+    #[test]
+    fn eq_unknown_length_resolved_later() {
+        init();
+
+        // `y` is only known once its later assignment is visited, after the
+        // comparison has already been rewritten
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                declare("z", Shared::new(Type::Bool)),
+                call("z", "eq_vec", vec![ident("x"), ident("y")]),
+                call("y", "Zeros", vec![int(4)]),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(4))));
+    }
+
+    #[test]
+    fn eq_conflicting_lengths() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Static(16))),
+                declare("z", Shared::new(Type::Bool)),
+                call("z", "eq_vec", vec![ident("x"), ident("y")]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("conflicting lengths"));
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(8))));
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(16))));
+    }
+
+    #[test]
+    fn zeros_length_in_later_block() {
+        init();
+
+        // `n` is assigned in both blocks, the use must see the assignment in its own
+        // block
+        let def = function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("x", bits(Size::Unknown)),
+                copy("n", int(4)),
+            ],
+        );
+
+        let second = ControlFlowBlock::new();
+        second.set_statements(vec![
+            copy("n", int(8)),
+            call("x", "Zeros", vec![ident("n")]),
+        ]);
+        def.entry_block
+            .set_terminator(Terminator::Unconditional { target: second });
+
+        let ast = ast([def]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(8))));
+    }
+
+    #[test]
+    fn zeros_length_assigned_from_itself() {
+        init();
+
+        // the only assignment to `n` reads `n`, so has no constant value and the
+        // length is only known at runtime
+        let ast = ast([function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("x", bits(Size::Unknown)),
+                copy("n", Operation::Subtract(ident("n"), int(1)).into()),
+                call("x", "Zeros", vec![ident("n")]),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Runtime(_))));
+    }
+
+    #[test]
+    fn value_handlers() {
+        init();
+
+        // one call to each handler built on `rewrite_to_copy`, rendered as they
+        // were lowered when each handler rewrote the call itself
+        let ast = ast([function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("z", bits(Size::Unknown)),
+                declare("o", bits(Size::Unknown)),
+                declare("r", bits(Size::Unknown)),
+                declare("c", bits(Size::Unknown)),
+                declare("e", Shared::new(Type::Bool)),
+                declare("u", bits(Size::Unknown)),
+                call("n", "g", vec![]),
+                call("z", "Zeros", vec![int(8)]),
+                call("o", "Ones", vec![int(4)]),
+                call("r", "Ones", vec![ident("n")]),
+                call("c", "bitvector_concat", vec![ident("o"), ident("z")]),
+                call("e", "eq_vec", vec![ident("c"), int(0xf00)]),
+                call("u", "undefined_bitvector", vec![int(16)]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+        assert!(pass.take_diagnostics().is_empty());
+
+        let statements = ast.get().functions[&"f".into()]
+            .entry_block
+            .statements()
+            .iter()
+            .map(|statement| statement.get().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statements[7..],
+            [
+                "n = g();",
+                "z = 0;",
+                "o = 15;",
+                "r = ((18446744073709551615 >> ((64 - n) & 63)) * (n != 0) as i64);",
+                "c = ((o << 8) | z);",
+                "e = (c == 3840);",
+                "u = 0;",
+            ]
+        );
+    }
+
+    #[test]
+    fn ones_runtime_length() {
+        init();
+
+        let (zeros, ones) = (
+            call("x", "Zeros", vec![ident("n")]),
+            call("y", "Ones", vec![ident("n")]),
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                call("n", "g", vec![]),
+                zeros.clone(),
+                ones.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+        assert!(pass.take_diagnostics().is_empty());
+
+        assert_eq!(copied_value(&zeros), "0");
+        assert_eq!(
+            copied_value(&ones),
+            "((18446744073709551615 >> ((64 - n) & 63)) * (n != 0) as i64)"
+        );
+        for local in ["x", "y"] {
+            let Some(Size::Runtime(length)) = size_of(&ast, "f", local) else {
+                panic!("{local} does not have a runtime length");
+            };
+            assert_eq!(render(length), "n");
+        }
+    }
+
+    #[test]
+    fn zeros_length_overwritten_by_call() {
+        init();
+
+        // the call overwrites the constant, so the length is only known at runtime
+        let ast = ast([function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("x", bits(Size::Unknown)),
+                copy("n", int(8)),
+                call("n", "g", vec![]),
+                call("x", "Zeros", vec![ident("n")]),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Runtime(_))));
+    }
+
+    #[test]
+    fn zeros_length_reassigned_in_loop() {
+        init();
+
+        // `n` is assigned before the loop and decremented after the call on each
+        // iteration
+        let def = function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("x", bits(Size::Unknown)),
+                copy("n", int(8)),
+            ],
+        );
+
+        let (body, exit) = (ControlFlowBlock::new(), ControlFlowBlock::new());
+        def.entry_block.set_terminator(Terminator::Unconditional {
+            target: body.clone(),
+        });
+        body.set_statements(vec![
+            call("x", "Zeros", vec![ident("n")]),
+            copy("n", Operation::Subtract(ident("n"), int(1)).into()),
+        ]);
+        body.set_terminator(Terminator::Conditional {
+            condition: Value::Identifier("c".into()),
+            target: body.clone(),
+            fallthrough: exit,
+        });
+
+        let ast = ast([def]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Runtime(_))));
+    }
+
+    #[test]
+    fn zeros_length_in_dominating_block() {
+        init();
+
+        // `n` is assigned in the guard block and again after the call, which
+        // joins two branches so has no single parent
+        let def = function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("x", bits(Size::Unknown)),
+                copy("n", int(4)),
+            ],
+        );
+
+        let (left, right, merge) = (
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+        );
+        def.entry_block.set_terminator(Terminator::Conditional {
+            condition: Value::Identifier("c".into()),
+            target: left.clone(),
+            fallthrough: right.clone(),
+        });
+        left.set_terminator(Terminator::Unconditional {
+            target: merge.clone(),
+        });
+        right.set_terminator(Terminator::Unconditional {
+            target: merge.clone(),
+        });
+        merge.set_statements(vec![
+            call("x", "Zeros", vec![ident("n")]),
+            copy("n", int(8)),
+        ]);
+
+        let ast = ast([def]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(4))));
+    }
+
+    #[test]
+    fn zeros_length_reassigned_in_branch() {
+        init();
+
+        // `n` is assigned in the guard block, but reassigned in only one of the
+        // branches joining before the call
+        let def = function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("x", bits(Size::Unknown)),
+                copy("n", int(4)),
+            ],
+        );
+
+        let (left, right, merge) = (
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+        );
+        def.entry_block.set_terminator(Terminator::Conditional {
+            condition: Value::Identifier("c".into()),
+            target: left.clone(),
+            fallthrough: right.clone(),
+        });
+        left.set_statements(vec![copy("n", int(16))]);
+        left.set_terminator(Terminator::Unconditional {
+            target: merge.clone(),
+        });
+        right.set_terminator(Terminator::Unconditional {
+            target: merge.clone(),
+        });
+        merge.set_statements(vec![call("x", "Zeros", vec![ident("n")])]);
+
+        let ast = ast([def]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Runtime(_))));
+    }
+
+    #[test]
+    fn zeros_length_reassigned_along_back_edge() {
+        init();
+
+        // the loop header dominates the latch, which reassigns `n` before
+        // jumping back to the call
+        let def = function(
+            "f",
+            vec![
+                declare(
+                    "n",
+                    Shared::new(Type::Integer {
+                        size: Size::Unknown,
+                        range: None,
+                    }),
+                ),
+                declare("x", bits(Size::Unknown)),
+                copy("n", int(4)),
+            ],
+        );
+
+        let (header, latch, exit) = (
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+            ControlFlowBlock::new(),
+        );
+        def.entry_block.set_terminator(Terminator::Unconditional {
+            target: header.clone(),
+        });
+        header.set_statements(vec![call("x", "Zeros", vec![ident("n")])]);
+        header.set_terminator(Terminator::Conditional {
+            condition: Value::Identifier("c".into()),
+            target: latch.clone(),
+            fallthrough: exit,
+        });
+        latch.set_statements(vec![copy("n", int(16))]);
+        latch.set_terminator(Terminator::Unconditional { target: header });
+
+        let ast = ast([def]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Runtime(_))));
+    }
+
+    #[test]
+    fn subrange() {
+        init();
+
+        let statement = call("y", "vector_subrange", vec![ident("x"), int(7), int(0)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(32))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(8))));
+        assert_eq!(copied_value(&statement), "((x >> 0) & 255)");
+    }
+
+    #[test]
+    fn subrange_runtime_bounds() {
+        init();
+
+        let statement = call(
+            "y",
+            "vector_subrange",
+            vec![ident("x"), ident("hi"), ident("lo")],
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(32))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Runtime(_))));
+        assert_eq!(
+            copied_value(&statement),
+            "((x >> lo) & ((18446744073709551615 >> ((64 - ((hi - lo) + 1)) & 63)) * (((hi - lo) + 1) != 0) as i64))"
+        );
+    }
+
+    #[test]
+    fn subrange_bounds_range() {
+        init();
+
+        let integer = || {
+            Shared::new(Type::Integer {
+                size: Size::Static(64),
+                range: None,
+            })
+        };
+
+        // `lo` is proven to be 0 and `hi` 3, though neither is a constant
+        let statement = call(
+            "y",
+            "vector_subrange",
+            vec![ident("x"), ident("hi"), ident("lo")],
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(32))),
+                declare("y", bits(Size::Unknown)),
+                declare("i", integer()),
+                declare("lo", integer()),
+                declare("hi", integer()),
+                call("i", "g", vec![]),
+                copy("lo", Operation::And(ident("i"), int(0)).into()),
+                copy("hi", Operation::Add(ident("lo"), int(3)).into()),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(RangeAnalysis.run(ast.clone()));
+        assert!(ResolveBitvectors::default().run(ast.clone()));
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(4))));
+        assert_eq!(copied_value(&statement), "((x >> 0) & 15)");
+    }
+
+    #[test]
+    fn get_slice_int() {
+        init();
+
+        let (constant, runtime) = (
+            call("x", "get_slice_int", vec![int(3), ident("n"), int(2)]),
+            call(
+                "y",
+                "get_slice_int",
+                vec![ident("l"), ident("n"), ident("s")],
+            ),
+        );
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                constant.clone(),
+                runtime.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(3))));
+        assert_eq!(copied_value(&constant), "((n >> 2) & 7)");
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Runtime(_))));
+        assert_eq!(
+            copied_value(&runtime),
+            "((n >> s) & ((18446744073709551615 >> ((64 - l) & 63)) * (l != 0) as i64))"
+        );
+    }
+
+    #[test]
+    fn set_slice_int() {
+        init();
+
+        let statement = call(
+            "m",
+            "set_slice_int",
+            vec![int(3), ident("n"), int(2), ident("x")],
+        );
+        let ast = ast([function(
+            "f",
+            vec![declare("x", bits(Size::Unknown)), statement.clone()],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(3))));
+        assert_eq!(
+            copied_value(&statement),
+            "((n & ~(7 << 2)) | ((x & 7) << 2))"
+        );
+    }
+
+    #[test]
+    fn set_slice_int_field() {
+        init();
+
+        let typ = Shared::new(Type::Struct {
+            name: "pair".into(),
+            fields: vec![NamedType {
+                name: "f".into(),
+                typ: bits(Size::Unknown),
+            }],
+        });
+        let field = Shared::new(Value::Field {
+            value: ident("s"),
+            field_name: "f".into(),
+        });
+        let statement = call(
+            "m",
+            "set_slice_int",
+            vec![int(3), ident("n"), int(2), field],
+        );
+        let ast = ast([function("f", vec![declare("s", typ), statement.clone()])]);
+
+        ResolveBitvectors::default().run(ast);
+
+        assert_eq!(
+            copied_value(&statement),
+            "((n & ~(7 << 2)) | ((s.f & 7) << 2))"
+        );
+    }
+
+    #[test]
+    fn bit_access() {
+        init();
+
+        let statement = call("b", "bitvector_access", vec![ident("v"), int(3)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("v", bits(Size::Static(8))),
+                declare("b", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "b"), Some(Size::Static(1))));
+        assert_eq!(copied_value(&statement), "((v >> 3) & 1)");
+    }
+
+    #[test]
+    fn bit_access_runtime_index() {
+        init();
+
+        let statement = call("b", "bitvector_access", vec![ident("v"), ident("i")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("v", bits(Size::Static(8))),
+                declare("b", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "b"), Some(Size::Static(1))));
+        assert_eq!(copied_value(&statement), "((v >> i) & 1)");
+    }
+
+    #[test]
+    fn replicate() {
+        init();
+
+        let statement = call("y", "replicate_bits", vec![ident("x"), int(4)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(4))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(16))));
+        assert_eq!(
+            copied_value(&statement),
+            "(((x | (x << 4)) | (x << 8)) | (x << 12))"
+        );
+    }
+
+    #[test]
+    fn replicate_wide() {
+        init();
+
+        let statement = call("y", "replicate_bits", vec![ident("x"), int(3)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(32))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(96))));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn concat_length_overflows() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(usize::MAX))),
+                declare("y", bits(Size::Static(2))),
+                declare("z", bits(Size::Unknown)),
+                call("z", "bitvector_concat", vec![ident("x"), ident("y")]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let errors = pass
+            .take_diagnostics()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "bitvector length overflows");
+        assert_eq!(errors[0].function, Some("f".into()));
+        assert!(errors[0].statement.is_some());
+        assert!(matches!(size_of(&ast, "f", "z"), Some(Size::Unknown)));
+    }
+
+    #[test]
+    fn replicate_length_overflows() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(4))),
+                declare("y", bits(Size::Unknown)),
+                call("y", "replicate_bits", vec![ident("x"), int(usize::MAX)]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Unknown)));
+    }
+
+    #[test]
+    fn assert_eq_vec() {
+        init();
+
+        let comparison = call("t", "eq_vec", vec![ident("a"), ident("b")]);
+        let assert: Shared<Statement> = Statement::FunctionCall {
+            expression: None,
+            name: "sail_assert".into(),
+            arguments: vec![ident("t"), string("mismatch")],
+        }
+        .into();
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Unknown)),
+                declare("t", Shared::new(Type::Bool)),
+                comparison.clone(),
+                assert.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        assert!(pass.take_diagnostics().is_empty());
+        assert!(matches!(size_of(&ast, "f", "b"), Some(Size::Static(8))));
+        assert_eq!(copied_value(&comparison), "(a == b)");
+
+        let Statement::FunctionCall {
+            name, arguments, ..
+        } = &*assert.get()
+        else {
+            panic!("assertion was not preserved");
+        };
+        assert_eq!(name.as_ref(), "sail_assert");
+        assert_eq!(render(arguments[1].clone()), "\"mismatch\"");
+    }
+
+    #[test]
+    fn assert_inline_comparison() {
+        init();
+
+        let condition = Operation::NotEqual(ident("a"), ident("b"));
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Unknown)),
+                declare("b", bits(Size::Static(16))),
+                Statement::FunctionCall {
+                    expression: None,
+                    name: "sail_assert".into(),
+                    arguments: vec![Shared::new(Value::Operation(condition)), ident("msg")],
+                }
+                .into(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "a"), Some(Size::Static(16))));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("not a string literal"));
+    }
+
+    #[test]
+    fn truncate() {
+        init();
+
+        let statement = call("y", "truncate", vec![ident("x"), int(8)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(32))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(8))));
+        assert_eq!(copied_value(&statement), "(x & 255)");
+    }
+
+    #[test]
+    fn truncate_full_width() {
+        init();
+
+        let statement = call("y", "truncate", vec![ident("x"), int(64)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(64))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(64))));
+        assert_eq!(copied_value(&statement), "x");
+    }
+
+    #[test]
+    fn not_vec() {
+        init();
+
+        let statement = call("y", "not_vec", vec![ident("x")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(4))),
+                declare("y", bits(Size::Unknown)),
+                copy("x", bits_literal("0101")),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(4))));
+        assert_eq!(copied_value(&statement), "(~x & 15)");
+    }
+
+    #[test]
+    fn not_vec_unknown_length() {
+        init();
+
+        let statement = call("y", "not_vec", vec![ident("x")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(!ResolveBitvectors::default().run(ast.clone()));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn bitwise() {
+        init();
+
+        let statements = [
+            call("r0", "and_vec", vec![ident("x"), ident("y")]),
+            call("r1", "or_vec", vec![ident("x"), ident("y")]),
+            call("r2", "xor_vec", vec![ident("x"), ident("y")]),
+        ];
+        let ast = ast([function(
+            "f",
+            [
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                declare("r0", bits(Size::Unknown)),
+                declare("r1", bits(Size::Unknown)),
+                declare("r2", bits(Size::Unknown)),
+            ]
+            .into_iter()
+            .chain(statements.iter().cloned())
+            .collect(),
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        assert!(pass.take_diagnostics().is_empty());
+        for name in ["y", "r0", "r1", "r2"] {
+            assert!(matches!(size_of(&ast, "f", name), Some(Size::Static(8))));
+        }
+        assert_eq!(
+            statements.iter().map(copied_value).collect::<Vec<_>>(),
+            ["(x & y)", "((x | y) & 255)", "((x ^ y) & 255)"]
+        );
+    }
+
+    #[test]
+    fn add_sub_bits() {
+        init();
+
+        let add = call("r0", "add_bits", vec![ident("x"), ident("y")]);
+        let sub = call("r1", "sub_bits", vec![ident("x"), ident("y")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Static(8))),
+                declare("r0", bits(Size::Unknown)),
+                declare("r1", bits(Size::Unknown)),
+                add.clone(),
+                sub.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "r0"), Some(Size::Static(8))));
+        assert!(matches!(size_of(&ast, "f", "r1"), Some(Size::Static(8))));
+        assert_eq!(copied_value(&add), "((x + y) & 255)");
+        assert_eq!(copied_value(&sub), "((x - y) & 255)");
+    }
+
+    #[test]
+    fn add_bits_full_width() {
+        init();
+
+        let statement = call("r", "add_bits", vec![ident("x"), ident("y")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(64))),
+                declare("y", bits(Size::Static(64))),
+                declare("r", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "r"), Some(Size::Static(64))));
+        assert_eq!(copied_value(&statement), "(x + y)");
+    }
+
+    #[test]
+    fn add_bits_unknown_length() {
+        init();
+
+        let statement = call("r", "add_bits", vec![ident("x"), ident("y")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Unknown)),
+                declare("y", bits(Size::Unknown)),
+                declare("r", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        assert!(!ResolveBitvectors::default().run(ast.clone()));
+        assert!(matches!(*statement.get(), Statement::FunctionCall { .. }));
+    }
+
+    #[test]
+    fn shiftl() {
+        init();
+
+        let statement = call("y", "shiftl", vec![ident("x"), int(5)]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(8))));
+        assert_eq!(copied_value(&statement), "((x << 5) & 255)");
+    }
+
+    #[test]
+    fn shiftr_runtime_amount() {
+        init();
+
+        let statement = call("y", "shiftr", vec![ident("x"), ident("n")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        ResolveBitvectors::default().run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "y"), Some(Size::Static(8))));
+        assert_eq!(
+            copied_value(&statement),
+            "((x >> (n & 63)) * (n < 8) as i64)"
+        );
+    }
+
+    #[test]
+    fn wide() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(64))),
+                declare("y", bits(Size::Static(64))),
+                declare("z", bits(Size::Unknown)),
+                call("z", "bitvector_concat", vec![ident("x"), ident("y")]),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        assert!(matches!(size_of(&ast, "f", "z"), Some(Size::Static(128))));
+        assert!(pass.is_wide("f".into(), "z".into()));
+        assert!(!pass.is_wide("f".into(), "x".into()));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].ident, Some("z".into()));
+        assert!(diagnostics[0].message.contains("128 bits"));
+    }
+
+    #[test]
+    fn concat_runtime_length() {
+        init();
+
+        let statement = call("z", "bitvector_concat", vec![ident("x"), ident("y")]);
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Runtime(ident("n")))),
+                declare("z", bits(Size::Unknown)),
+                statement.clone(),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        assert!(pass.run(ast.clone()));
+        assert!(pass.take_diagnostics().is_empty());
+
+        let Some(Size::Runtime(length)) = size_of(&ast, "f", "z") else {
+            panic!("expected runtime length");
+        };
+        assert_eq!(render(length), "(8 + n)");
+        assert_eq!(copied_value(&statement), "((x << n) | y)");
+    }
+
+    /// Resolves `x`, assigned from a local of length `first` then one of
+    /// length `second`
+    fn conflicting_copies(
+        policy: SizeConflictPolicy,
+        first: usize,
+        second: usize,
+    ) -> (Option<Size>, usize) {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(first))),
+                declare("b", bits(Size::Static(second))),
+                declare("x", bits(Size::Unknown)),
+                copy("x", ident("a")),
+                copy("x", ident("b")),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default().with_policy(policy);
+        pass.run(ast.clone());
+
+        (size_of(&ast, "f", "x"), pass.take_diagnostics().len())
+    }
+
+    #[test]
+    fn conflict_keep_existing() {
+        // a narrower source also warns
+        let (size, diagnostics) = conflicting_copies(SizeConflictPolicy::KeepExisting, 16, 8);
+        assert!(matches!(size, Some(Size::Static(16))));
+        assert_eq!(diagnostics, 1);
+
+        let (size, diagnostics) = conflicting_copies(SizeConflictPolicy::KeepExisting, 8, 16);
+        assert!(matches!(size, Some(Size::Static(8))));
+        assert_eq!(diagnostics, 1);
+    }
+
+    #[test]
+    fn conflict_warning() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(8))),
+                declare("b", bits(Size::Static(16))),
+                declare("x", bits(Size::Unknown)),
+                copy("x", ident("a")),
+                copy("x", ident("b")),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].ident, Some("x".into()));
+        assert_eq!(
+            diagnostics[0].message,
+            "conflicting lengths, x has 8 bits but is assigned b with 16, keeping 8"
+        );
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(8))));
+    }
+
+    #[test]
+    fn conflict_warning_narrower() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("a", bits(Size::Static(16))),
+                declare("b", bits(Size::Static(8))),
+                declare("x", bits(Size::Unknown)),
+                copy("x", ident("a")),
+                copy("x", ident("b")),
+            ],
+        )]);
+
+        let mut pass = ResolveBitvectors::default();
+        pass.run(ast.clone());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(
+            diagnostics[0].message,
+            "conflicting lengths, x has 16 bits but is assigned b with 8, keeping 16"
+        );
+        assert!(matches!(size_of(&ast, "f", "x"), Some(Size::Static(16))));
+    }
+
+    #[test]
+    fn conflict_prefer_longest() {
+        let (size, diagnostics) = conflicting_copies(SizeConflictPolicy::PreferLongest, 8, 16);
+        assert!(matches!(size, Some(Size::Static(16))));
+        assert_eq!(diagnostics, 0);
+    }
+
+    #[test]
+    fn conflict_prefer_shortest() {
+        let (size, diagnostics) = conflicting_copies(SizeConflictPolicy::PreferShortest, 16, 8);
+        assert!(matches!(size, Some(Size::Static(8))));
+        assert_eq!(diagnostics, 0);
+    }
+
+    #[test]
+    fn conflict_error() {
+        let (size, diagnostics) = conflicting_copies(SizeConflictPolicy::Error, 16, 8);
+        assert!(matches!(size, Some(Size::Static(16))));
+        assert_eq!(diagnostics, 1);
+    }
+}
@@ -0,0 +1,580 @@
+//! Splits bitvectors longer than 64 bits into two 64-bit halves
+//!
+//! Targets without a 128-bit integer type store a wide bitvector `x` in the
+//! locals `x_lo`, holding the low 64 bits, and `x_hi`, holding the rest.
+//! Copies, bitwise operations, additions and subtractions on `x` become
+//! operations on each half, propagating the carry (or borrow) out of the low
+//! half into the high half. Shifts by a constant amount move bits across the
+//! halves.
+//!
+//! A local is only split if every statement using it can be split, along with
+//! every other local it is combined with. Locals used by calls, conditions,
+//! returns or shifts by a runtime amount are left whole and reported with a
+//! warning, as are bitvectors longer than 128 bits.
+
+use {
+    crate::boom::{
+        bits_to_bigint,
+        control_flow::Terminator,
+        passes::{
+            resolve_bitvectors::{mask, mask_literal},
+            Diagnostic, Pass, Severity,
+        },
+        visitor::{Visitor, Walkable},
+        Ast, Expression, FunctionDefinition, Literal, Operation, Size, Statement, Type, Value,
+    },
+    common::{intern::InternedString, shared::Shared, HashMap},
+    num_bigint::BigInt,
+};
+
+/// Length of each half
+const HALF: usize = 64;
+
+/// Splits wide bitvectors into low and high halves
+#[derive(Debug, Default)]
+pub struct SplitWide {
+    current_func: Option<InternedString>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl SplitWide {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+
+    fn split_function(&mut self, def: &FunctionDefinition) -> bool {
+        self.current_func = Some(def.signature.name);
+
+        let blocks = def.entry_block.iter().collect::<Vec<_>>();
+
+        // lengths of the locals to split
+        let mut wide = blocks
+            .iter()
+            .flat_map(|block| block.statements())
+            .filter_map(|statement| match &*statement.get() {
+                Statement::TypeDeclaration { name, typ } => match typ.get().get_size() {
+                    Some(Size::Static(length)) if length > HALF && length <= 2 * HALF => {
+                        Some((*name, length))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        if wide.is_empty() {
+            return false;
+        }
+
+        let declared = wide.clone();
+
+        // leaving a local whole may make statements combining it with others
+        // unsplittable, so repeat until every remaining use can be split
+        loop {
+            let mut whole = vec![];
+
+            for block in &blocks {
+                for statement in block.statements() {
+                    if split_statement(&statement, &wide).is_none() {
+                        whole.extend(mentions(|c| statement.get().walk(c)));
+                    }
+                }
+
+                whole.extend(mentions(|c| match block.terminator() {
+                    Terminator::Return(Some(value))
+                    | Terminator::Conditional {
+                        condition: value, ..
+                    } => c.visit_value(Shared::new(value)),
+                    Terminator::Panic(values) => {
+                        values.into_iter().for_each(|value| c.visit_value(value))
+                    }
+                    Terminator::Return(None) | Terminator::Unconditional { .. } => (),
+                }));
+            }
+
+            whole.retain(|name| wide.contains_key(name));
+            if whole.is_empty() {
+                break;
+            }
+            whole.iter().for_each(|name| {
+                wide.remove(name);
+            });
+        }
+
+        let mut whole = declared
+            .into_iter()
+            .filter(|(name, _)| !wide.contains_key(name))
+            .collect::<Vec<_>>();
+        whole.sort_by_key(|(name, _)| name.to_string());
+
+        for (name, length) in whole {
+            self.diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                pass: self.name(),
+                function: self.current_func,
+                statement: None,
+                span: None,
+                statement_id: None,
+                ident: Some(name),
+                message: format!("{name} has {length} bits but cannot be split into halves"),
+            });
+        }
+
+        if wide.is_empty() {
+            return false;
+        }
+
+        for block in &blocks {
+            let statements = block
+                .statements()
+                .into_iter()
+                .flat_map(|statement| match split_statement(&statement, &wide) {
+                    Some(Some(statements)) => statements.into_iter().map(Shared::new).collect(),
+                    _ => vec![statement],
+                })
+                .collect();
+            block.set_statements(statements);
+        }
+
+        true
+    }
+}
+
+impl Pass for SplitWide {
+    fn name(&self) -> &'static str {
+        "SplitWide"
+    }
+
+    fn reset(&mut self) {
+        self.current_func = None;
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        self.reset();
+
+        let mut functions = ast.get().functions.values().cloned().collect::<Vec<_>>();
+        functions.sort_by_key(|def| def.signature.name.to_string());
+
+        let mut did_change = false;
+        for def in &functions {
+            did_change |= self.split_function(def);
+        }
+
+        did_change
+    }
+}
+
+/// Gets the name of the local holding the low half of `name`
+fn lo(name: InternedString) -> InternedString {
+    format!("{name}_lo").into()
+}
+
+/// Gets the name of the local holding the high half of `name`
+fn hi(name: InternedString) -> InternedString {
+    format!("{name}_hi").into()
+}
+
+/// Splits a statement using the locals in `wide`
+///
+/// Returns `None` if the statement uses them in an unsupported way, and
+/// `Some(None)` if it does not need splitting.
+fn split_statement(
+    statement: &Shared<Statement>,
+    wide: &HashMap<InternedString, usize>,
+) -> Option<Option<Vec<Statement>>> {
+    match &*statement.get() {
+        Statement::TypeDeclaration { name, .. } => Some(wide.get(name).map(|length| {
+            [(lo(*name), HALF), (hi(*name), length - HALF)]
+                .into_iter()
+                .map(|(name, length)| Statement::TypeDeclaration {
+                    name,
+                    typ: Shared::new(Type::Bits {
+                        size: Size::Static(length),
+                    }),
+                })
+                .collect()
+        })),
+        Statement::Copy {
+            expression: Expression::Identifier(destination),
+            value,
+        } if wide.contains_key(destination) => {
+            split_copy(*destination, wide[destination], value, wide).map(Some)
+        }
+        statement => {
+            let uses_wide = mentions(|c| statement.walk(c))
+                .iter()
+                .any(|name| wide.contains_key(name));
+            (!uses_wide).then_some(None)
+        }
+    }
+}
+
+/// Splits a copy into the wide local `destination` of `length` bits
+fn split_copy(
+    destination: InternedString,
+    length: usize,
+    value: &Shared<Value>,
+    wide: &HashMap<InternedString, usize>,
+) -> Option<Vec<Statement>> {
+    let operand = |value: &Shared<Value>| Operand::new(value, length, wide);
+    let hi_length = length - HALF;
+
+    // the high half is written first where it reads the low halves, as the
+    // destination may also be an operand
+    let halves = match &*value.get() {
+        Value::Operation(Operation::Add(a, b)) => {
+            let (a, b) = (operand(a)?, operand(b)?);
+            let sum = op(Operation::Add(a.hi(), b.hi()));
+            vec![
+                (
+                    hi(destination),
+                    truncate(op(Operation::Add(sum, carry(&a, &b))), hi_length),
+                ),
+                (lo(destination), op(Operation::Add(a.lo(), b.lo()))),
+            ]
+        }
+        Value::Operation(Operation::Subtract(a, b)) => {
+            let (a, b) = (operand(a)?, operand(b)?);
+            let difference = op(Operation::Subtract(a.hi(), b.hi()));
+            vec![
+                (
+                    hi(destination),
+                    truncate(
+                        op(Operation::Subtract(difference, borrow(&a, &b))),
+                        hi_length,
+                    ),
+                ),
+                (lo(destination), op(Operation::Subtract(a.lo(), b.lo()))),
+            ]
+        }
+        Value::Operation(
+            operation @ (Operation::And(a, b) | Operation::Or(a, b) | Operation::Xor(a, b)),
+        ) => {
+            let (a, b) = (operand(a)?, operand(b)?);
+            let bitwise = |lhs, rhs| match operation {
+                Operation::And(..) => op(Operation::And(lhs, rhs)),
+                Operation::Or(..) => op(Operation::Or(lhs, rhs)),
+                _ => op(Operation::Xor(lhs, rhs)),
+            };
+            vec![
+                (lo(destination), bitwise(a.lo(), b.lo())),
+                (hi(destination), bitwise(a.hi(), b.hi())),
+            ]
+        }
+        Value::Operation(Operation::LeftShift(a, amount)) => {
+            let (a, amount) = (operand(a)?, shift_amount(amount)?);
+            match amount {
+                0 => vec![(lo(destination), a.lo()), (hi(destination), a.hi())],
+                1..HALF => vec![
+                    (
+                        hi(destination),
+                        truncate(
+                            op(Operation::Or(
+                                op(Operation::LeftShift(a.hi(), int(amount))),
+                                op(Operation::RightShift(a.lo(), int(HALF - amount))),
+                            )),
+                            hi_length,
+                        ),
+                    ),
+                    (
+                        lo(destination),
+                        op(Operation::LeftShift(a.lo(), int(amount))),
+                    ),
+                ],
+                _ if amount < length => vec![
+                    (
+                        hi(destination),
+                        truncate(
+                            op(Operation::LeftShift(a.lo(), int(amount - HALF))),
+                            hi_length,
+                        ),
+                    ),
+                    (lo(destination), int(0)),
+                ],
+                _ => vec![(lo(destination), int(0)), (hi(destination), int(0))],
+            }
+        }
+        Value::Operation(Operation::RightShift(a, amount)) => {
+            let (a, amount) = (operand(a)?, shift_amount(amount)?);
+            // the low half is written first, as it reads the high half
+            match amount {
+                0 => vec![(lo(destination), a.lo()), (hi(destination), a.hi())],
+                1..HALF => vec![
+                    (
+                        lo(destination),
+                        op(Operation::Or(
+                            op(Operation::RightShift(a.lo(), int(amount))),
+                            op(Operation::LeftShift(a.hi(), int(HALF - amount))),
+                        )),
+                    ),
+                    (
+                        hi(destination),
+                        op(Operation::RightShift(a.hi(), int(amount))),
+                    ),
+                ],
+                _ if amount < length => vec![
+                    (
+                        lo(destination),
+                        op(Operation::RightShift(a.hi(), int(amount - HALF))),
+                    ),
+                    (hi(destination), int(0)),
+                ],
+                _ => vec![(lo(destination), int(0)), (hi(destination), int(0))],
+            }
+        }
+        _ => {
+            let value = operand(value)?;
+            vec![(lo(destination), value.lo()), (hi(destination), value.hi())]
+        }
+    };
+
+    Some(
+        halves
+            .into_iter()
+            .map(|(destination, value)| Statement::Copy {
+                expression: Expression::Identifier(destination),
+                value,
+            })
+            .collect(),
+    )
+}
+
+/// Operand of an operation on a wide local
+enum Operand {
+    /// Local split into halves
+    Local(InternedString),
+    /// Literal masked to the length of the operation
+    Literal(BigInt),
+}
+
+impl Operand {
+    /// Gets the operand of `length` bits, if it is a literal or a local being
+    /// split with the same length
+    fn new(
+        value: &Shared<Value>,
+        length: usize,
+        wide: &HashMap<InternedString, usize>,
+    ) -> Option<Self> {
+        match &*value.get() {
+            Value::Identifier(ident) => {
+                (wide.get(ident) == Some(&length)).then_some(Self::Local(*ident))
+            }
+            Value::Literal(literal) => {
+                let value = match &*literal.get() {
+                    Literal::Int(int) => int.clone(),
+                    Literal::Bits(bits)
+                        if bits.len() == length && bits.iter().all(|bit| bit.is_fixed()) =>
+                    {
+                        bits_to_bigint(bits)
+                    }
+                    _ => return None,
+                };
+
+                Some(Self::Literal(value & mask(length)))
+            }
+            _ => None,
+        }
+    }
+
+    fn lo(&self) -> Shared<Value> {
+        match self {
+            Self::Local(ident) => Shared::new(Value::Identifier(lo(*ident))),
+            Self::Literal(value) => Literal::Int(value & mask(HALF)).into(),
+        }
+    }
+
+    fn hi(&self) -> Shared<Value> {
+        match self {
+            Self::Local(ident) => Shared::new(Value::Identifier(hi(*ident))),
+            Self::Literal(value) => Literal::Int(value >> HALF).into(),
+        }
+    }
+}
+
+/// Gets the carry out of adding the low halves of `a` and `b`, the top bit of
+/// `(a & b) | ((a | b) & ~(a + b))`
+fn carry(a: &Operand, b: &Operand) -> Shared<Value> {
+    let sum = op(Operation::Add(a.lo(), b.lo()));
+    let generated = op(Operation::And(a.lo(), b.lo()));
+    let propagated = op(Operation::And(
+        op(Operation::Or(a.lo(), b.lo())),
+        op(Operation::Complement(sum)),
+    ));
+
+    op(Operation::RightShift(
+        op(Operation::Or(generated, propagated)),
+        int(HALF - 1),
+    ))
+}
+
+/// Gets the borrow out of subtracting the low half of `b` from that of `a`,
+/// the top bit of `(~a & b) | (~(a ^ b) & (a - b))`
+fn borrow(a: &Operand, b: &Operand) -> Shared<Value> {
+    let difference = op(Operation::Subtract(a.lo(), b.lo()));
+    let generated = op(Operation::And(op(Operation::Complement(a.lo())), b.lo()));
+    let propagated = op(Operation::And(
+        op(Operation::Complement(op(Operation::Xor(a.lo(), b.lo())))),
+        difference,
+    ));
+
+    op(Operation::RightShift(
+        op(Operation::Or(generated, propagated)),
+        int(HALF - 1),
+    ))
+}
+
+/// Masks the high half of a result to its length, a full half needs no mask
+fn truncate(value: Shared<Value>, length: usize) -> Shared<Value> {
+    if length < HALF {
+        op(Operation::And(value, mask_literal(length)))
+    } else {
+        value
+    }
+}
+
+/// Gets a constant shift amount
+fn shift_amount(amount: &Shared<Value>) -> Option<usize> {
+    match &*amount.get() {
+        Value::Literal(literal) => match &*literal.get() {
+            Literal::Int(int) => int.try_into().ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn op(operation: Operation) -> Shared<Value> {
+    Shared::new(Value::Operation(operation))
+}
+
+fn int(value: usize) -> Shared<Value> {
+    Literal::Int(value.into()).into()
+}
+
+/// Gets the locals read or written by a node
+fn mentions<F: FnOnce(&mut Mentions)>(walk: F) -> Vec<InternedString> {
+    let mut mentions = Mentions(vec![]);
+    walk(&mut mentions);
+    mentions.0
+}
+
+struct Mentions(Vec<InternedString>);
+
+impl Visitor for Mentions {
+    fn visit_expression(&mut self, node: &Expression) {
+        if let Expression::Identifier(ident) = node {
+            self.0.push(*ident);
+        }
+        node.walk(self);
+    }
+
+    fn visit_value_pre(&mut self, node: Shared<Value>) {
+        if let Value::Identifier(ident) = &*node.get() {
+            self.0.push(*ident);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{split_wide::SplitWide, Pass},
+            pretty_print::print_statement,
+            test_utils::*,
+            Ast, Operation, Size, Value,
+        },
+        common::{intern::InternedString, shared::Shared},
+        pretty_assertions::assert_eq,
+    };
+
+    fn statements(ast: &Shared<Ast>) -> Vec<String> {
+        ast.get().functions[&InternedString::from("f")]
+            .entry_block
+            .statements()
+            .into_iter()
+            .map(|statement| {
+                let mut buf = vec![];
+                print_statement(&mut buf, statement);
+                String::from_utf8(buf).unwrap().trim().to_owned()
+            })
+            .collect()
+    }
+
+    fn operation(operation: Operation) -> Shared<Value> {
+        Shared::new(Value::Operation(operation))
+    }
+
+    #[test]
+    fn add_split() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(128))),
+                declare("y", bits(Size::Static(128))),
+                copy("y", int(1)),
+                copy("x", operation(Operation::Add(ident("x"), ident("y")))),
+            ],
+        )]);
+
+        let mut pass = SplitWide::default();
+        assert!(pass.run(ast.clone()));
+        assert!(pass.take_diagnostics().is_empty());
+        assert!(!pass.run(ast.clone()));
+
+        assert_eq!(
+            statements(&ast),
+            [
+                "bv64 x_lo;",
+                "bv64 x_hi;",
+                "bv64 y_lo;",
+                "bv64 y_hi;",
+                "y_lo = 1;",
+                "y_hi = 0;",
+                "x_hi = ((x_hi + y_hi) + (((x_lo & y_lo) | ((x_lo | y_lo) & ~(x_lo + y_lo))) >> 63));",
+                "x_lo = (x_lo + y_lo);",
+            ]
+        );
+    }
+
+    #[test]
+    fn unsplittable_use_left_whole() {
+        init();
+
+        let ast = ast([function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(96))),
+                declare("y", bits(Size::Static(96))),
+                declare("z", bits(Size::Static(96))),
+                copy("y", operation(Operation::LeftShift(ident("x"), int(40)))),
+                call("x", "g", vec![]),
+                copy("z", operation(Operation::RightShift(ident("z"), int(70)))),
+            ],
+        )]);
+
+        let mut pass = SplitWide::default();
+        assert!(pass.run(ast.clone()));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| d.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "x has 96 bits but cannot be split into halves",
+                "y has 96 bits but cannot be split into halves"
+            ]
+        );
+
+        assert_eq!(statements(&ast)[6..], ["z_lo = (z_hi >> 6);", "z_hi = 0;"]);
+    }
+}
@@ -0,0 +1,166 @@
+//! Checks that every function call has a target
+//!
+//! Passes that rename, specialize or merge functions must redirect the calls to
+//! them, a call left pointing at a removed function otherwise only fails in
+//! codegen. A call may target a function in the AST, a union constructor or a
+//! builtin with a default handler in `ResolveBitvectors`. Builtins lowered
+//! elsewhere are added with `with_builtins`.
+
+use {
+    crate::boom::{
+        passes::{resolve_bitvectors::has_default_handler, Diagnostic, Pass, Severity},
+        visitor::{Visitor, Walkable},
+        Ast, Definition, FunctionDefinition, Statement,
+    },
+    common::{intern::InternedString, shared::Shared, HashSet},
+};
+
+/// Reports calls to undefined functions, never changes the AST
+#[derive(Debug, Default)]
+pub struct VerifyCalls {
+    /// Builtins without a default handler that calls may target
+    builtins: HashSet<InternedString>,
+    /// Functions and union constructors of the AST
+    defined: HashSet<InternedString>,
+    current_func: Option<InternedString>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl VerifyCalls {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+
+    /// Sets additional builtins that calls may target
+    pub fn with_builtins<I: IntoIterator<Item = InternedString>>(mut self, builtins: I) -> Self {
+        self.builtins = builtins.into_iter().collect();
+        self
+    }
+
+    fn is_target(&self, name: InternedString) -> bool {
+        self.defined.contains(&name) || self.builtins.contains(&name) || has_default_handler(name)
+    }
+}
+
+impl Pass for VerifyCalls {
+    fn name(&self) -> &'static str {
+        "VerifyCalls"
+    }
+
+    fn reset(&mut self) {
+        self.current_func = None;
+        self.defined.clear();
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn configuration(&self) -> String {
+        let mut builtins = self
+            .builtins
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        builtins.sort();
+
+        format!("builtins={builtins:?}")
+    }
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        self.reset();
+
+        let ast = ast.get();
+
+        let constructors = ast
+            .definitions
+            .iter()
+            .flat_map(|definition| match definition {
+                Definition::Union { fields, .. } => fields.iter().map(|field| field.name).collect(),
+                _ => vec![],
+            });
+        self.defined = ast.functions.keys().copied().chain(constructors).collect();
+
+        let mut functions = ast.functions.values().collect::<Vec<_>>();
+        functions.sort_by_key(|def| def.signature.name.to_string());
+
+        functions
+            .into_iter()
+            .for_each(|def| self.visit_function_definition(def));
+
+        false
+    }
+}
+
+impl Visitor for VerifyCalls {
+    fn visit_function_definition(&mut self, node: &FunctionDefinition) {
+        self.current_func = Some(node.signature.name);
+        node.walk(self);
+    }
+
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        if let Statement::FunctionCall { name, .. } = &*node.get() {
+            if !self.is_target(*name) {
+                self.diagnostics.push(Diagnostic {
+                    severity: Severity::Error,
+                    pass: self.name(),
+                    function: self.current_func,
+                    statement: Some(node.clone()),
+                    span: None,
+                    statement_id: None,
+                    ident: None,
+                    message: format!("call to undefined function {name}"),
+                });
+            }
+        }
+
+        node.get().walk(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{verify_calls::VerifyCalls, Pass},
+            test_utils::*,
+        },
+        common::shared::Shared,
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn dangling_call() {
+        init();
+
+        let dangling = call("y", "removed", vec![]);
+        let ast = ast([
+            function("g", vec![]),
+            function(
+                "f",
+                vec![
+                    call("x", "g", vec![]),
+                    call("z", "Zeros", vec![int(8)]),
+                    dangling.clone(),
+                ],
+            ),
+        ]);
+
+        let mut pass = VerifyCalls::default();
+        assert!(!pass.run(ast.clone()));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].function, Some("f".into()));
+        assert_eq!(diagnostics[0].message, "call to undefined function removed");
+        assert!(Shared::ptr_eq(
+            diagnostics[0].statement.as_ref().unwrap(),
+            &dangling
+        ));
+
+        let mut pass = VerifyCalls::default().with_builtins(["removed".into()]);
+        pass.run(ast);
+        assert!(pass.take_diagnostics().is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+//! Checks that the length of every bitvector was resolved
+//!
+//! Run after the resolving passes and before codegen, which cannot lower
+//! bitvectors of unknown length. Every unresolved parameter or local is
+//! reported rather than stopping at the first.
+
+use {
+    crate::boom::{
+        passes::{Diagnostic, Pass, Severity},
+        visitor::{Visitor, Walkable},
+        Ast, FunctionDefinition, FunctionSignature, Parameter, Size, Statement, Type,
+    },
+    common::{intern::InternedString, shared::Shared},
+};
+
+/// Reports bitvectors of unknown length, never changes the AST
+#[derive(Debug, Default)]
+pub struct VerifySizes {
+    current_func: Option<InternedString>,
+    diagnostics: Vec<Diagnostic>,
+    unresolved: usize,
+}
+
+impl VerifySizes {
+    /// Create a new Pass object
+    pub fn new_boxed() -> Box<dyn Pass> {
+        Box::<Self>::default()
+    }
+
+    /// Whether every bitvector had a resolved length in the last run
+    pub fn is_codegen_ready(&self) -> bool {
+        self.unresolved == 0
+    }
+
+    fn check(
+        &mut self,
+        ident: InternedString,
+        typ: &Shared<Type>,
+        statement: Option<Shared<Statement>>,
+    ) {
+        if !matches!(
+            &*typ.get(),
+            Type::Bits {
+                size: Size::Unknown
+            }
+        ) {
+            return;
+        }
+
+        self.unresolved += 1;
+
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            pass: self.name(),
+            function: self.current_func,
+            statement,
+            span: None,
+            statement_id: None,
+            ident: Some(ident),
+            message: format!("length of bitvector {ident} is unresolved"),
+        });
+    }
+}
+
+impl Pass for VerifySizes {
+    fn name(&self) -> &'static str {
+        "VerifySizes"
+    }
+
+    fn reset(&mut self) {
+        self.current_func = None;
+        self.unresolved = 0;
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn run(&mut self, ast: Shared<Ast>) -> bool {
+        self.reset();
+
+        let mut functions = ast.get().functions.values().cloned().collect::<Vec<_>>();
+        functions.sort_by_key(|def| def.signature.name.to_string());
+
+        functions
+            .iter()
+            .for_each(|def| self.visit_function_definition(def));
+
+        false
+    }
+}
+
+impl Visitor for VerifySizes {
+    fn visit_function_definition(&mut self, node: &FunctionDefinition) {
+        self.current_func = Some(node.signature.name);
+        node.walk(self);
+    }
+
+    fn visit_function_signature(&mut self, node: &FunctionSignature) {
+        node.parameters
+            .get()
+            .iter()
+            .for_each(|Parameter { name, typ, .. }| self.check(*name, typ, None));
+    }
+
+    fn visit_statement(&mut self, node: Shared<Statement>) {
+        if let Statement::TypeDeclaration { name, typ } = &*node.get() {
+            self.check(*name, typ, Some(node.clone()));
+        }
+
+        node.get().walk(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            passes::{verify_sizes::VerifySizes, Pass},
+            test_utils::*,
+            Parameter, Size,
+        },
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn unresolved() {
+        init();
+
+        let def = function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                declare("y", bits(Size::Unknown)),
+            ],
+        );
+        *def.signature.parameters.get_mut() = vec![Parameter {
+            name: "p".into(),
+            typ: bits(Size::Static(4)),
+            is_ref: false,
+        }];
+        let ast = ast([def]);
+
+        let mut pass = VerifySizes::default();
+        assert!(!pass.run(ast));
+        assert!(!pass.is_codegen_ready());
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].function, Some("f".into()));
+        assert_eq!(diagnostics[0].ident, Some("y".into()));
+    }
+
+    #[test]
+    fn unresolved_parameter() {
+        init();
+
+        let def = function("f", vec![]);
+        *def.signature.parameters.get_mut() = vec![Parameter {
+            name: "p".into(),
+            typ: bits(Size::Unknown),
+            is_ref: false,
+        }];
+
+        let mut pass = VerifySizes::default();
+        pass.run(ast([def]));
+
+        let diagnostics = pass.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].ident, Some("p".into()));
+        assert!(diagnostics[0].statement.is_none());
+    }
+}
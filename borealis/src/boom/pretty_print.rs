@@ -7,8 +7,9 @@ use {
         Ast, Definition, Expression, FunctionDefinition, FunctionSignature, Literal, NamedType,
         NamedValue, Operation, Parameter, Size, Statement, Type, Value,
     },
-    common::{intern::InternedString, shared::Shared},
+    common::{identifiable::Id, intern::InternedString, shared::Shared, HashMap},
     std::{
+        fmt::{self, Display, Formatter},
         io::Write,
         rc::Rc,
         sync::atomic::{AtomicUsize, Ordering},
@@ -23,6 +24,7 @@ pub fn print_ast<W: Write>(w: &mut W, ast: Shared<Ast>) {
         definitions,
         registers,
         functions,
+        ..
     } = &*ast.get();
 
     let mut visitor = PrettyPrinter::new(w);
@@ -47,6 +49,27 @@ pub fn print_ast<W: Write>(w: &mut W, ast: Shared<Ast>) {
         .for_each(|(_, fundef)| visitor.visit_function_definition(fundef));
 }
 
+/// Pretty-print the functions of a BOOM AST in a stable order
+///
+/// Functions are sorted by name and unlabelled blocks are named by their
+/// position in the function, so dumps of the same AST are identical and dumps
+/// taken before and after a pass can be diffed.
+pub fn print_functions_stable<W: Write>(w: &mut W, ast: Shared<Ast>) {
+    let mut functions = ast.get().functions.values().cloned().collect::<Vec<_>>();
+    functions.sort_by_key(|def| def.signature.name.as_ref().to_owned());
+
+    functions
+        .iter()
+        .for_each(|def| print_function_stable(w, def));
+}
+
+/// Pretty-print a single function, naming unlabelled blocks by their position
+pub fn print_function_stable<W: Write>(w: &mut W, def: &FunctionDefinition) {
+    let mut visitor = PrettyPrinter::new(w);
+    visitor.name_blocks(def);
+    visitor.visit_function_definition(def);
+}
+
 /// Pretty-print BOOM statement
 pub fn print_statement<W: Write>(w: &mut W, statement: Shared<Statement>) {
     let mut visitor = PrettyPrinter::new(w);
@@ -62,6 +85,8 @@ pub fn print_value<W: Write>(w: &mut W, value: Shared<Value>) {
 pub struct PrettyPrinter<'writer, W> {
     indent: Rc<AtomicUsize>,
     writer: &'writer mut W,
+    /// Names of unlabelled blocks, otherwise the block ID is used
+    block_names: Option<HashMap<Id, String>>,
 }
 
 impl<'writer, W: Write> PrettyPrinter<'writer, W> {
@@ -70,6 +95,7 @@ impl<'writer, W: Write> PrettyPrinter<'writer, W> {
         Self {
             indent: Rc::new(AtomicUsize::new(0)),
             writer,
+            block_names: None,
         }
     }
 }
@@ -97,9 +123,30 @@ impl<'writer, W: Write> PrettyPrinter<'writer, W> {
         }
     }
 
+    /// Names the unlabelled blocks of a function by their position
+    fn name_blocks(&mut self, def: &FunctionDefinition) {
+        self.block_names = Some(
+            def.entry_block
+                .iter()
+                .enumerate()
+                .map(|(index, block)| (block.id(), format!("block{index}")))
+                .collect(),
+        );
+    }
+
+    fn block_name(&self, block: &ControlFlowBlock) -> String {
+        match (block.label(), &self.block_names) {
+            (None, Some(names)) => names
+                .get(&block.id())
+                .cloned()
+                .unwrap_or_else(|| block.to_string()),
+            _ => block.to_string(),
+        }
+    }
+
     fn print_control_flow_graph(&mut self, entry_block: ControlFlowBlock) {
         entry_block.iter().for_each(|b| {
-            writeln!(self.writer, "    {}:", b).unwrap();
+            writeln!(self.writer, "    {}:", self.block_name(&b)).unwrap();
             {
                 b.statements().iter().for_each(|stmt| {
                     write!(self.writer, "        ").unwrap();
@@ -122,12 +169,15 @@ impl<'writer, W: Write> PrettyPrinter<'writer, W> {
                         write!(self.writer, "        if (").unwrap();
                         self.visit_value(Shared::new(condition));
                         writeln!(self.writer, ") {{").unwrap();
+                        let (target, fallthrough) =
+                            (self.block_name(&target), self.block_name(&fallthrough));
                         writeln!(self.writer, "            goto {target};").unwrap();
                         writeln!(self.writer, "        }} else {{").unwrap();
                         writeln!(self.writer, "            goto {fallthrough};").unwrap();
                         writeln!(self.writer, "        }}").unwrap();
                     }
                     Terminator::Unconditional { target } => {
+                        let target = self.block_name(&target);
                         writeln!(self.writer, "        goto {target};").unwrap();
                     }
                     Terminator::Panic(values) => {
@@ -339,7 +389,7 @@ impl<'writer, W: Write> Visitor for PrettyPrinter<'writer, W> {
             Type::Real => write!(self.writer, "real"),
             Type::Float => write!(self.writer, "float"),
 
-            Type::Integer { size } => {
+            Type::Integer { size, .. } => {
                 write!(self.writer, "i").unwrap();
 
                 match size {
@@ -399,6 +449,17 @@ impl<'writer, W: Write> Visitor for PrettyPrinter<'writer, W> {
                 self.visit_type(typ.clone());
                 Ok(())
             }
+            Type::Tuple(elements) => {
+                write!(self.writer, "(").unwrap();
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(self.writer, ", ").unwrap();
+                    }
+                    self.visit_type(element.clone());
+                }
+                write!(self.writer, ")").unwrap();
+                Ok(())
+            }
         }
         .unwrap()
     }
@@ -464,6 +525,20 @@ impl<'writer, W: Write> Visitor for PrettyPrinter<'writer, W> {
                 write!(self.writer, " as ").unwrap();
                 write_uid(self, *identifier, types);
             }
+            Value::Tuple(values) => {
+                write!(self.writer, "(").unwrap();
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(self.writer, ", ").unwrap();
+                    }
+                    self.visit_value(value.clone());
+                }
+                write!(self.writer, ")").unwrap();
+            }
+            Value::TupleMember { value, index } => {
+                self.visit_value(value.clone());
+                write!(self.writer, ".{index}").unwrap();
+            }
         }
     }
 
@@ -494,6 +569,10 @@ impl<'writer, W: Write> Visitor for PrettyPrinter<'writer, W> {
                 write!(self.writer, "&").unwrap();
                 self.visit_expression(expression);
             }
+            Expression::TupleMember { expression, index } => {
+                self.visit_expression(expression);
+                write!(self.writer, ".{index}").unwrap();
+            }
         }
     }
 
@@ -547,8 +626,11 @@ impl<'writer, W: Write> Visitor for PrettyPrinter<'writer, W> {
             Operation::Xor(lhs, rhs) => emit_op2(self, lhs, rhs, "^"),
             Operation::And(lhs, rhs) => emit_op2(self, lhs, rhs, "&"),
             Operation::Divide(lhs, rhs) => emit_op2(self, lhs, rhs, "/"),
+            Operation::LogicalAnd(lhs, rhs) => emit_op2(self, lhs, rhs, "&&"),
+            Operation::LogicalOr(lhs, rhs) => emit_op2(self, lhs, rhs, "||"),
             Operation::LeftShift(lhs, rhs) => emit_op2(self, lhs, rhs, "<<"),
             Operation::RightShift(lhs, rhs) => emit_op2(self, lhs, rhs, ">>"),
+            Operation::ArithmeticRightShift(lhs, rhs) => emit_op2(self, lhs, rhs, ">>a"),
 
             Operation::RotateLeft(lhs, rhs) => emit_op2(self, lhs, rhs, "<<<"),
             Operation::RotateRight(lhs, rhs) => emit_op2(self, lhs, rhs, ">>>"),
@@ -561,3 +643,78 @@ impl<'writer, W: Write> Visitor for PrettyPrinter<'writer, W> {
         }
     }
 }
+
+/// Renders to a formatter using the pretty-printer
+fn display<F: FnOnce(&mut PrettyPrinter<Vec<u8>>)>(f: &mut Formatter, print: F) -> fmt::Result {
+    let mut buf = vec![];
+    print(&mut PrettyPrinter::new(&mut buf));
+    write!(f, "{}", String::from_utf8_lossy(&buf).trim_end())
+}
+
+impl Display for Ast {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut buf = vec![];
+        print_ast(&mut buf, Shared::new(self.clone()));
+        write!(f, "{}", String::from_utf8_lossy(&buf).trim_end())
+    }
+}
+
+impl Display for FunctionDefinition {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        display(f, |printer| {
+            printer.name_blocks(self);
+            printer.visit_function_definition(self);
+        })
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        display(f, |printer| {
+            printer.visit_statement(Shared::new(self.clone()))
+        })
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        display(f, |printer| printer.visit_value(Shared::new(self.clone())))
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        display(f, |printer| printer.visit_operation(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{test_utils::*, Operation, Size},
+        pretty_assertions::assert_eq,
+    };
+
+    #[test]
+    fn display_function() {
+        init();
+
+        let def = function(
+            "f",
+            vec![
+                declare("x", bits(Size::Static(8))),
+                copy("x", int(5)),
+                copy("y", Operation::Add(ident("x"), int(1)).into()),
+            ],
+        );
+
+        assert_eq!(
+            def.to_string(),
+            "fn f() -> () {\n    block0:\n        bv8 x;\n        x = 5;\n        y = (x + 1);\n        return;\n\n}"
+        );
+        assert_eq!(
+            Operation::LeftShift(ident("x"), int(2)).to_string(),
+            "(x << 2)"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! JSON report of resolved sizes for external tooling
+//!
+//! Written after the passes have run, the report maps each function to the
+//! sizes of its sized parameters and locals:
+//!
+//! ```json
+//! {
+//!   "decode_add": {
+//!     "imm": { "kind": "static", "width": 12 },
+//!     "data": { "kind": "runtime", "ident": "datasize", "value": "datasize" },
+//!     "tmp": { "kind": "unknown" }
+//!   }
+//! }
+//! ```
+//!
+//! Runtime sizes name the first identifier in their length, or `null` if the
+//! length does not contain one, along with the length as printed in BOOM.
+//! Functions and locals are sorted by name so reports can be diffed.
+
+use {
+    crate::boom::{
+        pretty_print::print_value,
+        visitor::{Visitor, Walkable},
+        Ast, FunctionDefinition, Size, Statement, Value,
+    },
+    common::{intern::InternedString, shared::Shared},
+    serde::Serialize,
+    std::{collections::BTreeMap, io::Write},
+};
+
+/// Resolved size of a single local
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum SizeEntry {
+    Static {
+        width: usize,
+    },
+    Runtime {
+        /// Identifier governing the length
+        ident: Option<String>,
+        /// Length as printed in BOOM
+        value: String,
+    },
+    Unknown,
+}
+
+impl From<&Size> for SizeEntry {
+    fn from(size: &Size) -> Self {
+        match size {
+            Size::Static(width) => Self::Static { width: *width },
+            Size::Runtime(value) => {
+                let mut idents = Identifiers::default();
+                idents.visit_value(value.clone());
+
+                let mut printed = vec![];
+                print_value(&mut printed, value.clone());
+
+                Self::Runtime {
+                    ident: idents.0.first().map(ToString::to_string),
+                    value: String::from_utf8(printed).unwrap(),
+                }
+            }
+            Size::Unknown => Self::Unknown,
+        }
+    }
+}
+
+/// Writes the resolved sizes of the locals of every function as JSON
+///
+/// The AST is not modified.
+pub fn export_size_report<W: Write>(ast: &Ast, w: W) -> serde_json::Result<()> {
+    let report = ast
+        .functions
+        .iter()
+        .map(|(name, def)| (name.to_string(), function_sizes(def)))
+        .collect::<BTreeMap<_, _>>();
+
+    serde_json::to_writer_pretty(w, &report)
+}
+
+/// Gets the sizes of the parameters and locals of a function
+fn function_sizes(def: &FunctionDefinition) -> BTreeMap<String, SizeEntry> {
+    let parameters = def
+        .signature
+        .parameters
+        .get()
+        .iter()
+        .map(|parameter| (parameter.name, parameter.typ.clone()))
+        .collect::<Vec<_>>();
+
+    let locals = def.entry_block.iter().flat_map(|block| {
+        block
+            .statements()
+            .into_iter()
+            .filter_map(|statement| match &*statement.get() {
+                Statement::TypeDeclaration { name, typ } => Some((*name, typ.clone())),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    });
+
+    parameters
+        .into_iter()
+        .chain(locals)
+        .filter_map(|(name, typ)| {
+            let size = typ.get().get_size()?;
+            Some((name.to_string(), SizeEntry::from(&size)))
+        })
+        .collect()
+}
+
+/// Collects identifiers in the order they are visited
+#[derive(Default)]
+struct Identifiers(Vec<InternedString>);
+
+impl Visitor for Identifiers {
+    fn visit_value(&mut self, node: Shared<Value>) {
+        if let Value::Identifier(ident) = &*node.get() {
+            self.0.push(*ident);
+        }
+
+        node.get().walk(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            size_report::export_size_report, test_utils::*, Operation, Parameter, Size, Type,
+        },
+        common::shared::Shared,
+        pretty_assertions::assert_eq,
+        serde_json::json,
+    };
+
+    #[test]
+    fn two_functions() {
+        init();
+
+        let decode = function(
+            "decode",
+            vec![
+                declare("imm", bits(Size::Static(12))),
+                declare(
+                    "data",
+                    bits(Size::Runtime(
+                        Operation::Multiply(ident("n"), int(8)).into(),
+                    )),
+                ),
+                declare("flag", Shared::new(Type::Bool)),
+            ],
+        );
+        *decode.signature.parameters.get_mut() = vec![Parameter {
+            name: "n".into(),
+            typ: Shared::new(Type::Integer {
+                size: Size::Static(64),
+                range: None,
+            }),
+            is_ref: false,
+        }];
+
+        let ast = ast([
+            decode,
+            function("execute", vec![declare("tmp", bits(Size::Unknown))]),
+        ]);
+
+        let mut buf = vec![];
+        export_size_report(&ast.get(), &mut buf).unwrap();
+
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&buf).unwrap(),
+            json!({
+                "decode": {
+                    "n": { "kind": "static", "width": 64 },
+                    "imm": { "kind": "static", "width": 12 },
+                    "data": { "kind": "runtime", "ident": "n", "value": "(n * 8)" },
+                },
+                "execute": {
+                    "tmp": { "kind": "unknown" },
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,455 @@
+//! Comparing and hashing BOOM values by structure
+//!
+//! Values are wrapped in `Shared` so can be mutated in place by passes, which
+//! means identical expressions built separately are distinct nodes. These
+//! traits compare the contents of nodes instead, recursing through operands
+//! and types. Identifiers are compared by their interned string.
+//!
+//! Values and types form trees, nodes shared between two trees are compared
+//! by identity first so are not traversed.
+
+use {
+    crate::boom::{Bit, Literal, NamedType, NamedValue, Operation, Size, Type, Value},
+    common::shared::Shared,
+    std::{
+        hash::{DefaultHasher, Hash, Hasher},
+        mem::discriminant,
+    },
+};
+
+/// Equality by structure rather than by identity of shared nodes
+pub trait StructuralEq {
+    fn structural_eq(&self, other: &Self) -> bool;
+}
+
+/// Hashing consistent with `StructuralEq`
+pub trait StructuralHash {
+    fn structural_hash<H: Hasher>(&self, state: &mut H);
+}
+
+/// Gets the structural hash of a value
+pub fn hash<T: StructuralHash + ?Sized>(value: &T) -> u64 {
+    let mut state = DefaultHasher::new();
+    value.structural_hash(&mut state);
+    state.finish()
+}
+
+impl<T: StructuralEq> StructuralEq for Shared<T> {
+    fn structural_eq(&self, other: &Self) -> bool {
+        // also avoids locking the same node twice
+        Shared::ptr_eq(self, other) || self.get().structural_eq(&other.get())
+    }
+}
+
+impl<T: StructuralHash> StructuralHash for Shared<T> {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        self.get().structural_hash(state);
+    }
+}
+
+impl<T: StructuralEq> StructuralEq for [T] {
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.structural_eq(b))
+    }
+}
+
+impl<T: StructuralHash> StructuralHash for [T] {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        self.iter()
+            .for_each(|element| element.structural_hash(state));
+    }
+}
+
+impl StructuralEq for Value {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Identifier(a), Value::Identifier(b)) => a == b,
+            (Value::Literal(a), Value::Literal(b)) => a.structural_eq(b),
+            (Value::Operation(a), Value::Operation(b)) => a.structural_eq(b),
+            (
+                Value::Struct {
+                    name: a_name,
+                    fields: a_fields,
+                },
+                Value::Struct {
+                    name: b_name,
+                    fields: b_fields,
+                },
+            ) => a_name == b_name && a_fields.structural_eq(b_fields),
+            (
+                Value::Field {
+                    value: a,
+                    field_name: a_name,
+                },
+                Value::Field {
+                    value: b,
+                    field_name: b_name,
+                },
+            ) => a_name == b_name && a.structural_eq(b),
+            (
+                Value::CtorKind {
+                    value: a,
+                    identifier: a_ident,
+                    types: a_types,
+                },
+                Value::CtorKind {
+                    value: b,
+                    identifier: b_ident,
+                    types: b_types,
+                },
+            )
+            | (
+                Value::CtorUnwrap {
+                    value: a,
+                    identifier: a_ident,
+                    types: a_types,
+                },
+                Value::CtorUnwrap {
+                    value: b,
+                    identifier: b_ident,
+                    types: b_types,
+                },
+            ) => a_ident == b_ident && a_types.structural_eq(b_types) && a.structural_eq(b),
+            (Value::Tuple(a), Value::Tuple(b)) => a.structural_eq(b),
+            (
+                Value::TupleMember {
+                    value: a,
+                    index: a_index,
+                },
+                Value::TupleMember {
+                    value: b,
+                    index: b_index,
+                },
+            ) => a_index == b_index && a.structural_eq(b),
+            _ => false,
+        }
+    }
+}
+
+impl StructuralHash for Value {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        discriminant(self).hash(state);
+
+        match self {
+            Value::Identifier(ident) => ident.hash(state),
+            Value::Literal(literal) => literal.structural_hash(state),
+            Value::Operation(operation) => operation.structural_hash(state),
+            Value::Struct { name, fields } => {
+                name.hash(state);
+                fields.structural_hash(state);
+            }
+            Value::Field { value, field_name } => {
+                field_name.hash(state);
+                value.structural_hash(state);
+            }
+            Value::CtorKind {
+                value,
+                identifier,
+                types,
+            }
+            | Value::CtorUnwrap {
+                value,
+                identifier,
+                types,
+            } => {
+                identifier.hash(state);
+                types.structural_hash(state);
+                value.structural_hash(state);
+            }
+            Value::Tuple(values) => values.structural_hash(state),
+            Value::TupleMember { value, index } => {
+                index.hash(state);
+                value.structural_hash(state);
+            }
+        }
+    }
+}
+
+impl StructuralEq for NamedValue {
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.value.structural_eq(&other.value)
+    }
+}
+
+impl StructuralHash for NamedValue {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.value.structural_hash(state);
+    }
+}
+
+impl StructuralEq for Literal {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Literal::Int(a), Literal::Int(b)) => a == b,
+            (Literal::Bits(a), Literal::Bits(b)) => a == b,
+            (Literal::Bit(a), Literal::Bit(b)) => a == b,
+            (Literal::Bool(a), Literal::Bool(b)) => a == b,
+            (Literal::String(a), Literal::String(b))
+            | (Literal::Reference(a), Literal::Reference(b)) => a == b,
+            (Literal::Unit, Literal::Unit) | (Literal::Undefined, Literal::Undefined) => true,
+            _ => false,
+        }
+    }
+}
+
+impl StructuralHash for Literal {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        discriminant(self).hash(state);
+
+        match self {
+            Literal::Int(int) => int.hash(state),
+            Literal::Bits(bits) => bits.iter().for_each(|bit| hash_bit(bit, state)),
+            Literal::Bit(bit) => hash_bit(bit, state),
+            Literal::Bool(bool) => bool.hash(state),
+            Literal::String(str) | Literal::Reference(str) => str.hash(state),
+            Literal::Unit | Literal::Undefined => (),
+        }
+    }
+}
+
+fn hash_bit<H: Hasher>(bit: &Bit, state: &mut H) {
+    discriminant(bit).hash(state);
+}
+
+impl StructuralEq for Operation {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Operation::Cast(a, a_typ), Operation::Cast(b, b_typ)) => {
+                a_typ.structural_eq(b_typ) && a.structural_eq(b)
+            }
+            _ => {
+                discriminant(self) == discriminant(other)
+                    && operands(self).structural_eq(&operands(other))
+            }
+        }
+    }
+}
+
+impl StructuralHash for Operation {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        discriminant(self).hash(state);
+        operands(self).structural_hash(state);
+
+        if let Operation::Cast(_, typ) = self {
+            typ.structural_hash(state);
+        }
+    }
+}
+
+/// Gets the value operands of an operation, in order
+fn operands(operation: &Operation) -> Vec<Shared<Value>> {
+    match operation {
+        Operation::Not(value) | Operation::Complement(value) | Operation::Cast(value, _) => {
+            vec![value.clone()]
+        }
+        Operation::Equal(lhs, rhs)
+        | Operation::NotEqual(lhs, rhs)
+        | Operation::LessThan(lhs, rhs)
+        | Operation::LessThanOrEqual(lhs, rhs)
+        | Operation::GreaterThan(lhs, rhs)
+        | Operation::GreaterThanOrEqual(lhs, rhs)
+        | Operation::Subtract(lhs, rhs)
+        | Operation::Add(lhs, rhs)
+        | Operation::Or(lhs, rhs)
+        | Operation::Multiply(lhs, rhs)
+        | Operation::And(lhs, rhs)
+        | Operation::Xor(lhs, rhs)
+        | Operation::Divide(lhs, rhs)
+        | Operation::LogicalAnd(lhs, rhs)
+        | Operation::LogicalOr(lhs, rhs)
+        | Operation::LeftShift(lhs, rhs)
+        | Operation::RightShift(lhs, rhs)
+        | Operation::ArithmeticRightShift(lhs, rhs)
+        | Operation::RotateRight(lhs, rhs)
+        | Operation::RotateLeft(lhs, rhs) => vec![lhs.clone(), rhs.clone()],
+    }
+}
+
+impl StructuralEq for Type {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            // ranges are analysis results rather than part of the type
+            (Type::Integer { size: a, .. }, Type::Integer { size: b, .. })
+            | (Type::Bits { size: a }, Type::Bits { size: b }) => a.structural_eq(b),
+            (Type::Constant(a), Type::Constant(b)) => a == b,
+            (
+                Type::Enum {
+                    name: a_name,
+                    variants: a_variants,
+                },
+                Type::Enum {
+                    name: b_name,
+                    variants: b_variants,
+                },
+            ) => a_name == b_name && a_variants == b_variants,
+            (
+                Type::Union {
+                    name: a_name,
+                    fields: a_fields,
+                },
+                Type::Union {
+                    name: b_name,
+                    fields: b_fields,
+                },
+            )
+            | (
+                Type::Struct {
+                    name: a_name,
+                    fields: a_fields,
+                },
+                Type::Struct {
+                    name: b_name,
+                    fields: b_fields,
+                },
+            ) => a_name == b_name && a_fields.structural_eq(b_fields),
+            (Type::List { element_type: a }, Type::List { element_type: b })
+            | (Type::Vector { element_type: a }, Type::Vector { element_type: b })
+            | (Type::Reference(a), Type::Reference(b)) => a.structural_eq(b),
+            (
+                Type::FixedVector {
+                    length: a_length,
+                    element_type: a,
+                },
+                Type::FixedVector {
+                    length: b_length,
+                    element_type: b,
+                },
+            ) => a_length == b_length && a.structural_eq(b),
+            (Type::Tuple(a), Type::Tuple(b)) => a.structural_eq(b),
+            (Type::Unit, Type::Unit)
+            | (Type::String, Type::String)
+            | (Type::Bool, Type::Bool)
+            | (Type::Bit, Type::Bit)
+            | (Type::Real, Type::Real)
+            | (Type::Float, Type::Float) => true,
+            _ => false,
+        }
+    }
+}
+
+impl StructuralHash for Type {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        discriminant(self).hash(state);
+
+        match self {
+            Type::Integer { size, .. } | Type::Bits { size } => size.structural_hash(state),
+            Type::Constant(constant) => constant.hash(state),
+            Type::Enum { name, variants } => {
+                name.hash(state);
+                variants.hash(state);
+            }
+            Type::Union { name, fields } | Type::Struct { name, fields } => {
+                name.hash(state);
+                fields.structural_hash(state);
+            }
+            Type::List { element_type }
+            | Type::Vector { element_type }
+            | Type::Reference(element_type) => element_type.structural_hash(state),
+            Type::FixedVector {
+                length,
+                element_type,
+            } => {
+                length.hash(state);
+                element_type.structural_hash(state);
+            }
+            Type::Tuple(elements) => elements.structural_hash(state),
+            Type::Unit | Type::String | Type::Bool | Type::Bit | Type::Real | Type::Float => (),
+        }
+    }
+}
+
+impl StructuralEq for NamedType {
+    fn structural_eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.typ.structural_eq(&other.typ)
+    }
+}
+
+impl StructuralHash for NamedType {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.typ.structural_hash(state);
+    }
+}
+
+impl StructuralEq for Size {
+    fn structural_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Size::Static(a), Size::Static(b)) => a == b,
+            (Size::Runtime(a), Size::Runtime(b)) => a.structural_eq(b),
+            (Size::Unknown, Size::Unknown) => true,
+            _ => false,
+        }
+    }
+}
+
+impl StructuralHash for Size {
+    fn structural_hash<H: Hasher>(&self, state: &mut H) {
+        discriminant(self).hash(state);
+
+        match self {
+            Size::Static(length) => length.hash(state),
+            Size::Runtime(length) => length.structural_hash(state),
+            Size::Unknown => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            structural::{self, StructuralEq},
+            test_utils::*,
+            Operation, Size, Type, Value,
+        },
+        common::shared::Shared,
+    };
+
+    /// `(x >> 4) & 15`, built from fresh nodes each time
+    fn slice() -> Shared<Value> {
+        Operation::And(Operation::RightShift(ident("x"), int(4)).into(), int(15)).into()
+    }
+
+    #[test]
+    fn identical_trees() {
+        init();
+
+        let (a, b) = (slice(), slice());
+        assert!(!Shared::ptr_eq(&a, &b));
+
+        assert!(a.structural_eq(&b));
+        assert_eq!(structural::hash(&a), structural::hash(&b));
+    }
+
+    #[test]
+    fn differing_trees() {
+        init();
+
+        let shifted: Shared<Value> =
+            Operation::And(Operation::RightShift(ident("x"), int(5)).into(), int(15)).into();
+        let other_source: Shared<Value> =
+            Operation::And(Operation::RightShift(ident("y"), int(4)).into(), int(15)).into();
+        let other_operation: Shared<Value> =
+            Operation::Or(Operation::RightShift(ident("x"), int(4)).into(), int(15)).into();
+
+        for other in [shifted, other_source, other_operation] {
+            assert!(!slice().structural_eq(&other));
+            assert_ne!(structural::hash(&slice()), structural::hash(&other));
+        }
+    }
+
+    #[test]
+    fn casts_compare_types() {
+        init();
+
+        let cast = |size| -> Shared<Value> {
+            Operation::Cast(ident("x"), Shared::new(Type::Bits { size })).into()
+        };
+
+        assert!(cast(Size::Static(8)).structural_eq(&cast(Size::Static(8))));
+        assert!(!cast(Size::Static(8)).structural_eq(&cast(Size::Static(16))));
+        assert!(cast(Size::Runtime(ident("n"))).structural_eq(&cast(Size::Runtime(ident("n")))));
+    }
+}
@@ -0,0 +1,142 @@
+//! Helpers for constructing BOOM ASTs in tests
+
+use {
+    crate::boom::{
+        control_flow::ControlFlowBlock, pretty_print::print_value, Ast, Bit, Expression,
+        FunctionDefinition, FunctionSignature, Literal, Position, Size, Span, Statement, Type,
+        Value,
+    },
+    common::{
+        intern::{init_interner, InternedString},
+        shared::Shared,
+        HashMap,
+    },
+    num_bigint::BigInt,
+    std::sync::Once,
+};
+
+/// Initializes the global interner exactly once for all tests
+pub fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| init_interner(&HashMap::default()));
+}
+
+pub fn ident(name: &str) -> Shared<Value> {
+    Shared::new(Value::Identifier(name.into()))
+}
+
+pub fn int<I: Into<BigInt>>(value: I) -> Shared<Value> {
+    Literal::Int(value.into()).into()
+}
+
+pub fn string(value: &str) -> Shared<Value> {
+    Literal::String(value.into()).into()
+}
+
+/// Creates a bit literal from a string of binary digits, most significant first
+pub fn bits_literal(digits: &str) -> Shared<Value> {
+    Literal::Bits(
+        digits
+            .chars()
+            .rev()
+            .map(|digit| match digit {
+                '0' => Bit::Zero,
+                '1' => Bit::One,
+                _ => Bit::Unknown,
+            })
+            .collect(),
+    )
+    .into()
+}
+
+pub fn bits(size: Size) -> Shared<Type> {
+    Shared::new(Type::Bits { size })
+}
+
+pub fn declare(name: &str, typ: Shared<Type>) -> Shared<Statement> {
+    Statement::TypeDeclaration {
+        name: name.into(),
+        typ,
+    }
+    .into()
+}
+
+pub fn copy(destination: &str, value: Shared<Value>) -> Shared<Statement> {
+    Statement::Copy {
+        expression: Expression::Identifier(destination.into()),
+        value,
+    }
+    .into()
+}
+
+pub fn call(destination: &str, name: &str, arguments: Vec<Shared<Value>>) -> Shared<Statement> {
+    Statement::FunctionCall {
+        expression: Some(Expression::Identifier(destination.into())),
+        name: name.into(),
+        arguments,
+    }
+    .into()
+}
+
+/// Creates a function with no parameters and a single block containing the
+/// supplied statements
+pub fn function(name: &str, statements: Vec<Shared<Statement>>) -> FunctionDefinition {
+    let entry_block = ControlFlowBlock::new();
+    entry_block.set_statements(statements);
+
+    FunctionDefinition {
+        signature: FunctionSignature {
+            name: name.into(),
+            parameters: Shared::new(vec![]),
+            return_type: Shared::new(Type::Unit),
+        },
+        entry_block,
+    }
+}
+
+pub fn ast<I: IntoIterator<Item = FunctionDefinition>>(functions: I) -> Shared<Ast> {
+    Shared::new(Ast {
+        functions: functions
+            .into_iter()
+            .map(|def| (def.signature.name, def))
+            .collect(),
+        ..Default::default()
+    })
+}
+
+/// Creates a span covering the first column of a line of `model.sail`
+pub fn span(line: usize) -> Span {
+    let position = Position { line, column: 1 };
+
+    Span {
+        file: "model.sail".into(),
+        start: position,
+        end: position,
+    }
+}
+
+/// Gets the size of the local `name` declared in function `func`
+pub fn size_of(ast: &Shared<Ast>, func: &str, name: &str) -> Option<Size> {
+    ast.get()
+        .functions
+        .get(&InternedString::from(func))
+        .unwrap()
+        .get_ident_type(name.into())
+        .and_then(|typ| typ.get_size())
+}
+
+/// Renders a value with the BOOM pretty-printer
+pub fn render(value: Shared<Value>) -> String {
+    let mut buf = vec![];
+    print_value(&mut buf, value);
+    String::from_utf8(buf).unwrap()
+}
+
+/// Renders the value copied by a statement, panicking if it is not a copy
+pub fn copied_value(statement: &Shared<Statement>) -> String {
+    let Statement::Copy { value, .. } = &*statement.get() else {
+        panic!("statement is not a copy: {:?}", *statement.get());
+    };
+
+    render(value.clone())
+}
@@ -54,10 +54,22 @@ pub trait Visitor: Sized {
         node.walk(self);
     }
 
+    /// Visits a value, calling `visit_value_pre` before its children and
+    /// `visit_value_post` after them
     fn visit_value(&mut self, node: Shared<Value>) {
+        self.visit_value_pre(node.clone());
         node.get().walk(self);
+        self.visit_value_post(node);
     }
 
+    /// Visits a value before its children, such as to propagate information
+    /// downwards
+    fn visit_value_pre(&mut self, _node: Shared<Value>) {}
+
+    /// Visits a value after its children, such as to rewrite it using the
+    /// already rewritten children
+    fn visit_value_post(&mut self, _node: Shared<Value>) {}
+
     fn visit_literal(&mut self, node: Shared<Literal>) {
         node.get().walk(self);
     }
@@ -65,6 +77,23 @@ pub trait Visitor: Sized {
     fn visit_operation(&mut self, node: &Operation) {
         node.walk(self);
     }
+
+    /// Replaces a statement with the one `f` maps it to, if any, returning
+    /// whether it was replaced
+    ///
+    /// The statement is not borrowed while `f` runs, so `f` is free to read it
+    /// and the statements around it.
+    fn map_statement<F>(&mut self, node: &Shared<Statement>, f: F) -> bool
+    where
+        F: FnOnce(&mut Self) -> Option<Statement>,
+    {
+        let Some(statement) = f(self) else {
+            return false;
+        };
+
+        *node.get_mut() = statement;
+        true
+    }
 }
 
 /// Trait encapsulating the traversing logic for the AST
@@ -72,3 +101,54 @@ pub trait Walkable {
     /// Visit children of the current node
     fn walk<V: Visitor>(&self, visitor: &mut V);
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::boom::{
+            test_utils::*,
+            visitor::{Visitor, Walkable},
+            Operation, Value,
+        },
+        common::shared::Shared,
+        pretty_assertions::assert_eq,
+    };
+
+    #[derive(Default)]
+    struct Order {
+        pre: Vec<String>,
+        post: Vec<String>,
+    }
+
+    impl Visitor for Order {
+        fn visit_value_pre(&mut self, node: Shared<Value>) {
+            self.pre.push(node.get().to_string());
+        }
+
+        fn visit_value_post(&mut self, node: Shared<Value>) {
+            self.post.push(node.get().to_string());
+        }
+    }
+
+    #[test]
+    fn pre_and_post_order() {
+        init();
+
+        let value: Shared<Value> = Operation::Add(
+            Operation::Multiply(ident("a"), ident("b")).into(),
+            ident("c"),
+        )
+        .into();
+
+        let mut order = Order::default();
+        order.visit_value(value.clone());
+
+        assert_eq!(order.pre, ["((a * b) + c)", "(a * b)", "a", "b", "c"]);
+        assert_eq!(order.post, ["a", "b", "(a * b)", "c", "((a * b) + c)"]);
+
+        // walking a node visits its children only
+        let mut order = Order::default();
+        value.get().walk(&mut order);
+        assert_eq!(order.post, ["a", "b", "(a * b)", "c"]);
+    }
+}
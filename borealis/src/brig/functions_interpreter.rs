@@ -467,9 +467,16 @@ pub fn codegen_stmt(stmt: Statement) -> TokenStream {
             let length = get_ident(&length);
             quote!(Bits::new(#value, #length))
         }
-        StatementKind::Assert { condition } => {
+        StatementKind::Assert { condition, message } => {
             let condition = get_ident(&condition);
-            quote!(assert!(#condition))
+
+            match message {
+                Some(message) => {
+                    let message = message.as_ref();
+                    quote!(assert!(#condition, "{}", #message))
+                }
+                None => quote!(assert!(#condition)),
+            }
         }
         StatementKind::BitsCast {
             kind,
@@ -6,9 +6,10 @@ use {
             self,
             passes::{
                 self, cycle_finder::CycleFinder, fold_unconditionals::FoldUnconditionals,
+                lower_match::LowerMatch, lower_tuples::LowerTuples,
                 make_exception_panic::MakeExceptionPanic,
                 monomorphize_vectors::MonomorphizeVectors, remove_const_branch::RemoveConstBranch,
-                resolve_return_assigns::ResolveReturns,
+                resolve_return_assigns::ResolveReturns, Severity,
             },
             Ast,
         },
@@ -89,9 +90,11 @@ pub fn sail_to_brig(jib_ast: ListVec<jib_ast::Definition>, path: PathBuf, mode:
     }
 
     info!("Running passes on BOOM");
-    passes::run_fixed_point(
+    let diagnostics = passes::run_fixed_point(
         ast.clone(),
         &mut [
+            LowerTuples::new_boxed(),
+            LowerMatch::new_boxed(),
             FoldUnconditionals::new_boxed(),
             RemoveConstBranch::new_boxed(),
             ResolveReturns::new_boxed(),
@@ -99,13 +102,26 @@ pub fn sail_to_brig(jib_ast: ListVec<jib_ast::Definition>, path: PathBuf, mode:
             MonomorphizeVectors::new_boxed(),
             CycleFinder::new_boxed(),
         ],
+        // the lowered AST is only valid once every pass is done
+        None,
     );
+    for diagnostic in diagnostics {
+        match diagnostic.severity {
+            Severity::Error | Severity::Warning => warn!("{diagnostic}"),
+            Severity::Info => info!("{diagnostic}"),
+        }
+    }
 
     if let Some(path) = &dump_ir {
         boom::pretty_print::print_ast(
             &mut create_file(path.join("ast.processed.boom")).unwrap(),
             ast.clone(),
         );
+        boom::size_report::export_size_report(
+            &ast.get(),
+            create_file(path.join("sizes.json")).unwrap(),
+        )
+        .unwrap();
     }
 
     info!("Building rudder");
@@ -212,7 +212,7 @@ impl StatementUseAnalysis {
                     self.add_use(&value, &stmt);
                     self.add_use(&index, &stmt);
                 }
-                StatementKind::Assert { condition } => {
+                StatementKind::Assert { condition, .. } => {
                     self.add_use(&condition, &stmt);
                 }
                 StatementKind::Panic(panic_values) => {
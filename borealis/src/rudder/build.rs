@@ -287,6 +287,7 @@ impl BuildContext {
             boom::Type::Union { name, .. } => self.unions.get(name).unwrap().0.clone(),
             boom::Type::Struct { name, .. } => self.structs.get(name).unwrap().0.clone(),
             boom::Type::List { .. } => todo!(),
+            boom::Type::Tuple(_) => panic!("tuples should have been lowered"),
             boom::Type::Vector { element_type } => {
                 let element_type = (*self.resolve_type(element_type.clone())).clone();
                 // todo: Brian Campbell said the Sail C backend had functionality to staticize
@@ -305,7 +306,7 @@ impl BuildContext {
                 // todo: this is broken:(
                 self.resolve_type(inner.clone())
             }
-            boom::Type::Integer { size } => match size {
+            boom::Type::Integer { size, .. } => match size {
                 boom::Size::Static(size) => Arc::new(rudder::Type::new_primitive(
                     rudder::PrimitiveTypeClass::SignedInteger,
                     *size,
@@ -1108,9 +1109,20 @@ impl<'ctx: 'fn_ctx, 'fn_ctx> BlockBuildContext<'ctx, 'fn_ctx> {
                     }))
                 }
 
-                "sail_assert" => Some(self.builder.build(StatementKind::Assert {
-                    condition: args[0].clone(),
-                })),
+                "sail_assert" => {
+                    let message = match args[1].kind() {
+                        StatementKind::Constant {
+                            value: ConstantValue::String(message),
+                            ..
+                        } => Some(message),
+                        _ => None,
+                    };
+
+                    Some(self.builder.build(StatementKind::Assert {
+                        condition: args[0].clone(),
+                        message,
+                    }))
+                }
 
                 "write_gpr_from_vector" => {
                     // todo assert args[2] is always "GPRs"
@@ -1735,6 +1747,9 @@ impl<'ctx: 'fn_ctx, 'fn_ctx> BlockBuildContext<'ctx, 'fn_ctx> {
                     unwrap_sum
                 }
             }
+            boom::Value::Tuple(_) | boom::Value::TupleMember { .. } => {
+                panic!("tuples should have been lowered")
+            }
         }
     }
 
@@ -1743,7 +1758,10 @@ impl<'ctx: 'fn_ctx, 'fn_ctx> BlockBuildContext<'ctx, 'fn_ctx> {
             boom::Literal::Int(i) => StatementKind::Constant {
                 typ: Arc::new(Type::ArbitraryLengthInteger),
                 value: rudder::ConstantValue::SignedInteger(
-                    i.try_into().unwrap_or_else(|_| panic!("{i:x?}")),
+                    // values of the unsigned backing value above isize::MAX keep their bits
+                    isize::try_from(i)
+                        .or_else(|_| u64::try_from(i).map(|i| i as isize))
+                        .unwrap_or_else(|_| panic!("{i:x?}")),
                 ),
             },
             boom::Literal::Bits(bits) => StatementKind::Constant {
@@ -1823,6 +1841,7 @@ impl<'ctx: 'fn_ctx, 'fn_ctx> BlockBuildContext<'ctx, 'fn_ctx> {
 
             boom::Operation::LeftShift(value, amount)
             | boom::Operation::RightShift(value, amount)
+            | boom::Operation::ArithmeticRightShift(value, amount)
             | boom::Operation::RotateRight(value, amount)
             | boom::Operation::RotateLeft(value, amount) => {
                 let value = self.build_value(value.clone());
@@ -1834,6 +1853,9 @@ impl<'ctx: 'fn_ctx, 'fn_ctx> BlockBuildContext<'ctx, 'fn_ctx> {
                         // todo figure out if logical or arithmetic
                         ShiftOperationKind::LogicalShiftRight
                     }
+                    boom::Operation::ArithmeticRightShift(_, _) => {
+                        ShiftOperationKind::ArithmeticShiftRight
+                    }
                     boom::Operation::RotateRight(_, _) => ShiftOperationKind::RotateRight,
                     boom::Operation::RotateLeft(_, _) => ShiftOperationKind::RotateLeft,
 
@@ -1859,7 +1881,9 @@ impl<'ctx: 'fn_ctx, 'fn_ctx> BlockBuildContext<'ctx, 'fn_ctx> {
             | boom::Operation::Multiply(left, right)
             | boom::Operation::And(left, right)
             | boom::Operation::Xor(left, right)
-            | boom::Operation::Divide(left, right) => {
+            | boom::Operation::Divide(left, right)
+            | boom::Operation::LogicalAnd(left, right)
+            | boom::Operation::LogicalOr(left, right) => {
                 let mut lhs = self.build_value(left.clone());
                 let mut rhs = self.build_value(right.clone());
 
@@ -1898,6 +1922,10 @@ impl<'ctx: 'fn_ctx, 'fn_ctx> BlockBuildContext<'ctx, 'fn_ctx> {
                         boom::Operation::And(_, _) => BinaryOperationKind::And,
                         boom::Operation::Xor(_, _) => BinaryOperationKind::Xor,
                         boom::Operation::Divide(_, _) => BinaryOperationKind::Divide,
+                        // operands are booleans without side effects, so need not be
+                        // short-circuited
+                        boom::Operation::LogicalAnd(_, _) => BinaryOperationKind::And,
+                        boom::Operation::LogicalOr(_, _) => BinaryOperationKind::Or,
 
                         _ => unreachable!(),
                     },
@@ -1986,6 +2014,7 @@ fn expression_field_collapse(expression: &boom::Expression) -> Vec<InternedStrin
                 current_expression = &expression;
             }
             boom::Expression::Address(_) => panic!("addresses not supported"),
+            boom::Expression::TupleMember { .. } => panic!("tuples should have been lowered"),
         }
     }
 }
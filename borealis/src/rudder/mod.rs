@@ -410,8 +410,11 @@ pub enum StatementKind {
     /// purposes
     Panic(Vec<Statement>),
 
+    /// Runtime check of a Sail assertion, with its message if it was a
+    /// constant string
     Assert {
         condition: Statement,
+        message: Option<InternedString>,
     },
 
     CreateProduct {
@@ -875,9 +878,10 @@ impl StatementInner {
                 };
             }
 
-            StatementKind::Assert { .. } => {
+            StatementKind::Assert { message, .. } => {
                 self.kind = StatementKind::Assert {
                     condition: with.clone(),
+                    message,
                 };
             }
             StatementKind::ShiftOperation {
@@ -171,8 +171,9 @@ fn clone_statement(
 
             builder.build(StatementKind::Panic(stmts))
         }
-        StatementKind::Assert { condition } => builder.build(StatementKind::Assert {
+        StatementKind::Assert { condition, message } => builder.build(StatementKind::Assert {
             condition: mapping.get(&condition).unwrap().clone(),
+            message,
         }),
         StatementKind::CreateProduct { typ, fields } => {
             let fields = fields
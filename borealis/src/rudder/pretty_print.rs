@@ -281,8 +281,14 @@ impl Display for StatementKind {
             StatementKind::SizeOf { value } => {
                 write!(f, "size-of {}", value.name())
             }
-            StatementKind::Assert { condition } => {
-                write!(f, "assert {}", condition.name())
+            StatementKind::Assert { condition, message } => {
+                write!(f, "assert {}", condition.name())?;
+
+                if let Some(message) = message {
+                    write!(f, " {message:?}")?;
+                }
+
+                Ok(())
             }
 
             StatementKind::CreateBits { value, length } => {
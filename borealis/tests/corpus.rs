@@ -0,0 +1,43 @@
+//! Regression corpus of decode functions in the shape of the ARMv8 model
+//!
+//! `tests/corpus/armv8_decode.json` holds BOOM decode and execute functions
+//! minimized by hand to the builtins the ARMv8 decoders depend on: `Zeros`,
+//! `Ones`, `bitvector_concat`, `eq_vec` and `undefined_bitvector`, with every
+//! local declared of unknown length. Each optimization level is run over the
+//! corpus, and must resolve every length without reporting an error.
+
+use {
+    borealis::boom::{
+        passes::{
+            manager::{OptLevel, PassManager},
+            Severity,
+        },
+        Ast,
+    },
+    common::{intern::init_interner, shared::Shared, HashMap},
+    std::{fs, path::PathBuf},
+};
+
+fn load(name: &str) -> Shared<Ast> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/corpus")
+        .join(format!("{name}.json"));
+
+    Shared::new(serde_json::from_slice::<Ast>(&fs::read(path).unwrap()).unwrap())
+}
+
+#[test]
+fn armv8_decode() {
+    init_interner(&HashMap::default());
+
+    for level in [OptLevel::None, OptLevel::Basic, OptLevel::Aggressive] {
+        let ast = load("armv8_decode");
+
+        let diagnostics = PassManager::with_opt_level(level).run_to_fixpoint(ast);
+        let errors = diagnostics
+            .iter()
+            .filter(|diagnostic| matches!(diagnostic.severity, Severity::Error))
+            .collect::<Vec<_>>();
+        assert!(errors.is_empty(), "{level:?}: {errors:?}");
+    }
+}
@@ -0,0 +1,68 @@
+//! Golden-file tests of pass output
+//!
+//! Each fixture in `tests/snapshot` is a BOOM AST serialized as JSON. A
+//! pipeline of passes is run over it to a fixed point, and the functions are
+//! pretty-printed in a stable order and compared with the `.expected` file of
+//! the same name. Run with `UPDATE_SNAPSHOTS=1` to write the current output as
+//! the expected output instead.
+
+use {
+    borealis::boom::{
+        passes::{
+            self,
+            resolve_bitvectors::{ResolveBitvectors, SizeConflictPolicy},
+            Pass,
+        },
+        pretty_print::print_functions_stable,
+        Ast,
+    },
+    common::{intern::init_interner, shared::Shared, HashMap},
+    pretty_assertions::assert_eq,
+    std::{env, fs, path::PathBuf},
+};
+
+/// Runs the passes created by `pipeline` over the fixture `name`, comparing
+/// the result with its expected output
+///
+/// Passes are created after the interner is initialized, as builtin handlers
+/// intern their names.
+fn snapshot(name: &str, pipeline: fn() -> Vec<Box<dyn Pass>>) {
+    init_interner(&HashMap::default());
+
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshot");
+    let fixture = dir.join(format!("{name}.json"));
+    let expected_path = dir.join(format!("{name}.expected"));
+
+    let ast = serde_json::from_slice::<Ast>(&fs::read(&fixture).unwrap()).unwrap();
+    let ast = Shared::new(ast);
+
+    let diagnostics = passes::run_fixed_point(ast.clone(), &mut pipeline(), None);
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+    let mut buf = vec![];
+    print_functions_stable(&mut buf, ast);
+    let actual = String::from_utf8(buf).unwrap();
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&expected_path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&expected_path).unwrap_or_else(|_| {
+        panic!(
+            "missing {}, run with UPDATE_SNAPSHOTS=1 to create it",
+            expected_path.display()
+        )
+    });
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn resolve_bitvectors() {
+    snapshot("resolve_bitvectors", || {
+        vec![ResolveBitvectors::new_boxed(
+            SizeConflictPolicy::default(),
+            None,
+        )]
+    });
+}
@@ -64,6 +64,27 @@ impl<T> Shared<T> {
     }
 }
 
+/// Serializes the shared data itself, sharing is not preserved: each
+/// reference is serialized separately and deserializes to a distinct
+/// allocation
+impl<T: serde::Serialize> serde::Serialize for Shared<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.get().serialize(serializer)
+    }
+}
+
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Shared<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(Self::new)
+    }
+}
+
 /// Weak reference to a `Shared``
 #[derive(Debug)]
 pub struct Weak<T> {